@@ -41,15 +41,64 @@ export const api = {
   updateProfile(data) {
     return request('/me', { method: 'PATCH', data });
   },
+  deleteAccount(data) {
+    return request('/me', { method: 'DELETE', data });
+  },
+  cancelAccountDeletion() {
+    return request('/me/deletion', { method: 'DELETE' });
+  },
   changePassword(data) {
     return request('/me/password', { method: 'POST', data });
   },
   myPosts() {
     return request('/me/posts');
   },
-  listPosts(category) {
-    const query = category ? `?category=${encodeURIComponent(category)}` : '';
-    return request(`/posts${query}`);
+  bookmarks() {
+    return request('/me/bookmarks');
+  },
+  drafts() {
+    return request('/me/drafts');
+  },
+  createDraft(data) {
+    return request('/me/drafts', { method: 'POST', data });
+  },
+  saveDraft(id, data) {
+    return request(`/me/drafts/${id}`, { method: 'PUT', data });
+  },
+  deleteDraft(id) {
+    return request(`/me/drafts/${id}`, { method: 'DELETE' });
+  },
+  publishDraft(id) {
+    return request(`/me/drafts/${id}/publish`, { method: 'POST' });
+  },
+  notifications() {
+    return request('/me/notifications');
+  },
+  markNotificationsRead() {
+    return request('/me/notifications/read', { method: 'POST' });
+  },
+  privacy() {
+    return request('/me/privacy');
+  },
+  updatePrivacy(data) {
+    return request('/me/privacy', { method: 'PATCH', data });
+  },
+  listPosts(category, { feed, tag, sort, page } = {}) {
+    const params = new URLSearchParams();
+    if (sort) params.set('sort', sort);
+    if (category) params.set('category', category);
+    if (feed) params.set('feed', feed);
+    if (tag) params.set('tag', tag);
+    if (page) params.set('page', page);
+    const query = params.toString();
+    return request(`/posts${query ? `?${query}` : ''}`);
+  },
+  archive(category, { page } = {}) {
+    const params = new URLSearchParams();
+    if (category) params.set('category', category);
+    if (page) params.set('page', page);
+    const query = params.toString();
+    return request(`/archive${query ? `?${query}` : ''}`);
   },
   createPost(data) {
     return request('/posts', { method: 'POST', data });
@@ -60,13 +109,112 @@ export const api = {
   comment(postId, data) {
     return request(`/posts/${postId}/comments`, { method: 'POST', data });
   },
+  votePoll(postId, optionIds) {
+    return request(`/posts/${postId}/poll/vote`, { method: 'POST', data: { option_ids: optionIds } });
+  },
+  subscribe(postId) {
+    return request(`/posts/${postId}/subscription`, { method: 'POST' });
+  },
+  unsubscribe(postId) {
+    return request(`/posts/${postId}/subscription`, { method: 'DELETE' });
+  },
+  muteSubscription(postId, muted) {
+    return request(`/posts/${postId}/subscription`, { method: 'PATCH', data: { muted } });
+  },
+  bookmark(postId) {
+    return request(`/posts/${postId}/bookmark`, { method: 'POST' });
+  },
+  unbookmark(postId) {
+    return request(`/posts/${postId}/bookmark`, { method: 'DELETE' });
+  },
+  trendingTags() {
+    return request('/tags/trending');
+  },
+  categories() {
+    return request('/categories');
+  },
+  adminCategories() {
+    return request('/admin/categories');
+  },
+  createCategory(data) {
+    return request('/admin/categories', { method: 'POST', data });
+  },
+  updateCategory(slug, data) {
+    return request(`/admin/categories/${encodeURIComponent(slug)}`, { method: 'PATCH', data });
+  },
+  deleteCategory(slug) {
+    return request(`/admin/categories/${encodeURIComponent(slug)}`, { method: 'DELETE' });
+  },
+  adminPin(postId, data) {
+    return request(`/admin/posts/${postId}/pin`, { method: 'POST', data });
+  },
+  adminUnpin(postId) {
+    return request(`/admin/posts/${postId}/pin`, { method: 'DELETE' });
+  },
   adminDelete(postId) {
     return request(`/admin/posts/${postId}`, { method: 'DELETE' });
   },
+  adminUsers(q) {
+    const params = new URLSearchParams();
+    if (q) params.set('q', q);
+    const query = params.toString();
+    return request(`/admin/users${query ? `?${query}` : ''}`);
+  },
+  adminUser(uid) {
+    return request(`/admin/users/${uid}`);
+  },
+  adminResetPassword(uid) {
+    return request(`/admin/users/${uid}/reset-password`, { method: 'POST' });
+  },
+  adminRevokeSessions(uid) {
+    return request(`/admin/users/${uid}/sessions`, { method: 'DELETE' });
+  },
+  adminRevealAuthor(kind, id, reason) {
+    const params = new URLSearchParams({ reason });
+    return request(`/admin/${kind}/${id}/author?${params}`);
+  },
+  adminAudit() {
+    return request('/admin/audit');
+  },
+  adminDeleteComment(commentId) {
+    return request(`/admin/comments/${commentId}`, { method: 'DELETE' });
+  },
+  adminBulkModerate(data) {
+    return request('/admin/moderation/bulk', { method: 'POST', data });
+  },
+  adminBackups() {
+    return request('/admin/backups');
+  },
+  adminCreateBackup() {
+    return request('/admin/backups', { method: 'POST' });
+  },
+  adminMovePosts(postIds, category) {
+    return request('/admin/moderation/move', { method: 'POST', data: { post_ids: postIds, category } });
+  },
   publicProfile(uid) {
     return request(`/users/${uid}`);
+  },
+  follow(uid) {
+    return request(`/users/${uid}/follow`, { method: 'POST' });
+  },
+  unfollow(uid) {
+    return request(`/users/${uid}/follow`, { method: 'DELETE' });
   }
 };
 
-export const CATEGORIES = ['扩列', '吐槽', '表白', '提问', '其它'];
-export const ALL_BOARDS = ['最新', ...CATEGORIES];
+export const EXPORT_URL = `${API_BASE}/me/export`;
+
+export const FOLLOWING_FEED = 'following';
+export const PAGE_SIZE = 20;
+export const SORT_OPTIONS = [
+  { value: 'new', label: '最新发布' },
+  { value: 'active', label: '最新回复' },
+  { value: 'comments', label: '评论最多' },
+  { value: 'hot', label: '热门' }
+];
+export const LATEST_BOARD = '最新';
+export const QQ_VISIBILITY_OPTIONS = [
+  { value: 'everyone', label: '所有人可见' },
+  { value: 'following', label: '仅我关注的人可见' },
+  { value: 'nobody', label: '仅自己可见' }
+];
@@ -1,4 +1,13 @@
-use std::{fs, net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env, fs,
+    io::ErrorKind,
+    net::{IpAddr, SocketAddr},
+    path::{Path as FsPath, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    time::{Duration as StdDuration, Instant, SystemTime},
+};
 
 use argon2::{
     password_hash::{
@@ -6,31 +15,253 @@ use argon2::{
     },
     Argon2,
 };
+use async_trait::async_trait;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{ConnectInfo, FromRequest, FromRequestParts, MatchedPath, Path, Request, State},
+    http::request::Parts,
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::{delete, get, post},
+    routing::{delete, get, patch, post},
     Json, Router,
 };
-use axum_extra::extract::cookie::{Cookie, CookieJar, CookieLayer};
+use clap::{Parser, Subcommand};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
 use rand_core::OsRng;
-use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, SqlitePool};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPool, PgPoolOptions},
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+    ConnectOptions, Connection, FromRow, PgConnection, Row, SqliteConnection, SqlitePool,
+};
 use thiserror::Error;
 use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
-use tokio::net::TcpListener;
+use tokio::{net::TcpListener, sync::watch, task::JoinSet};
+use toml_edit::{Array, DocumentMut};
 use tower_cookies::{Cookie, CookieManagerLayer, Cookies};
-use tower_http::services::{ServeDir, ServeFile};
-use tracing::{info, warn};
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::{ServeDir, ServeFile},
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::{debug, error, field, info, info_span, warn, Level, Span};
 use tracing_subscriber::EnvFilter;
 use uuid::Uuid;
 
+const CONFIG_PATH: &str = "config.toml";
+const CONFIG_ENV_PREFIX: &str = "TALK_WALL__";
+const DEFAULT_SERVER_ADDR: &str = "0.0.0.0:8080";
+const CONFIG_POLL_INTERVAL_SECS: u64 = 5;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+const METRICS_UPKEEP_INTERVAL_SECS: u64 = 5;
+const METRICS_DURATION_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+const METRIC_HTTP_REQUESTS: &str = "talk_wall_http_requests_total";
+const METRIC_HTTP_DURATION: &str = "talk_wall_http_request_duration_seconds";
+const METRIC_DB_DURATION: &str = "talk_wall_db_query_duration_seconds";
+const METRIC_ACTIVE_SESSIONS: &str = "talk_wall_active_sessions";
+const METRIC_POSTS_CREATED: &str = "talk_wall_posts_created_total";
+const METRIC_COMMENTS_CREATED: &str = "talk_wall_comments_created_total";
+const METRIC_LOGIN_FAILURES: &str = "talk_wall_login_failures_total";
+const METRIC_RATE_LIMITED: &str = "talk_wall_rate_limited_total";
+const DEFAULT_LOGIN_ATTEMPTS_PER_MINUTE: u32 = 20;
+const DEFAULT_LOGIN_ATTEMPTS_PER_ACCOUNT_PER_MINUTE: u32 = 5;
+const DEFAULT_POSTS_PER_HOUR: u32 = 30;
+const DEFAULT_COMMENTS_PER_MINUTE: u32 = 10;
+const RATE_LIMIT_PRUNE_THRESHOLD: usize = 10_000;
+const SECRET_CONFIG_KEYS: [&str; 2] = ["database.url", "metrics.token"];
+const RESTART_REQUIRED_SECTIONS: [&str; 4] = ["server", "database", "metrics", "logging"];
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
 const SESSION_COOKIE: &str = "session_id";
 const SESSION_TTL_DAYS: i64 = 7;
-const CATEGORIES: [&str; 5] = ["扩列", "吐槽", "表白", "提问", "其它"];
+const MAX_TAGS_PER_POST: usize = 5;
+const MAX_TAG_CHARS: usize = 20;
+const DEFAULT_TRENDING_DAYS: i64 = 7;
+const DEFAULT_TRENDING_LIMIT: i64 = 10;
+const POST_COLUMNS: &str = r#"p.id, p.user_id, p.title, p.category, p.is_anonymous, p.created_at,
+       u.username, u.qq, u.uid, u.qq_visibility, p.pin_scope, p.comment_count, p.last_comment_at,
+       p.last_activity_at, p.expires_at, p.archived_at IS NOT NULL AS archived,
+       p.hidden_at IS NOT NULL AS hidden,
+       EXISTS(SELECT 1 FROM bookmarks b WHERE b.post_id = p.id AND b.user_id = $1) AS bookmarked,
+       EXISTS(SELECT 1 FROM follows f WHERE f.follower_id = p.user_id AND f.followee_id = $1)
+           AS follows_viewer,
+       (SELECT string_agg(t.name, ',')
+        FROM post_tags pt JOIN tags t ON t.id = pt.tag_id
+        WHERE pt.post_id = p.id AND NOT t.banned) AS tags"#;
+const POST_FROM: &str = "FROM posts p\nLEFT JOIN users u ON p.user_id = u.id";
+const DRAFT_SELECT: &str = r#"SELECT p.id, p.title, p.content, p.category, p.is_anonymous, p.publish_at,
+       p.created_at, COALESCE(p.updated_at, p.created_at) AS updated_at,
+       (SELECT string_agg(t.name, ',')
+        FROM post_tags pt JOIN tags t ON t.id = pt.tag_id
+        WHERE pt.post_id = p.id AND NOT t.banned) AS tags
+FROM posts p"#;
+const SCHEDULED_PUBLISH_INTERVAL_SECS: u64 = 30;
+const ARCHIVE_SWEEP_INTERVAL_SECS: u64 = 300;
+const ACCOUNT_PURGE_INTERVAL_SECS: u64 = 3600;
+const DEFAULT_DELETION_GRACE_DAYS: i64 = 7;
+const DEFAULT_DATABASE_PATH: &str = "talk_wall.db";
+const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+const DEFAULT_BUSY_TIMEOUT_MS: u64 = 5000;
+const DEFAULT_BACKUP_DIR: &str = "backups";
+const DEFAULT_BACKUP_KEEP: usize = 7;
+const BACKUP_CHECK_INTERVAL_SECS: u64 = 60;
+const BACKUP_FILE_PREFIX: &str = "talk_wall-";
+const BACKUP_FILE_SUFFIX: &str = ".db";
+const TEMPORARY_PASSWORD_CHARS: usize = 12;
+const AUDIT_REVEAL_POST_AUTHOR: &str = "reveal_post_author";
+const AUDIT_REVEAL_COMMENT_AUTHOR: &str = "reveal_comment_author";
+const AUDIT_RESET_PASSWORD: &str = "reset_password";
+const AUDIT_REVOKE_SESSIONS: &str = "revoke_sessions";
+const AUDIT_DELETE_COMMENT: &str = "delete_comment";
+const AUDIT_BULK_MODERATION: &str = "bulk_moderation";
+const AUDIT_MOVE_POSTS: &str = "move_posts";
+const AUDIT_CREATE_BACKUP: &str = "create_backup";
+const MAX_BULK_ITEMS: i64 = 500;
+const REQUIRED_SCHEMA: [(&str, &[&str]); 16] = [
+    (
+        "users",
+        &[
+            "id",
+            "uid",
+            "username",
+            "qq",
+            "password_hash",
+            "created_at",
+            "qq_visibility",
+            "profile_hidden",
+            "posts_hidden",
+            "deletion_scheduled_at",
+            "deletion_mode",
+            "deleted_at",
+            "banned_at",
+        ],
+    ),
+    ("follows", &["follower_id", "followee_id", "created_at"]),
+    ("sessions", &["id", "user_id", "created_at", "expires_at"]),
+    (
+        "categories",
+        &[
+            "slug",
+            "name",
+            "description",
+            "sort_order",
+            "archived",
+            "post_permission",
+            "created_at",
+            "post_lifetime_days",
+        ],
+    ),
+    (
+        "posts",
+        &[
+            "id",
+            "user_id",
+            "title",
+            "content",
+            "category",
+            "is_anonymous",
+            "created_at",
+            "pin_scope",
+            "pinned_at",
+            "pin_expires_at",
+            "status",
+            "publish_at",
+            "updated_at",
+            "expires_at",
+            "archived_at",
+            "hidden_at",
+            "comment_count",
+            "last_comment_at",
+            "last_activity_at",
+            "hot_score",
+        ],
+    ),
+    (
+        "comments",
+        &[
+            "id",
+            "post_id",
+            "user_id",
+            "content",
+            "is_anonymous",
+            "created_at",
+            "hidden_at",
+        ],
+    ),
+    ("bookmarks", &["user_id", "post_id", "created_at"]),
+    (
+        "post_subscriptions",
+        &["user_id", "post_id", "muted", "created_at"],
+    ),
+    (
+        "notifications",
+        &[
+            "id",
+            "user_id",
+            "kind",
+            "post_id",
+            "comment_id",
+            "created_at",
+            "read_at",
+        ],
+    ),
+    (
+        "tags",
+        &["id", "name", "banned", "merged_into", "created_at"],
+    ),
+    ("post_tags", &["post_id", "tag_id"]),
+    (
+        "polls",
+        &[
+            "id",
+            "post_id",
+            "multiple",
+            "anonymous_votes",
+            "closes_at",
+            "created_at",
+        ],
+    ),
+    ("poll_options", &["id", "poll_id", "position", "label"]),
+    ("poll_votes", &["poll_id", "user_id", "created_at"]),
+    ("poll_vote_choices", &["poll_id", "user_id", "option_id"]),
+    (
+        "admin_audit_log",
+        &[
+            "id",
+            "admin_id",
+            "action",
+            "target_type",
+            "target_id",
+            "detail",
+            "created_at",
+        ],
+    ),
+];
+const ADMIN_USER_SELECT: &str = r#"SELECT u.id, u.uid, u.username, u.qq, u.created_at,
+       u.deletion_scheduled_at, u.deleted_at, u.banned_at
+FROM users u"#;
+const MAX_POST_LIFETIME_DAYS: i64 = 365;
+const HOT_SCORE_DECAY_SECONDS: f64 = 45000.0;
+const SEED_CATEGORIES: [&str; 5] = ["扩列", "吐槽", "表白", "提问", "其它"];
+const MAX_CATEGORY_SLUG_CHARS: usize = 32;
 const LATEST_CATEGORY: &str = "最新";
 const DEFAULT_CATEGORY: &str = "其它";
+const FOLLOWING_FEED: &str = "following";
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 50;
+const NOTIFICATION_NEW_COMMENT: &str = "new_comment";
+const NOTIFICATION_EXCERPT_CHARS: usize = 60;
+const POST_EXCERPT_CHARS: usize = 120;
+const MIN_POLL_OPTIONS: usize = 2;
+const MAX_POLL_OPTIONS: usize = 10;
+const MAX_POLL_OPTION_CHARS: usize = 50;
 const STATIC_DIR: &str = "frontend/dist";
 const FRONTEND_ENTRY: &str = "index.html";
 
@@ -42,18 +273,179 @@ enum ApiError {
     Unauthorized,
     #[error("禁止访问")]
     Forbidden,
-    #[error("请求参数错误: {0}")]
-    Validation(String),
-    #[error("冲突: {0}")]
-    Conflict(String),
+    #[error("请求参数错误: {}", join_details(.0))]
+    Validation(Vec<ErrorDetail>),
+    #[error("冲突: {}", .0.zh)]
+    Conflict(ErrorDetail),
     #[error("资源不存在")]
     NotFound,
+    #[error("操作过于频繁，请稍后再试")]
+    TooManyRequests,
     #[error("密码处理失败: {0}")]
     PasswordHash(String),
     #[error("服务器内部错误: {0}")]
     Internal(String),
 }
 
+#[derive(Debug, Error)]
+#[error("配置项 `{key}` 无效: {message}")]
+struct ConfigError {
+    key: String,
+    message: String,
+}
+
+impl ConfigError {
+    fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ErrorDetail {
+    code: &'static str,
+    field: Option<String>,
+    zh: String,
+    en: String,
+}
+
+impl ErrorDetail {
+    fn new(code: &'static str, zh: impl Into<String>, en: impl Into<String>) -> Self {
+        Self {
+            code,
+            field: None,
+            zh: zh.into(),
+            en: en.into(),
+        }
+    }
+
+    fn field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+
+    fn message(&self, locale: Locale) -> &str {
+        match locale {
+            Locale::Zh => &self.zh,
+            Locale::En => &self.en,
+        }
+    }
+}
+
+fn join_details(details: &[ErrorDetail]) -> String {
+    details
+        .iter()
+        .map(|detail| detail.zh.as_str())
+        .collect::<Vec<_>>()
+        .join("；")
+}
+
+impl ApiError {
+    fn invalid(code: &'static str, zh: impl Into<String>, en: impl Into<String>) -> Self {
+        ApiError::Validation(vec![ErrorDetail::new(code, zh, en)])
+    }
+
+    fn invalid_field(
+        field: &'static str,
+        code: &'static str,
+        zh: impl Into<String>,
+        en: impl Into<String>,
+    ) -> Self {
+        ApiError::Validation(vec![ErrorDetail::new(code, zh, en).field(field)])
+    }
+
+    fn conflict(code: &'static str, zh: impl Into<String>, en: impl Into<String>) -> Self {
+        ApiError::Conflict(ErrorDetail::new(code, zh, en))
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::Validation(details) if details.len() == 1 => details[0].code,
+            ApiError::Validation(_) => "request.invalid",
+            ApiError::Conflict(detail) => detail.code,
+            ApiError::Unauthorized => "auth.unauthorized",
+            ApiError::Forbidden => "auth.forbidden",
+            ApiError::NotFound => "resource.not_found",
+            ApiError::TooManyRequests => "rate.limited",
+            ApiError::Database(_) | ApiError::PasswordHash(_) | ApiError::Internal(_) => {
+                "server.internal"
+            }
+        }
+    }
+
+    fn message(&self, locale: Locale) -> String {
+        let (zh, en) = match self {
+            ApiError::Validation(details) => {
+                let separator = match locale {
+                    Locale::Zh => "；",
+                    Locale::En => "; ",
+                };
+                return details
+                    .iter()
+                    .map(|detail| detail.message(locale))
+                    .collect::<Vec<_>>()
+                    .join(separator);
+            }
+            ApiError::Conflict(detail) => return detail.message(locale).to_string(),
+            ApiError::Unauthorized => ("未授权", "Unauthorized"),
+            ApiError::Forbidden => ("禁止访问", "Forbidden"),
+            ApiError::NotFound => ("资源不存在", "Resource not found"),
+            ApiError::TooManyRequests => (
+                "操作过于频繁，请稍后再试",
+                "Too many requests, please try again later",
+            ),
+            ApiError::Database(_) | ApiError::PasswordHash(_) | ApiError::Internal(_) => (
+                "服务器内部错误，请稍后重试",
+                "Internal server error, please try again later",
+            ),
+        };
+        match locale {
+            Locale::Zh => zh.into(),
+            Locale::En => en.into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Locale {
+    #[default]
+    Zh,
+    En,
+}
+
+impl Locale {
+    fn from_accept_language(header: &str) -> Self {
+        let mut best: Option<(f32, Locale)> = None;
+        for entry in header.split(',') {
+            let mut parts = entry.split(';');
+            let tag = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let locale = if tag.starts_with("zh") {
+                Locale::Zh
+            } else if tag.starts_with("en") {
+                Locale::En
+            } else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|value| value.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality > 0.0 && best.is_none_or(|(current, _)| quality > current) {
+                best = Some((quality, locale));
+            }
+        }
+        best.map(|(_, locale)| locale).unwrap_or_default()
+    }
+}
+
+#[derive(Clone)]
+struct RequestContext {
+    request_id: String,
+    locale: Locale,
+}
+
 impl From<PasswordHashError> for ApiError {
     fn from(err: PasswordHashError) -> Self {
         ApiError::PasswordHash(err.to_string())
@@ -68,11 +460,37 @@ impl IntoResponse for ApiError {
             ApiError::Conflict(_) => StatusCode::CONFLICT,
             ApiError::Forbidden => StatusCode::FORBIDDEN,
             ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
+        if status.is_server_error() {
+            error!(error = %self, "请求处理失败");
+        } else {
+            debug!(error = %self, "请求被拒绝");
+        }
+        let context = REQUEST_CONTEXT.try_with(Clone::clone).ok();
+        let locale = context
+            .as_ref()
+            .map_or(Locale::default(), |context| context.locale);
+        let details = match &self {
+            ApiError::Validation(details) => details
+                .iter()
+                .filter_map(|detail| {
+                    Some(FieldErrorResponse {
+                        field: detail.field.clone()?,
+                        code: detail.code,
+                        message: detail.message(locale).to_string(),
+                    })
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
         let body = Json(ErrorResponse {
-            message: self.to_string(),
+            code: self.code(),
+            message: self.message(locale),
+            details,
+            request_id: context.map(|context| context.request_id),
         });
 
         (status, body).into_response()
@@ -81,9 +499,156 @@ impl IntoResponse for ApiError {
 
 #[derive(Serialize)]
 struct ErrorResponse {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    details: Vec<FieldErrorResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct FieldErrorResponse {
+    field: String,
+    code: &'static str,
     message: String,
 }
 
+struct ApiJson<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_json = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .is_some_and(|mime| {
+                let mime = mime.trim();
+                mime.eq_ignore_ascii_case("application/json") || mime.ends_with("+json")
+            });
+        if !is_json {
+            return Err(ApiError::invalid(
+                "request.content_type",
+                "请求体必须是 JSON（Content-Type: application/json）",
+                "Request body must be JSON (Content-Type: application/json)",
+            ));
+        }
+        let body = Bytes::from_request(request, state).await.map_err(|_| {
+            ApiError::invalid(
+                "request.body_unreadable",
+                "无法读取请求体",
+                "Failed to read the request body",
+            )
+        })?;
+        let mut deserializer = serde_json::Deserializer::from_slice(&body);
+        serde_path_to_error::deserialize(&mut deserializer)
+            .map(ApiJson)
+            .map_err(|err| {
+                if err.inner().is_data() {
+                    let message = err.inner().to_string();
+                    deserialize_error(err.path().to_string(), &message)
+                } else {
+                    ApiError::invalid(
+                        "request.body_invalid",
+                        "请求体不是合法的 JSON",
+                        "Request body is not valid JSON",
+                    )
+                }
+            })
+    }
+}
+
+struct ApiQuery<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = parts.uri.query().unwrap_or_default();
+        let deserializer =
+            serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+        serde_path_to_error::deserialize(deserializer)
+            .map(ApiQuery)
+            .map_err(|err| {
+                let message = err.inner().to_string();
+                deserialize_error(err.path().to_string(), &message)
+            })
+    }
+}
+
+struct ApiPath<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Path::<T>::from_request_parts(parts, state)
+            .await
+            .map(|Path(value)| ApiPath(value))
+            .map_err(|err| {
+                debug!("路径参数无效: {err}");
+                ApiError::invalid(
+                    "request.path_invalid",
+                    "路径参数格式错误",
+                    "Invalid path parameter",
+                )
+            })
+    }
+}
+
+fn deserialize_error(path: String, message: &str) -> ApiError {
+    let missing = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split('`').next());
+    let field = match (path.as_str(), missing) {
+        (".", Some(name)) => name.to_string(),
+        (_, Some(name)) => format!("{path}.{name}"),
+        _ => path,
+    };
+    let detail = if missing.is_some() {
+        ErrorDetail::new(
+            "request.field_missing",
+            format!("缺少字段 {field}"),
+            format!("Missing field {field}"),
+        )
+    } else if field == "." {
+        ErrorDetail::new(
+            "request.field_invalid",
+            "请求参数格式错误",
+            "Invalid request parameters",
+        )
+    } else {
+        ErrorDetail::new(
+            "request.field_invalid",
+            format!("字段 {field} 格式错误"),
+            format!("Field {field} has an invalid value"),
+        )
+    };
+    ApiError::Validation(vec![if field == "." {
+        detail
+    } else {
+        detail.field(field)
+    }])
+}
+
 #[derive(Serialize)]
 struct MessageResponse {
     message: String,
@@ -92,735 +657,7605 @@ struct MessageResponse {
 type SharedState = Arc<AppState>;
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
-#[derive(Clone)]
 struct AppState {
-    db: SqlitePool,
-    config: AppConfig,
+    repo: Arc<dyn Repository>,
+    config: RwLock<Arc<AppConfig>>,
+    shutdown: watch::Sender<bool>,
+    rate_limiter: RateLimiter,
+}
+
+impl AppState {
+    fn config(&self) -> Arc<AppConfig> {
+        self.config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn replace_config(&self, config: AppConfig) {
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+    }
+
+    fn rate_limit(&self, action: RateLimitAction, key: &str) -> ApiResult<()> {
+        self.rate_limiter
+            .check(&self.config().rate_limit, action, key)
+    }
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 struct AppConfig {
+    #[serde(default)]
     server: ServerSection,
     #[serde(default)]
     admins: AdminSection,
+    #[serde(default)]
+    tags: TagSection,
+    #[serde(default)]
+    accounts: AccountSection,
+    #[serde(default)]
+    database: DatabaseSection,
+    #[serde(default)]
+    backup: BackupSection,
+    #[serde(default)]
+    metrics: MetricsSection,
+    #[serde(default)]
+    rate_limit: RateLimitSection,
+    #[serde(default)]
+    logging: LoggingSection,
 }
 
 impl AppConfig {
     fn is_admin(&self, uid: &str) -> bool {
         self.admins.uids.iter().any(|candidate| candidate == uid)
     }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if let Err(err) = self.server.addr.parse::<SocketAddr>() {
+            return Err(ConfigError::new("server.addr", err.to_string()));
+        }
+        if let Some(index) = self
+            .admins
+            .uids
+            .iter()
+            .position(|uid| uid.trim().is_empty())
+        {
+            return Err(ConfigError::new(
+                format!("admins.uids[{index}]"),
+                "UID 不能为空",
+            ));
+        }
+        if self.accounts.deletion_grace_days < 0 {
+            return Err(ConfigError::new(
+                "accounts.deletion_grace_days",
+                "不能为负数",
+            ));
+        }
+        if self.database.path.as_os_str().is_empty() {
+            return Err(ConfigError::new("database.path", "不能为空"));
+        }
+        if self.database.max_connections == 0 {
+            return Err(ConfigError::new("database.max_connections", "必须大于 0"));
+        }
+        if self.database.backend == DatabaseBackend::Postgres
+            && self
+                .database
+                .url
+                .as_deref()
+                .is_none_or(|url| url.trim().is_empty())
+        {
+            return Err(ConfigError::new(
+                "database.url",
+                "使用 postgres 后端时必须填写连接地址",
+            ));
+        }
+        if self.backup.dir.as_os_str().is_empty() {
+            return Err(ConfigError::new("backup.dir", "不能为空"));
+        }
+        if let Some(addr) = &self.metrics.addr {
+            if let Err(err) = addr.parse::<SocketAddr>() {
+                return Err(ConfigError::new("metrics.addr", err.to_string()));
+            }
+        }
+        if self.metrics.enabled
+            && self.metrics.addr.is_none()
+            && self
+                .metrics
+                .token
+                .as_deref()
+                .is_none_or(|token| token.trim().is_empty())
+        {
+            return Err(ConfigError::new(
+                "metrics.token",
+                "启用指标时必须设置访问令牌，或通过 metrics.addr 单独监听",
+            ));
+        }
+        Ok(())
+    }
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 struct ServerSection {
+    #[serde(default = "default_server_addr")]
     addr: String,
+    #[serde(default = "default_shutdown_timeout_secs")]
+    shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerSection {
+    fn default() -> Self {
+        Self {
+            addr: default_server_addr(),
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+        }
+    }
 }
 
-#[derive(Clone, Deserialize)]
+fn default_server_addr() -> String {
+    DEFAULT_SERVER_ADDR.to_string()
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    DEFAULT_SHUTDOWN_TIMEOUT_SECS
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 struct AdminSection {
     #[serde(default)]
     uids: Vec<String>,
 }
 
-impl Default for AdminSection {
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct TagSection {
+    #[serde(default = "default_allow_new_tags")]
+    allow_new: bool,
+}
+
+impl Default for TagSection {
     fn default() -> Self {
-        Self { uids: Vec::new() }
+        Self {
+            allow_new: default_allow_new_tags(),
+        }
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .init();
-
-    let config = load_config()?;
-    let pool = SqlitePool::connect("sqlite:talk_wall.db").await?;
-    init_db(&pool).await?;
+fn default_allow_new_tags() -> bool {
+    true
+}
 
-    let state = Arc::new(AppState {
-        db: pool,
-        config: config.clone(),
-    });
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct RateLimitSection {
+    #[serde(default = "default_login_attempts_per_minute")]
+    login_attempts_per_minute: u32,
+    #[serde(default = "default_login_attempts_per_account_per_minute")]
+    login_attempts_per_account_per_minute: u32,
+    #[serde(default = "default_posts_per_hour")]
+    posts_per_hour: u32,
+    #[serde(default = "default_comments_per_minute")]
+    comments_per_minute: u32,
+}
 
-    if fs::metadata(STATIC_DIR).is_err() {
-        warn!("{STATIC_DIR} 不存在，运行 `npm install && npm run build` 以构建 Svelte 前端");
-    } else {
-        let entry = PathBuf::from(STATIC_DIR).join(FRONTEND_ENTRY);
-        if fs::metadata(&entry).is_err() {
-            warn!(
-                "未找到 {}，请确认 `npm run build` 是否成功并输出入口文件",
-                entry.display()
-            );
+impl Default for RateLimitSection {
+    fn default() -> Self {
+        Self {
+            login_attempts_per_minute: default_login_attempts_per_minute(),
+            login_attempts_per_account_per_minute: default_login_attempts_per_account_per_minute(),
+            posts_per_hour: default_posts_per_hour(),
+            comments_per_minute: default_comments_per_minute(),
         }
     }
+}
 
-    let static_service = ServeDir::new(STATIC_DIR).not_found_service(ServeFile::new(
-        PathBuf::from(STATIC_DIR).join(FRONTEND_ENTRY),
-    ));
+fn default_login_attempts_per_minute() -> u32 {
+    DEFAULT_LOGIN_ATTEMPTS_PER_MINUTE
+}
 
-    let api_routes = Router::new()
-        .route("/api/register", post(register))
-        .route("/api/login", post(login))
-        .route("/api/logout", post(logout))
-        .route("/api/me", get(me).patch(update_profile))
-        .route("/api/me/posts", get(list_my_posts))
-        .route("/api/me/password", post(change_password))
-        .route("/api/users/:uid", get(get_user_profile))
-        .route("/api/posts", post(create_post).get(list_posts))
-        .route("/api/posts/:post_id", get(get_post))
-        .route("/api/posts/:post_id/comments", post(create_comment))
-        .route("/api/admin/posts/:post_id", delete(delete_post))
-        .with_state(state.clone());
+fn default_login_attempts_per_account_per_minute() -> u32 {
+    DEFAULT_LOGIN_ATTEMPTS_PER_ACCOUNT_PER_MINUTE
+}
 
-    let app = api_routes
-    let app = Router::new()
-        .merge(api_routes)
-        .layer(CookieManagerLayer::new())
-        .fallback_service(static_service);
+fn default_posts_per_hour() -> u32 {
+    DEFAULT_POSTS_PER_HOUR
+}
 
-    let addr: SocketAddr = config.server.addr.parse()?;
-    let listener = TcpListener::bind(addr).await?;
-    info!("listening on {}", listener.local_addr()?);
-    axum::serve(listener, app).await?;
+fn default_comments_per_minute() -> u32 {
+    DEFAULT_COMMENTS_PER_MINUTE
+}
 
-    Ok(())
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum RateLimitAction {
+    Login,
+    LoginAccount,
+    Post,
+    Comment,
 }
 
-async fn register(
-    State(state): State<SharedState>,
-    Json(payload): Json<RegisterPayload>,
-) -> ApiResult<impl IntoResponse> {
-    if payload.username.trim().is_empty() {
-        return Err(ApiError::Validation("用户名不能为空".into()));
-    }
-    if payload.qq.trim().is_empty() {
-        return Err(ApiError::Validation("QQ号不能为空".into()));
+impl RateLimitAction {
+    fn label(self) -> &'static str {
+        match self {
+            RateLimitAction::Login => "login",
+            RateLimitAction::LoginAccount => "login_account",
+            RateLimitAction::Post => "post",
+            RateLimitAction::Comment => "comment",
+        }
     }
-    if payload.password.len() < 6 {
-        return Err(ApiError::Validation("密码至少需要6位".into()));
+
+    fn limit(self, config: &RateLimitSection) -> (u32, StdDuration) {
+        match self {
+            RateLimitAction::Login => {
+                (config.login_attempts_per_minute, StdDuration::from_secs(60))
+            }
+            RateLimitAction::LoginAccount => (
+                config.login_attempts_per_account_per_minute,
+                StdDuration::from_secs(60),
+            ),
+            RateLimitAction::Post => (config.posts_per_hour, StdDuration::from_secs(3600)),
+            RateLimitAction::Comment => (config.comments_per_minute, StdDuration::from_secs(60)),
+        }
     }
+}
 
-    let hashed = hash_password(&payload.password)?;
-    let now = now_iso();
-    let uid = Uuid::new_v4().to_string();
+struct RateWindow {
+    started: Instant,
+    period: StdDuration,
+    count: u32,
+}
 
-    let result = sqlx::query(
-        r#"INSERT INTO users (uid, username, qq, password_hash, created_at)
-           VALUES (?1, ?2, ?3, ?4, ?5)"#,
-    )
-    .bind(uid)
-    .bind(payload.username.trim())
-    .bind(payload.qq.trim())
-    .bind(hashed)
-    .bind(now)
-    .execute(&state.db)
-    .await;
+#[derive(Default)]
+struct RateLimiter {
+    windows: Mutex<HashMap<(RateLimitAction, String), RateWindow>>,
+}
 
-    if let Err(err) = result {
-        if is_unique_violation(&err) {
-            return Err(ApiError::Conflict("用户名已存在".into()));
+impl RateLimiter {
+    fn check(
+        &self,
+        config: &RateLimitSection,
+        action: RateLimitAction,
+        key: &str,
+    ) -> ApiResult<()> {
+        let (limit, period) = action.limit(config);
+        if limit == 0 {
+            return Ok(());
         }
-        return Err(ApiError::from(err));
+        let mut windows = self.windows.lock().unwrap_or_else(PoisonError::into_inner);
+        if windows.len() >= RATE_LIMIT_PRUNE_THRESHOLD {
+            windows.retain(|_, window| window.started.elapsed() < window.period);
+        }
+        let window = windows
+            .entry((action, key.to_string()))
+            .or_insert(RateWindow {
+                started: Instant::now(),
+                period,
+                count: 0,
+            });
+        if window.started.elapsed() >= window.period {
+            *window = RateWindow {
+                started: Instant::now(),
+                period,
+                count: 0,
+            };
+        }
+        if window.count >= limit {
+            counter!(METRIC_RATE_LIMITED, "action" => action.label()).increment(1);
+            return Err(ApiError::TooManyRequests);
+        }
+        window.count += 1;
+        Ok(())
     }
+}
 
-    Ok((
-        StatusCode::CREATED,
-        Json(MessageResponse {
-            message: "注册成功".into(),
-        }),
-    ))
+/// 登录限流使用的客户端地址。直连时就是对端地址；对端是本机的反向代理时，
+/// 取代理追加在 `X-Forwarded-For` 末尾的地址，客户端自己伪造的前几项会被忽略。
+fn client_ip(peer: SocketAddr, headers: &HeaderMap) -> IpAddr {
+    if !peer.ip().is_loopback() {
+        return peer.ip();
+    }
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|entry| entry.trim().parse::<IpAddr>().ok())
+        .next_back()
+        .unwrap_or(peer.ip())
 }
 
-async fn login(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-    Json(payload): Json<LoginPayload>,
-) -> ApiResult<(CookieJar, Json<UserResponse>)> {
-    cookies: Cookies,
-    Json(payload): Json<LoginPayload>,
-) -> ApiResult<Json<UserResponse>> {
-    let user = sqlx::query_as::<_, DbUser>(
-        "SELECT id, uid, username, qq, password_hash FROM users WHERE username = ?1",
-    )
-    .bind(payload.username.trim())
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or(ApiError::Unauthorized)?;
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct AccountSection {
+    #[serde(default = "default_deletion_grace_days")]
+    deletion_grace_days: i64,
+}
 
-    if !verify_password(&user.password_hash, &payload.password)? {
-        return Err(ApiError::Unauthorized);
+impl Default for AccountSection {
+    fn default() -> Self {
+        Self {
+            deletion_grace_days: default_deletion_grace_days(),
+        }
     }
+}
 
-    let session_id = Uuid::new_v4().to_string();
-    let now = OffsetDateTime::now_utc();
-    let expires_at = now + Duration::days(SESSION_TTL_DAYS);
+fn default_deletion_grace_days() -> i64 {
+    DEFAULT_DELETION_GRACE_DAYS
+}
 
-    sqlx::query(
-        r#"INSERT INTO sessions (id, user_id, created_at, expires_at)
-           VALUES (?1, ?2, ?3, ?4)"#,
-    )
-    .bind(&session_id)
-    .bind(user.id)
-    .bind(now.format(&Rfc3339).unwrap())
-    .bind(expires_at.format(&Rfc3339).unwrap())
-    .execute(&state.db)
-    .await?;
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct DatabaseSection {
+    #[serde(default)]
+    backend: DatabaseBackend,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default = "default_database_path")]
+    path: PathBuf,
+    #[serde(default = "default_max_connections")]
+    max_connections: u32,
+    #[serde(default = "default_busy_timeout_ms")]
+    busy_timeout_ms: u64,
+    #[serde(default = "default_wal")]
+    wal: bool,
+    #[serde(default)]
+    synchronous: SynchronousLevel,
+    #[serde(default = "default_create_if_missing")]
+    create_if_missing: bool,
+}
 
-    let mut cookie = Cookie::new(SESSION_COOKIE, session_id);
-    cookie.set_http_only(true);
-    cookie.set_path("/");
-    cookie.set_max_age(Duration::days(SESSION_TTL_DAYS));
-    let jar = jar.add(cookie);
+impl Default for DatabaseSection {
+    fn default() -> Self {
+        Self {
+            backend: DatabaseBackend::default(),
+            url: None,
+            path: default_database_path(),
+            max_connections: default_max_connections(),
+            busy_timeout_ms: default_busy_timeout_ms(),
+            wal: default_wal(),
+            synchronous: SynchronousLevel::default(),
+            create_if_missing: default_create_if_missing(),
+        }
+    }
+}
 
-    let is_admin = state.config.is_admin(&user.uid);
+impl DatabaseSection {
+    fn connect_options(&self) -> SqliteConnectOptions {
+        SqliteConnectOptions::new()
+            .filename(&self.path)
+            .create_if_missing(self.create_if_missing)
+            .busy_timeout(StdDuration::from_millis(self.busy_timeout_ms))
+            .journal_mode(if self.wal {
+                SqliteJournalMode::Wal
+            } else {
+                SqliteJournalMode::Delete
+            })
+            .synchronous(self.synchronous.into())
+            .foreign_keys(true)
+    }
+}
 
-    Ok((
-        jar,
-        Json(UserResponse {
-            id: user.id,
-            username: user.username,
-            qq: user.qq,
-            uid: user.uid,
-            is_admin,
-        }),
-    ))
-    cookies.add(cookie);
+fn default_database_path() -> PathBuf {
+    PathBuf::from(DEFAULT_DATABASE_PATH)
+}
 
-    let is_admin = state.config.is_admin(&user.uid);
+fn default_max_connections() -> u32 {
+    DEFAULT_MAX_CONNECTIONS
+}
 
-    Ok(Json(UserResponse {
-        id: user.id,
-        username: user.username,
-        qq: user.qq,
-        uid: user.uid,
-        is_admin,
-    }))
+fn default_busy_timeout_ms() -> u64 {
+    DEFAULT_BUSY_TIMEOUT_MS
 }
 
-async fn logout(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-) -> ApiResult<(CookieJar, Json<MessageResponse>)> {
-    let mut jar = jar;
-    if let Some(cookie) = jar.get(SESSION_COOKIE) {
-    cookies: Cookies,
-) -> ApiResult<impl IntoResponse> {
-    if let Some(cookie) = cookies.get(SESSION_COOKIE) {
-        let token = cookie.value().to_string();
-        sqlx::query("DELETE FROM sessions WHERE id = ?1")
-            .bind(&token)
-            .execute(&state.db)
-            .await?;
+fn default_wal() -> bool {
+    true
+}
 
-        let mut expired = Cookie::named(SESSION_COOKIE.to_string());
-        expired.set_path("/");
-        jar = jar.remove(expired);
-    }
+fn default_create_if_missing() -> bool {
+    true
+}
 
-    Ok((
-        jar,
-        Json(MessageResponse {
-            message: "退出成功".into(),
-        }),
-    ))
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum DatabaseBackend {
+    #[default]
+    Sqlite,
+    Postgres,
 }
 
-async fn me(State(state): State<SharedState>, jar: CookieJar) -> ApiResult<Json<UserResponse>> {
-    let user = authenticate(&state, &jar).await?;
-        cookies.remove(expired);
-    }
-
-    Ok(Json(MessageResponse {
-        message: "退出成功".into(),
-    }))
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct BackupSection {
+    #[serde(default = "default_backup_dir")]
+    dir: PathBuf,
+    #[serde(default)]
+    interval_hours: u64,
+    #[serde(default = "default_backup_keep")]
+    keep: usize,
 }
 
-async fn me(State(state): State<SharedState>, cookies: Cookies) -> ApiResult<Json<UserResponse>> {
-    let user = authenticate(&state, &cookies).await?;
-    let is_admin = state.config.is_admin(&user.uid);
-    Ok(Json(UserResponse {
-        id: user.id,
-        username: user.username,
-        qq: user.qq,
-        uid: user.uid,
-        is_admin,
-    }))
+impl Default for BackupSection {
+    fn default() -> Self {
+        Self {
+            dir: default_backup_dir(),
+            interval_hours: 0,
+            keep: default_backup_keep(),
+        }
+    }
 }
 
-async fn create_post(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-    cookies: Cookies,
-    Json(payload): Json<CreatePostPayload>,
-) -> ApiResult<impl IntoResponse> {
-    if payload.title.trim().is_empty() {
-        return Err(ApiError::Validation("标题不能为空".into()));
-    }
-    if payload.content.trim().is_empty() {
-        return Err(ApiError::Validation("内容不能为空".into()));
-    }
+fn default_backup_dir() -> PathBuf {
+    PathBuf::from(DEFAULT_BACKUP_DIR)
+}
 
-    let user = authenticate(&state, &jar).await?;
-    let user = authenticate(&state, &cookies).await?;
-    let now = now_iso();
-    let anonymous = payload.anonymous.unwrap_or(false);
-    let category = normalize_post_category(payload.category)?;
+fn default_backup_keep() -> usize {
+    DEFAULT_BACKUP_KEEP
+}
 
-    sqlx::query(
-        r#"INSERT INTO posts (user_id, title, content, category, is_anonymous, created_at)
-           VALUES (?1, ?2, ?3, ?4, ?5, ?6)"#,
-    )
-    .bind(user.id)
-    .bind(payload.title.trim())
-    .bind(payload.content.trim())
-    .bind(&category)
-    .bind(anonymous)
-    .bind(now)
-    .execute(&state.db)
-    .await?;
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct LoggingSection {
+    #[serde(default)]
+    format: LogFormat,
+}
 
-    Ok((
-        StatusCode::CREATED,
-        Json(MessageResponse {
-            message: "发布成功".into(),
-        }),
-    ))
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum LogFormat {
+    #[default]
+    Text,
+    Json,
 }
 
-async fn list_posts(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-    Query(query): Query<PostListQuery>,
-) -> ApiResult<Json<Vec<PostSummary>>> {
-    authenticate(&state, &jar).await?;
-    cookies: Cookies,
-    Query(query): Query<PostListQuery>,
-) -> ApiResult<Json<Vec<PostSummary>>> {
-    authenticate(&state, &cookies).await?;
-    let category_filter = normalize_query_category(query.category)?;
-
-    let posts = sqlx::query_as::<_, DbPost>(
-        r#"SELECT p.id, p.user_id, p.title, p.content, p.category, p.is_anonymous, p.created_at,
-                  u.username, u.qq, u.uid
-           FROM posts p
-           LEFT JOIN users u ON p.user_id = u.id
-           WHERE (?1 IS NULL OR p.category = ?1)
-           ORDER BY p.created_at DESC"#,
-    )
-    .bind(category_filter)
-    .fetch_all(&state.db)
-    .await?;
-    let response = posts.into_iter().map(PostSummary::from).collect();
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct MetricsSection {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    addr: Option<String>,
+}
 
-    Ok(Json(response))
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SynchronousLevel {
+    Off,
+    Normal,
+    #[default]
+    Full,
+    Extra,
 }
 
-async fn create_comment(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-    cookies: Cookies,
-    Path(post_id): Path<i64>,
-    Json(payload): Json<CreateCommentPayload>,
-) -> ApiResult<impl IntoResponse> {
-    if payload.content.trim().is_empty() {
-        return Err(ApiError::Validation("内容不能为空".into()));
+impl From<SynchronousLevel> for SqliteSynchronous {
+    fn from(value: SynchronousLevel) -> Self {
+        match value {
+            SynchronousLevel::Off => SqliteSynchronous::Off,
+            SynchronousLevel::Normal => SqliteSynchronous::Normal,
+            SynchronousLevel::Full => SqliteSynchronous::Full,
+            SynchronousLevel::Extra => SqliteSynchronous::Extra,
+        }
     }
+}
 
-    let user = authenticate(&state, &jar).await?;
-    let user = authenticate(&state, &cookies).await?;
-    let now = now_iso();
-    let anonymous = payload.anonymous.unwrap_or(false);
+#[derive(Parser)]
+#[command(name = "talk-wall", about = "表白墙服务端与管理工具")]
+struct Cli {
+    #[arg(
+        long,
+        global = true,
+        env = "TALK_WALL_CONFIG",
+        help = "配置文件路径，默认 ./config.toml"
+    )]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    sqlx::query(
-        r#"INSERT INTO comments (post_id, user_id, content, is_anonymous, created_at)
-           VALUES (?1, ?2, ?3, ?4, ?5)"#,
-    )
-    .bind(post_id)
-    .bind(user.id)
-    .bind(payload.content.trim())
-    .bind(anonymous)
-    .bind(now)
-    .execute(&state.db)
-    .await?;
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "启动 HTTP 服务（默认）")]
+    Serve,
+    #[command(subcommand, about = "用户管理")]
+    User(UserCommand),
+    #[command(subcommand, about = "管理员授权")]
+    Admin(AdminCommand),
+    #[command(subcommand, about = "帖子管理")]
+    Post(PostCommand),
+    #[command(subcommand, about = "登录会话管理")]
+    Sessions(SessionsCommand),
+    #[command(subcommand, about = "配置文件工具")]
+    Config(ConfigCommand),
+    #[command(subcommand, about = "数据库在线备份")]
+    Backup(BackupCommand),
+    #[command(about = "用备份覆盖当前数据库，服务运行时拒绝执行")]
+    Restore { file: PathBuf },
+    #[command(about = "把 SQLite 中的全部数据复制到 Postgres，目标库已有数据时拒绝执行")]
+    Migrate {
+        #[arg(long, help = "目标 Postgres 连接地址，默认使用 database.url")]
+        to: Option<String>,
+    },
+}
 
-    Ok((
-        StatusCode::CREATED,
-        Json(MessageResponse {
-            message: "评论成功".into(),
-        }),
-    ))
+#[derive(Subcommand)]
+enum BackupCommand {
+    #[command(about = "立即创建一份在线备份")]
+    Create,
+    #[command(about = "列出备份目录中的备份")]
+    List,
+    #[command(about = "检查备份文件的完整性")]
+    Verify { file: PathBuf },
 }
 
-async fn get_post(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-    Path(post_id): Path<i64>,
-) -> ApiResult<Json<PostDetailResponse>> {
-    authenticate(&state, &jar).await?;
-    cookies: Cookies,
-    Path(post_id): Path<i64>,
-) -> ApiResult<Json<PostDetailResponse>> {
-    authenticate(&state, &cookies).await?;
-    let post = load_post(&state, post_id).await?;
-    let comments = fetch_comments(&state, post.id).await?;
-    Ok(Json(PostDetailResponse::from_parts(post, comments)))
+#[derive(Subcommand)]
+enum ConfigCommand {
+    #[command(about = "合并配置文件与环境变量并校验，不连接数据库")]
+    Check,
 }
 
-async fn list_my_posts(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-) -> ApiResult<Json<Vec<PostSummary>>> {
-    let user = authenticate(&state, &jar).await?;
-    cookies: Cookies,
-) -> ApiResult<Json<Vec<PostSummary>>> {
-    let user = authenticate(&state, &cookies).await?;
-    let posts = fetch_posts_for_user(&state, user.id, true).await?;
-    Ok(Json(posts))
+#[derive(Subcommand)]
+enum UserCommand {
+    #[command(about = "创建用户，未指定密码时生成临时密码")]
+    Create {
+        username: String,
+        qq: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    #[command(about = "列出用户，可按用户名 / QQ / UID 搜索")]
+    List {
+        #[arg(long)]
+        query: Option<String>,
+        #[arg(long, default_value_t = MAX_PAGE_SIZE)]
+        limit: i64,
+    },
+    #[command(about = "重置密码并注销该用户的所有会话")]
+    ResetPassword { user: String },
+    #[command(about = "封禁用户并注销其所有会话")]
+    Ban { user: String },
+    #[command(about = "解除封禁")]
+    Unban { user: String },
 }
 
-async fn get_user_profile(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-    Path(uid): Path<String>,
-) -> ApiResult<Json<UserProfileResponse>> {
-    authenticate(&state, &jar).await?;
-    cookies: Cookies,
-    Path(uid): Path<String>,
-) -> ApiResult<Json<UserProfileResponse>> {
-    authenticate(&state, &cookies).await?;
-    let user = find_user_by_uid(&state, &uid).await?;
-    let posts = fetch_posts_for_user(&state, user.id, false).await?;
-    Ok(Json(UserProfileResponse {
-        username: user.username,
-        qq: user.qq,
-        uid: user.uid,
-        joined_at: user.created_at,
-        posts,
-    }))
+#[derive(Subcommand)]
+enum AdminCommand {
+    #[command(about = "将用户 UID 加入 config.toml 的管理员列表")]
+    Grant { uid: String },
+    #[command(about = "将用户 UID 移出 config.toml 的管理员列表")]
+    Revoke { uid: String },
 }
 
-async fn update_profile(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-    Json(payload): Json<UpdateProfilePayload>,
-) -> ApiResult<Json<UserResponse>> {
-    let mut user = authenticate(&state, &jar).await?;
-    cookies: Cookies,
-    Json(payload): Json<UpdateProfilePayload>,
-) -> ApiResult<Json<UserResponse>> {
-    let mut user = authenticate(&state, &cookies).await?;
-    let new_username = payload
-        .username
-        .map(|v| v.trim().to_string())
-        .filter(|v| !v.is_empty())
-        .unwrap_or_else(|| user.username.clone());
-    let new_qq = payload
-        .qq
-        .map(|v| v.trim().to_string())
-        .filter(|v| !v.is_empty())
-        .unwrap_or_else(|| user.qq.clone());
+#[derive(Subcommand)]
+enum PostCommand {
+    #[command(about = "删除帖子及其评论")]
+    Delete { id: i64 },
+    #[command(about = "删除单条评论")]
+    DeleteComment { id: i64 },
+}
 
-    if new_username == user.username && new_qq == user.qq {
-        return Err(ApiError::Validation("没有需要更新的内容".into()));
+#[derive(Subcommand)]
+enum SessionsCommand {
+    #[command(about = "清理过期会话，--all 时清空全部会话")]
+    Purge {
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(err) = run(Cli::parse()).await {
+        eprintln!("错误: {err}");
+        std::process::exit(1);
     }
+}
 
-    let result = sqlx::query("UPDATE users SET username = ?1, qq = ?2 WHERE id = ?3")
-        .bind(&new_username)
-        .bind(&new_qq)
-        .bind(user.id)
-        .execute(&state.db)
-        .await;
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let required = cli.config.is_some();
+    let config_path = cli.config.unwrap_or_else(|| PathBuf::from(CONFIG_PATH));
+    let bootstrap = tracing_subscriber::fmt()
+        .with_env_filter(log_filter())
+        .finish();
+    let config =
+        tracing::subscriber::with_default(bootstrap, || load_config(&config_path, required))?;
+    init_logging(&config.logging);
 
-    if let Err(err) = result {
-        if is_unique_violation(&err) {
-            return Err(ApiError::Conflict("用户名已存在".into()));
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            let _instance_lock = match config.database.backend {
+                DatabaseBackend::Sqlite => Some(lock_database(&config.database)?),
+                DatabaseBackend::Postgres => None,
+            };
+            serve(open_state(config).await?, config_path, required).await
         }
-        return Err(ApiError::from(err));
+        Command::User(command) => run_user_command(&open_state(config).await?, command).await,
+        Command::Admin(command) => {
+            run_admin_command(&open_state(config).await?, &config_path, command).await
+        }
+        Command::Post(command) => run_post_command(&open_state(config).await?, command).await,
+        Command::Sessions(command) => {
+            run_sessions_command(&open_state(config).await?, command).await
+        }
+        Command::Config(ConfigCommand::Check) => run_config_check(&config, &config_path),
+        Command::Restore { file } => restore_backup(&config, &file).await,
+        Command::Migrate { to } => migrate_to_postgres(&config, to).await,
+        Command::Backup(command) => run_backup_command(&open_state(config).await?, command).await,
     }
+}
 
-    user.username = new_username;
-    user.qq = new_qq;
-
-    let is_admin = state.config.is_admin(&user.uid);
-    Ok(Json(UserResponse {
-        id: user.id,
-        username: user.username,
-        qq: user.qq,
-        uid: user.uid,
-        is_admin,
-    }))
+fn log_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
 }
 
-async fn change_password(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-    cookies: Cookies,
-    Json(payload): Json<ChangePasswordPayload>,
-) -> ApiResult<Json<MessageResponse>> {
-    if payload.new_password.len() < 6 {
-        return Err(ApiError::Validation("新密码至少需要6位".into()));
+fn init_logging(logging: &LoggingSection) {
+    let subscriber = tracing_subscriber::fmt().with_env_filter(log_filter());
+    match logging.format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
     }
+}
 
-    let user = authenticate(&state, &jar).await?;
-    let user = authenticate(&state, &cookies).await?;
-    let current_hash: Option<String> =
-        sqlx::query_scalar("SELECT password_hash FROM users WHERE id = ?1")
-            .bind(user.id)
-            .fetch_optional(&state.db)
-            .await?;
-
-    let Some(current_hash) = current_hash else {
-        return Err(ApiError::Internal("用户不存在".into()));
+async fn open_state(config: AppConfig) -> Result<SharedState, sqlx::Error> {
+    let repo: Arc<dyn Repository> = match config.database.backend {
+        DatabaseBackend::Sqlite => Arc::new(SqliteRepository::connect(&config.database).await?),
+        DatabaseBackend::Postgres => Arc::new(
+            PostgresRepository::connect(
+                config.database.url.as_deref().unwrap_or_default(),
+                config.database.max_connections,
+            )
+            .await?,
+        ),
     };
-
-    if !verify_password(&current_hash, &payload.current_password)? {
-        return Err(ApiError::Validation("原密码错误".into()));
-    }
-
-    let new_hash = hash_password(&payload.new_password)?;
-    sqlx::query("UPDATE users SET password_hash = ?1 WHERE id = ?2")
-        .bind(new_hash)
-        .bind(user.id)
-        .execute(&state.db)
-        .await?;
-
-    Ok(Json(MessageResponse {
-        message: "密码修改成功".into(),
+    Ok(Arc::new(AppState {
+        repo: Arc::new(InstrumentedRepository { inner: repo }),
+        config: RwLock::new(Arc::new(config)),
+        shutdown: watch::Sender::new(false),
+        rate_limiter: RateLimiter::default(),
     }))
 }
 
-async fn fetch_comments(state: &SharedState, post_id: i64) -> ApiResult<Vec<CommentResponse>> {
-    let rows = sqlx::query_as::<_, DbComment>(
-        r#"SELECT c.id, c.content, c.is_anonymous, c.created_at, u.username, u.qq, u.uid
-           FROM comments c
-           LEFT JOIN users u ON c.user_id = u.id
-           WHERE c.post_id = ?1
-           ORDER BY c.created_at ASC"#,
-    )
-    .bind(post_id)
-    .fetch_all(&state.db)
-    .await?;
-
-    Ok(rows.into_iter().map(CommentResponse::from).collect())
+async fn connect_database(database: &DatabaseSection) -> Result<SqlitePool, sqlx::Error> {
+    SqlitePoolOptions::new()
+        .max_connections(database.max_connections)
+        .connect_with(database.connect_options())
+        .await
 }
 
-async fn load_post(state: &SharedState, post_id: i64) -> ApiResult<DbPost> {
-    sqlx::query_as::<_, DbPost>(
-        r#"SELECT p.id, p.user_id, p.title, p.content, p.category, p.is_anonymous, p.created_at,
-                  u.username, u.qq, u.uid
-           FROM posts p
-           LEFT JOIN users u ON p.user_id = u.id
-           WHERE p.id = ?1"#,
-    )
-    .bind(post_id)
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or(ApiError::NotFound)
+#[async_trait]
+trait Repository: Send + Sync {
+    async fn create_user(
+        &self,
+        uid: &str,
+        username: &str,
+        qq: &str,
+        password_hash: &str,
+        created_at: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn find_login_user(&self, username: &str) -> Result<Option<DbUser>, sqlx::Error>;
+    async fn find_user_by_uid(&self, uid: &str) -> Result<Option<DbAdminUser>, sqlx::Error>;
+    async fn find_user_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<DbAdminUser>, sqlx::Error>;
+    async fn find_public_user(&self, uid: &str) -> Result<Option<DbPublicUser>, sqlx::Error>;
+    async fn search_users(
+        &self,
+        keyword: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DbAdminUser>, sqlx::Error>;
+    async fn find_password_hash(&self, user_id: i64) -> Result<Option<String>, sqlx::Error>;
+    async fn update_password(&self, user_id: i64, password_hash: &str) -> Result<(), sqlx::Error>;
+    async fn replace_password(
+        &self,
+        user_id: i64,
+        password_hash: &str,
+        audit: Option<&NewAuditEntry<'_>>,
+    ) -> Result<(), sqlx::Error>;
+    async fn set_user_banned(
+        &self,
+        user_id: i64,
+        banned_at: Option<&str>,
+    ) -> Result<u64, sqlx::Error>;
+    async fn update_profile(
+        &self,
+        user_id: i64,
+        username: &str,
+        qq: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn find_privacy(&self, user_id: i64) -> Result<Option<DbPrivacy>, sqlx::Error>;
+    async fn update_privacy(&self, user_id: i64, privacy: &DbPrivacy) -> Result<(), sqlx::Error>;
+    async fn follow_stats(
+        &self,
+        user_id: i64,
+        viewer_id: i64,
+    ) -> Result<DbFollowStats, sqlx::Error>;
+    async fn follow(
+        &self,
+        follower_id: i64,
+        followee_id: i64,
+        now: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn unfollow(&self, follower_id: i64, followee_id: i64) -> Result<(), sqlx::Error>;
+    async fn admin_user_stats(&self, user_id: i64) -> Result<DbAdminUserStats, sqlx::Error>;
+    async fn export_profile(&self, user_id: i64) -> Result<ExportProfile, sqlx::Error>;
+    async fn export_posts(&self, user_id: i64) -> Result<Vec<DbExportPost>, sqlx::Error>;
+    async fn export_comments(&self, user_id: i64) -> Result<Vec<ExportComment>, sqlx::Error>;
+    async fn export_sessions(
+        &self,
+        user_id: i64,
+        current: Option<&str>,
+    ) -> Result<Vec<ExportSession>, sqlx::Error>;
+    async fn request_account_deletion(
+        &self,
+        user_id: i64,
+        mode: DeletionMode,
+        scheduled_at: Option<&str>,
+        now: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn cancel_account_deletion(&self, user_id: i64) -> Result<bool, sqlx::Error>;
+    async fn due_account_deletions(
+        &self,
+        now: &str,
+    ) -> Result<Vec<(i64, DeletionMode)>, sqlx::Error>;
+    async fn erase_account(
+        &self,
+        user_id: i64,
+        mode: DeletionMode,
+        now: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn create_session(
+        &self,
+        id: &str,
+        user_id: i64,
+        created_at: &str,
+        expires_at: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn find_session(&self, id: &str) -> Result<Option<DbSession>, sqlx::Error>;
+    async fn delete_session(&self, id: &str) -> Result<(), sqlx::Error>;
+    async fn revoke_sessions(
+        &self,
+        user_id: i64,
+        admin_id: i64,
+        uid: &str,
+    ) -> Result<u64, sqlx::Error>;
+    async fn purge_sessions(&self, expired_before: Option<&str>) -> Result<u64, sqlx::Error>;
+    async fn count_active_sessions(&self, now: &str) -> Result<i64, sqlx::Error>;
+    async fn create_post(&self, post: &NewPost, allow_new_tags: bool, now: &str) -> ApiResult<i64>;
+    async fn list_posts(&self, feed: &PostFeed) -> Result<Vec<DbPost>, sqlx::Error>;
+    async fn list_archived_posts(
+        &self,
+        viewer_id: i64,
+        category: Option<&str>,
+        full: bool,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DbPost>, sqlx::Error>;
+    async fn list_bookmarked_posts(
+        &self,
+        viewer_id: i64,
+        full: bool,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DbPost>, sqlx::Error>;
+    async fn list_user_posts(
+        &self,
+        user_id: i64,
+        include_anonymous: bool,
+        viewer_id: i64,
+        full: bool,
+    ) -> Result<Vec<DbPost>, sqlx::Error>;
+    async fn find_post(&self, post_id: i64, viewer_id: i64) -> Result<Option<DbPost>, sqlx::Error>;
+    async fn find_post_archived(&self, post_id: i64) -> Result<Option<bool>, sqlx::Error>;
+    async fn post_exists(&self, post_id: i64) -> Result<bool, sqlx::Error>;
+    async fn delete_post(&self, post_id: i64) -> Result<bool, sqlx::Error>;
+    async fn pin_post(
+        &self,
+        post_id: i64,
+        scope: PinScope,
+        pinned_at: &str,
+        expires_at: Option<&str>,
+    ) -> Result<bool, sqlx::Error>;
+    async fn unpin_post(&self, post_id: i64) -> Result<bool, sqlx::Error>;
+    async fn move_post(&self, post_id: i64, category: &str) -> Result<bool, sqlx::Error>;
+    async fn archive_expired_posts(&self, now: &str) -> Result<u64, sqlx::Error>;
+    async fn due_scheduled_posts(&self, now: &str) -> Result<Vec<i64>, sqlx::Error>;
+    async fn publish_post(&self, post_id: i64, now: &str) -> Result<bool, sqlx::Error>;
+    async fn list_drafts(&self, user_id: i64) -> Result<Vec<DbDraft>, sqlx::Error>;
+    async fn find_draft(&self, user_id: i64, draft_id: i64)
+        -> Result<Option<DbDraft>, sqlx::Error>;
+    async fn create_draft(
+        &self,
+        user_id: i64,
+        draft: &NewDraft,
+        allow_new_tags: bool,
+        now: &str,
+    ) -> ApiResult<i64>;
+    async fn update_draft(
+        &self,
+        user_id: i64,
+        draft_id: i64,
+        draft: &NewDraft,
+        allow_new_tags: bool,
+        now: &str,
+    ) -> ApiResult<bool>;
+    async fn create_comment(
+        &self,
+        post_id: i64,
+        user_id: i64,
+        content: &str,
+        anonymous: bool,
+        now: &str,
+    ) -> Result<i64, sqlx::Error>;
+    async fn list_comments(
+        &self,
+        post_id: i64,
+        viewer_id: i64,
+    ) -> Result<Vec<DbComment>, sqlx::Error>;
+    async fn delete_comment(
+        &self,
+        comment_id: i64,
+        audit: Option<&NewAuditEntry<'_>>,
+    ) -> Result<bool, sqlx::Error>;
+    async fn bookmark(&self, user_id: i64, post_id: i64, now: &str) -> Result<(), sqlx::Error>;
+    async fn unbookmark(&self, user_id: i64, post_id: i64) -> Result<(), sqlx::Error>;
+    async fn find_subscription(
+        &self,
+        user_id: i64,
+        post_id: i64,
+    ) -> Result<Option<bool>, sqlx::Error>;
+    async fn subscribe(
+        &self,
+        user_id: i64,
+        post_id: i64,
+        muted: bool,
+        now: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn unsubscribe(&self, user_id: i64, post_id: i64) -> Result<(), sqlx::Error>;
+    async fn list_notifications(
+        &self,
+        user_id: i64,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DbNotification>, sqlx::Error>;
+    async fn mark_notifications_read(&self, user_id: i64, now: &str) -> Result<(), sqlx::Error>;
+    async fn find_poll(&self, post_id: i64) -> Result<Option<DbPoll>, sqlx::Error>;
+    async fn list_poll_options(&self, poll_id: i64) -> Result<Vec<DbPollOption>, sqlx::Error>;
+    async fn list_poll_choices(&self, poll_id: i64, user_id: i64) -> Result<Vec<i64>, sqlx::Error>;
+    async fn count_poll_voters(&self, poll_id: i64) -> Result<i64, sqlx::Error>;
+    async fn list_poll_voters(&self, poll_id: i64) -> Result<Vec<DbPollVoter>, sqlx::Error>;
+    async fn cast_vote(
+        &self,
+        poll_id: i64,
+        user_id: i64,
+        option_ids: &[i64],
+        now: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn trending_tags(&self, since: &str, limit: i64) -> Result<Vec<DbTagCount>, sqlx::Error>;
+    async fn create_tag(&self, name: &str, now: &str) -> Result<(), sqlx::Error>;
+    async fn set_tag_banned(&self, name: &str, banned: bool) -> Result<bool, sqlx::Error>;
+    async fn find_tag(&self, name: &str) -> Result<Option<DbTag>, sqlx::Error>;
+    async fn resolve_tag(&self, name: &str) -> Result<Option<DbTag>, sqlx::Error>;
+    async fn merge_tag(&self, source_id: i64, target_id: i64) -> Result<(), sqlx::Error>;
+    async fn list_categories(&self, include_archived: bool)
+        -> Result<Vec<DbCategory>, sqlx::Error>;
+    async fn find_category(&self, slug: &str) -> Result<Option<DbCategory>, sqlx::Error>;
+    async fn create_category(&self, category: &DbCategory, now: &str) -> Result<(), sqlx::Error>;
+    async fn update_category(&self, category: &DbCategory) -> Result<(), sqlx::Error>;
+    async fn category_in_use(&self, slug: &str) -> Result<bool, sqlx::Error>;
+    async fn delete_category(&self, slug: &str) -> Result<bool, sqlx::Error>;
+    async fn reveal_author(
+        &self,
+        target: ModerationTarget,
+        target_id: i64,
+        audit: &NewAuditEntry<'_>,
+    ) -> Result<Option<DbAdminUser>, sqlx::Error>;
+    async fn record_audit(&self, audit: &NewAuditEntry<'_>) -> Result<(), sqlx::Error>;
+    async fn list_audit(&self, limit: i64, offset: i64) -> Result<Vec<DbAuditEntry>, sqlx::Error>;
+    async fn find_moderation_targets(
+        &self,
+        target: ModerationTarget,
+        filter: &ModerationFilter,
+    ) -> Result<Vec<i64>, sqlx::Error>;
+    async fn moderate(
+        &self,
+        target: ModerationTarget,
+        action: ModerationAction,
+        id: i64,
+        now: &str,
+    ) -> Result<bool, sqlx::Error>;
+    async fn schema_columns(&self) -> Result<Vec<(String, String)>, sqlx::Error>;
+    async fn backup(&self, path: &FsPath) -> ApiResult<()>;
+    async fn close(&self);
 }
 
-async fn fetch_posts_for_user(
-    state: &SharedState,
-    user_id: i64,
-    include_anonymous: bool,
-) -> ApiResult<Vec<PostSummary>> {
-    let rows = sqlx::query_as::<_, DbPost>(
-        r#"SELECT p.id, p.user_id, p.title, p.content, p.category, p.is_anonymous, p.created_at,
-                  u.username, u.qq, u.uid
-           FROM posts p
-           LEFT JOIN users u ON p.user_id = u.id
-           WHERE p.user_id = ?1 AND (?2 = 1 OR p.is_anonymous = 0)
-           ORDER BY p.created_at DESC"#,
-    )
-    .bind(user_id)
-    .bind(bool_to_int(include_anonymous))
-    .fetch_all(&state.db)
-    .await?;
-
-    Ok(rows.into_iter().map(PostSummary::from).collect())
+struct InstrumentedRepository {
+    inner: Arc<dyn Repository>,
 }
 
-async fn find_user_by_uid(state: &SharedState, uid: &str) -> ApiResult<DbPublicUser> {
-    sqlx::query_as::<_, DbPublicUser>(
-        "SELECT id, uid, username, qq, created_at FROM users WHERE uid = ?1",
-    )
-    .bind(uid)
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or(ApiError::NotFound)
+async fn timed<T>(operation: &'static str, query: impl std::future::Future<Output = T>) -> T {
+    let started = Instant::now();
+    let result = query.await;
+    histogram!(METRIC_DB_DURATION, "operation" => operation)
+        .record(started.elapsed().as_secs_f64());
+    result
 }
 
-fn bool_to_int(flag: bool) -> i64 {
-    if flag {
-        1
-    } else {
-        0
+#[async_trait]
+impl Repository for InstrumentedRepository {
+    async fn create_user(
+        &self,
+        uid: &str,
+        username: &str,
+        qq: &str,
+        password_hash: &str,
+        created_at: &str,
+    ) -> Result<(), sqlx::Error> {
+        timed(
+            "create_user",
+            self.inner
+                .create_user(uid, username, qq, password_hash, created_at),
+        )
+        .await
     }
-}
 
-fn normalize_post_category(category: Option<String>) -> Result<String, ApiError> {
-    let value = category.unwrap_or_else(|| DEFAULT_CATEGORY.to_string());
-    let trimmed = value.trim();
-    let normalized = if trimmed.is_empty() {
-        DEFAULT_CATEGORY
-    } else {
-        trimmed
-    };
-    if CATEGORIES.contains(&normalized) {
-        Ok(normalized.to_string())
-    } else {
-        Err(ApiError::Validation("请选择有效的分区".into()))
+    async fn find_login_user(&self, username: &str) -> Result<Option<DbUser>, sqlx::Error> {
+        timed("find_login_user", self.inner.find_login_user(username)).await
     }
-}
 
-fn normalize_query_category(category: Option<String>) -> Result<Option<String>, ApiError> {
-    if let Some(raw) = category {
-        let trimmed = raw.trim();
-        if trimmed.is_empty() || trimmed == LATEST_CATEGORY {
-            return Ok(None);
-        }
-        if CATEGORIES.contains(&trimmed) {
-            return Ok(Some(trimmed.to_string()));
-        }
-        return Err(ApiError::Validation("未知的分区筛选".into()));
+    async fn find_user_by_uid(&self, uid: &str) -> Result<Option<DbAdminUser>, sqlx::Error> {
+        timed("find_user_by_uid", self.inner.find_user_by_uid(uid)).await
     }
-    Ok(None)
-}
 
-async fn ensure_column(
-    pool: &SqlitePool,
-    table: &str,
-    column: &str,
-    definition: &str,
-) -> Result<(), sqlx::Error> {
-    let query = format!("ALTER TABLE {table} ADD COLUMN {column} {definition}");
-    match sqlx::query(&query).execute(pool).await {
-        Ok(_) => Ok(()),
-        Err(sqlx::Error::Database(err)) if err.message().contains("duplicate column name") => {
-            Ok(())
-        }
-        Err(err) => Err(err),
+    async fn find_user_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<DbAdminUser>, sqlx::Error> {
+        timed(
+            "find_user_by_username",
+            self.inner.find_user_by_username(username),
+        )
+        .await
     }
-}
 
-async fn delete_post(
-    State(state): State<SharedState>,
-    jar: CookieJar,
-    Path(post_id): Path<i64>,
-) -> ApiResult<Json<MessageResponse>> {
-    let user = authenticate(&state, &jar).await?;
-    cookies: Cookies,
-    Path(post_id): Path<i64>,
-) -> ApiResult<Json<MessageResponse>> {
-    let user = authenticate(&state, &cookies).await?;
-    if !state.config.is_admin(&user.uid) {
-        return Err(ApiError::Forbidden);
+    async fn find_public_user(&self, uid: &str) -> Result<Option<DbPublicUser>, sqlx::Error> {
+        timed("find_public_user", self.inner.find_public_user(uid)).await
     }
 
-    let result = sqlx::query("DELETE FROM posts WHERE id = ?1")
-        .bind(post_id)
-        .execute(&state.db)
-        .await?;
-
-    if result.rows_affected() == 0 {
-        return Err(ApiError::NotFound);
+    async fn search_users(
+        &self,
+        keyword: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DbAdminUser>, sqlx::Error> {
+        timed(
+            "search_users",
+            self.inner.search_users(keyword, limit, offset),
+        )
+        .await
     }
 
-    Ok(Json(MessageResponse {
-        message: "帖子已删除".into(),
-    }))
-}
+    async fn find_password_hash(&self, user_id: i64) -> Result<Option<String>, sqlx::Error> {
+        timed("find_password_hash", self.inner.find_password_hash(user_id)).await
+    }
 
-fn hash_password(password: &str) -> ApiResult<String> {
-    let salt = SaltString::generate(&mut OsRng);
-    Argon2::default()
-        .hash_password(password.as_bytes(), &salt)
-        .map(|hash| hash.to_string())
-        .map_err(ApiError::from)
-}
+    async fn update_password(&self, user_id: i64, password_hash: &str) -> Result<(), sqlx::Error> {
+        timed(
+            "update_password",
+            self.inner.update_password(user_id, password_hash),
+        )
+        .await
+    }
 
-fn verify_password(hash: &str, password: &str) -> ApiResult<bool> {
-    let parsed = PasswordHash::new(hash).map_err(ApiError::from)?;
-    match Argon2::default().verify_password(password.as_bytes(), &parsed) {
-        Ok(_) => Ok(true),
-        Err(PasswordHashError::Password) => Ok(false),
-        Err(err) => Err(ApiError::from(err)),
+    async fn replace_password(
+        &self,
+        user_id: i64,
+        password_hash: &str,
+        audit: Option<&NewAuditEntry<'_>>,
+    ) -> Result<(), sqlx::Error> {
+        timed(
+            "replace_password",
+            self.inner.replace_password(user_id, password_hash, audit),
+        )
+        .await
     }
-}
 
-async fn authenticate(state: &SharedState, jar: &CookieJar) -> ApiResult<AuthedUser> {
-    let Some(cookie) = jar.get(SESSION_COOKIE) else {
-async fn authenticate(state: &SharedState, cookies: &Cookies) -> ApiResult<AuthedUser> {
-    let Some(cookie) = cookies.get(SESSION_COOKIE) else {
-        return Err(ApiError::Unauthorized);
-    };
-    let token = cookie.value().to_string();
+    async fn set_user_banned(
+        &self,
+        user_id: i64,
+        banned_at: Option<&str>,
+    ) -> Result<u64, sqlx::Error> {
+        timed(
+            "set_user_banned",
+            self.inner.set_user_banned(user_id, banned_at),
+        )
+        .await
+    }
 
-    let session = sqlx::query_as::<_, DbSession>(
-        r#"SELECT s.user_id, s.expires_at, u.username, u.qq, u.uid
-           FROM sessions s
-           JOIN users u ON u.id = s.user_id
-           WHERE s.id = ?1"#,
+    async fn update_profile(
+        &self,
+        user_id: i64,
+        username: &str,
+        qq: &str,
+    ) -> Result<(), sqlx::Error> {
+        timed(
+            "update_profile",
+            self.inner.update_profile(user_id, username, qq),
+        )
+        .await
+    }
+
+    async fn find_privacy(&self, user_id: i64) -> Result<Option<DbPrivacy>, sqlx::Error> {
+        timed("find_privacy", self.inner.find_privacy(user_id)).await
+    }
+
+    async fn update_privacy(&self, user_id: i64, privacy: &DbPrivacy) -> Result<(), sqlx::Error> {
+        timed(
+            "update_privacy",
+            self.inner.update_privacy(user_id, privacy),
+        )
+        .await
+    }
+
+    async fn follow_stats(
+        &self,
+        user_id: i64,
+        viewer_id: i64,
+    ) -> Result<DbFollowStats, sqlx::Error> {
+        timed("follow_stats", self.inner.follow_stats(user_id, viewer_id)).await
+    }
+
+    async fn follow(
+        &self,
+        follower_id: i64,
+        followee_id: i64,
+        now: &str,
+    ) -> Result<(), sqlx::Error> {
+        timed("follow", self.inner.follow(follower_id, followee_id, now)).await
+    }
+
+    async fn unfollow(&self, follower_id: i64, followee_id: i64) -> Result<(), sqlx::Error> {
+        timed("unfollow", self.inner.unfollow(follower_id, followee_id)).await
+    }
+
+    async fn admin_user_stats(&self, user_id: i64) -> Result<DbAdminUserStats, sqlx::Error> {
+        timed("admin_user_stats", self.inner.admin_user_stats(user_id)).await
+    }
+
+    async fn export_profile(&self, user_id: i64) -> Result<ExportProfile, sqlx::Error> {
+        timed("export_profile", self.inner.export_profile(user_id)).await
+    }
+
+    async fn export_posts(&self, user_id: i64) -> Result<Vec<DbExportPost>, sqlx::Error> {
+        timed("export_posts", self.inner.export_posts(user_id)).await
+    }
+
+    async fn export_comments(&self, user_id: i64) -> Result<Vec<ExportComment>, sqlx::Error> {
+        timed("export_comments", self.inner.export_comments(user_id)).await
+    }
+
+    async fn export_sessions(
+        &self,
+        user_id: i64,
+        current: Option<&str>,
+    ) -> Result<Vec<ExportSession>, sqlx::Error> {
+        timed(
+            "export_sessions",
+            self.inner.export_sessions(user_id, current),
+        )
+        .await
+    }
+
+    async fn request_account_deletion(
+        &self,
+        user_id: i64,
+        mode: DeletionMode,
+        scheduled_at: Option<&str>,
+        now: &str,
+    ) -> Result<(), sqlx::Error> {
+        timed(
+            "request_account_deletion",
+            self.inner
+                .request_account_deletion(user_id, mode, scheduled_at, now),
+        )
+        .await
+    }
+
+    async fn cancel_account_deletion(&self, user_id: i64) -> Result<bool, sqlx::Error> {
+        timed(
+            "cancel_account_deletion",
+            self.inner.cancel_account_deletion(user_id),
+        )
+        .await
+    }
+
+    async fn due_account_deletions(
+        &self,
+        now: &str,
+    ) -> Result<Vec<(i64, DeletionMode)>, sqlx::Error> {
+        timed(
+            "due_account_deletions",
+            self.inner.due_account_deletions(now),
+        )
+        .await
+    }
+
+    async fn erase_account(
+        &self,
+        user_id: i64,
+        mode: DeletionMode,
+        now: &str,
+    ) -> Result<(), sqlx::Error> {
+        timed(
+            "erase_account",
+            self.inner.erase_account(user_id, mode, now),
+        )
+        .await
+    }
+
+    async fn create_session(
+        &self,
+        id: &str,
+        user_id: i64,
+        created_at: &str,
+        expires_at: &str,
+    ) -> Result<(), sqlx::Error> {
+        timed(
+            "create_session",
+            self.inner
+                .create_session(id, user_id, created_at, expires_at),
+        )
+        .await
+    }
+
+    async fn find_session(&self, id: &str) -> Result<Option<DbSession>, sqlx::Error> {
+        timed("find_session", self.inner.find_session(id)).await
+    }
+
+    async fn delete_session(&self, id: &str) -> Result<(), sqlx::Error> {
+        timed("delete_session", self.inner.delete_session(id)).await
+    }
+
+    async fn revoke_sessions(
+        &self,
+        user_id: i64,
+        admin_id: i64,
+        uid: &str,
+    ) -> Result<u64, sqlx::Error> {
+        timed(
+            "revoke_sessions",
+            self.inner.revoke_sessions(user_id, admin_id, uid),
+        )
+        .await
+    }
+
+    async fn purge_sessions(&self, expired_before: Option<&str>) -> Result<u64, sqlx::Error> {
+        timed("purge_sessions", self.inner.purge_sessions(expired_before)).await
+    }
+
+    async fn count_active_sessions(&self, now: &str) -> Result<i64, sqlx::Error> {
+        timed(
+            "count_active_sessions",
+            self.inner.count_active_sessions(now),
+        )
+        .await
+    }
+
+    async fn create_post(&self, post: &NewPost, allow_new_tags: bool, now: &str) -> ApiResult<i64> {
+        timed(
+            "create_post",
+            self.inner.create_post(post, allow_new_tags, now),
+        )
+        .await
+    }
+
+    async fn list_posts(&self, feed: &PostFeed) -> Result<Vec<DbPost>, sqlx::Error> {
+        timed("list_posts", self.inner.list_posts(feed)).await
+    }
+
+    async fn list_archived_posts(
+        &self,
+        viewer_id: i64,
+        category: Option<&str>,
+        full: bool,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DbPost>, sqlx::Error> {
+        timed(
+            "list_archived_posts",
+            self.inner
+                .list_archived_posts(viewer_id, category, full, limit, offset),
+        )
+        .await
+    }
+
+    async fn list_bookmarked_posts(
+        &self,
+        viewer_id: i64,
+        full: bool,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DbPost>, sqlx::Error> {
+        timed(
+            "list_bookmarked_posts",
+            self.inner
+                .list_bookmarked_posts(viewer_id, full, limit, offset),
+        )
+        .await
+    }
+
+    async fn list_user_posts(
+        &self,
+        user_id: i64,
+        include_anonymous: bool,
+        viewer_id: i64,
+        full: bool,
+    ) -> Result<Vec<DbPost>, sqlx::Error> {
+        timed(
+            "list_user_posts",
+            self.inner
+                .list_user_posts(user_id, include_anonymous, viewer_id, full),
+        )
+        .await
+    }
+
+    async fn find_post(&self, post_id: i64, viewer_id: i64) -> Result<Option<DbPost>, sqlx::Error> {
+        timed("find_post", self.inner.find_post(post_id, viewer_id)).await
+    }
+
+    async fn find_post_archived(&self, post_id: i64) -> Result<Option<bool>, sqlx::Error> {
+        timed("find_post_archived", self.inner.find_post_archived(post_id)).await
+    }
+
+    async fn post_exists(&self, post_id: i64) -> Result<bool, sqlx::Error> {
+        timed("post_exists", self.inner.post_exists(post_id)).await
+    }
+
+    async fn delete_post(&self, post_id: i64) -> Result<bool, sqlx::Error> {
+        timed("delete_post", self.inner.delete_post(post_id)).await
+    }
+
+    async fn pin_post(
+        &self,
+        post_id: i64,
+        scope: PinScope,
+        pinned_at: &str,
+        expires_at: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        timed(
+            "pin_post",
+            self.inner.pin_post(post_id, scope, pinned_at, expires_at),
+        )
+        .await
+    }
+
+    async fn unpin_post(&self, post_id: i64) -> Result<bool, sqlx::Error> {
+        timed("unpin_post", self.inner.unpin_post(post_id)).await
+    }
+
+    async fn move_post(&self, post_id: i64, category: &str) -> Result<bool, sqlx::Error> {
+        timed("move_post", self.inner.move_post(post_id, category)).await
+    }
+
+    async fn archive_expired_posts(&self, now: &str) -> Result<u64, sqlx::Error> {
+        timed(
+            "archive_expired_posts",
+            self.inner.archive_expired_posts(now),
+        )
+        .await
+    }
+
+    async fn due_scheduled_posts(&self, now: &str) -> Result<Vec<i64>, sqlx::Error> {
+        timed("due_scheduled_posts", self.inner.due_scheduled_posts(now)).await
+    }
+
+    async fn publish_post(&self, post_id: i64, now: &str) -> Result<bool, sqlx::Error> {
+        timed("publish_post", self.inner.publish_post(post_id, now)).await
+    }
+
+    async fn list_drafts(&self, user_id: i64) -> Result<Vec<DbDraft>, sqlx::Error> {
+        timed("list_drafts", self.inner.list_drafts(user_id)).await
+    }
+
+    async fn find_draft(
+        &self,
+        user_id: i64,
+        draft_id: i64,
+    ) -> Result<Option<DbDraft>, sqlx::Error> {
+        timed("find_draft", self.inner.find_draft(user_id, draft_id)).await
+    }
+
+    async fn create_draft(
+        &self,
+        user_id: i64,
+        draft: &NewDraft,
+        allow_new_tags: bool,
+        now: &str,
+    ) -> ApiResult<i64> {
+        timed(
+            "create_draft",
+            self.inner.create_draft(user_id, draft, allow_new_tags, now),
+        )
+        .await
+    }
+
+    async fn update_draft(
+        &self,
+        user_id: i64,
+        draft_id: i64,
+        draft: &NewDraft,
+        allow_new_tags: bool,
+        now: &str,
+    ) -> ApiResult<bool> {
+        timed(
+            "update_draft",
+            self.inner
+                .update_draft(user_id, draft_id, draft, allow_new_tags, now),
+        )
+        .await
+    }
+
+    async fn create_comment(
+        &self,
+        post_id: i64,
+        user_id: i64,
+        content: &str,
+        anonymous: bool,
+        now: &str,
+    ) -> Result<i64, sqlx::Error> {
+        timed(
+            "create_comment",
+            self.inner
+                .create_comment(post_id, user_id, content, anonymous, now),
+        )
+        .await
+    }
+
+    async fn list_comments(
+        &self,
+        post_id: i64,
+        viewer_id: i64,
+    ) -> Result<Vec<DbComment>, sqlx::Error> {
+        timed(
+            "list_comments",
+            self.inner.list_comments(post_id, viewer_id),
+        )
+        .await
+    }
+
+    async fn delete_comment(
+        &self,
+        comment_id: i64,
+        audit: Option<&NewAuditEntry<'_>>,
+    ) -> Result<bool, sqlx::Error> {
+        timed(
+            "delete_comment",
+            self.inner.delete_comment(comment_id, audit),
+        )
+        .await
+    }
+
+    async fn bookmark(&self, user_id: i64, post_id: i64, now: &str) -> Result<(), sqlx::Error> {
+        timed("bookmark", self.inner.bookmark(user_id, post_id, now)).await
+    }
+
+    async fn unbookmark(&self, user_id: i64, post_id: i64) -> Result<(), sqlx::Error> {
+        timed("unbookmark", self.inner.unbookmark(user_id, post_id)).await
+    }
+
+    async fn find_subscription(
+        &self,
+        user_id: i64,
+        post_id: i64,
+    ) -> Result<Option<bool>, sqlx::Error> {
+        timed(
+            "find_subscription",
+            self.inner.find_subscription(user_id, post_id),
+        )
+        .await
+    }
+
+    async fn subscribe(
+        &self,
+        user_id: i64,
+        post_id: i64,
+        muted: bool,
+        now: &str,
+    ) -> Result<(), sqlx::Error> {
+        timed(
+            "subscribe",
+            self.inner.subscribe(user_id, post_id, muted, now),
+        )
+        .await
+    }
+
+    async fn unsubscribe(&self, user_id: i64, post_id: i64) -> Result<(), sqlx::Error> {
+        timed("unsubscribe", self.inner.unsubscribe(user_id, post_id)).await
+    }
+
+    async fn list_notifications(
+        &self,
+        user_id: i64,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DbNotification>, sqlx::Error> {
+        timed(
+            "list_notifications",
+            self.inner.list_notifications(user_id, limit, offset),
+        )
+        .await
+    }
+
+    async fn mark_notifications_read(&self, user_id: i64, now: &str) -> Result<(), sqlx::Error> {
+        timed(
+            "mark_notifications_read",
+            self.inner.mark_notifications_read(user_id, now),
+        )
+        .await
+    }
+
+    async fn find_poll(&self, post_id: i64) -> Result<Option<DbPoll>, sqlx::Error> {
+        timed("find_poll", self.inner.find_poll(post_id)).await
+    }
+
+    async fn list_poll_options(&self, poll_id: i64) -> Result<Vec<DbPollOption>, sqlx::Error> {
+        timed("list_poll_options", self.inner.list_poll_options(poll_id)).await
+    }
+
+    async fn list_poll_choices(&self, poll_id: i64, user_id: i64) -> Result<Vec<i64>, sqlx::Error> {
+        timed(
+            "list_poll_choices",
+            self.inner.list_poll_choices(poll_id, user_id),
+        )
+        .await
+    }
+
+    async fn count_poll_voters(&self, poll_id: i64) -> Result<i64, sqlx::Error> {
+        timed("count_poll_voters", self.inner.count_poll_voters(poll_id)).await
+    }
+
+    async fn list_poll_voters(&self, poll_id: i64) -> Result<Vec<DbPollVoter>, sqlx::Error> {
+        timed("list_poll_voters", self.inner.list_poll_voters(poll_id)).await
+    }
+
+    async fn cast_vote(
+        &self,
+        poll_id: i64,
+        user_id: i64,
+        option_ids: &[i64],
+        now: &str,
+    ) -> Result<(), sqlx::Error> {
+        timed(
+            "cast_vote",
+            self.inner.cast_vote(poll_id, user_id, option_ids, now),
+        )
+        .await
+    }
+
+    async fn trending_tags(&self, since: &str, limit: i64) -> Result<Vec<DbTagCount>, sqlx::Error> {
+        timed("trending_tags", self.inner.trending_tags(since, limit)).await
+    }
+
+    async fn create_tag(&self, name: &str, now: &str) -> Result<(), sqlx::Error> {
+        timed("create_tag", self.inner.create_tag(name, now)).await
+    }
+
+    async fn set_tag_banned(&self, name: &str, banned: bool) -> Result<bool, sqlx::Error> {
+        timed("set_tag_banned", self.inner.set_tag_banned(name, banned)).await
+    }
+
+    async fn find_tag(&self, name: &str) -> Result<Option<DbTag>, sqlx::Error> {
+        timed("find_tag", self.inner.find_tag(name)).await
+    }
+
+    async fn resolve_tag(&self, name: &str) -> Result<Option<DbTag>, sqlx::Error> {
+        timed("resolve_tag", self.inner.resolve_tag(name)).await
+    }
+
+    async fn merge_tag(&self, source_id: i64, target_id: i64) -> Result<(), sqlx::Error> {
+        timed("merge_tag", self.inner.merge_tag(source_id, target_id)).await
+    }
+
+    async fn list_categories(
+        &self,
+        include_archived: bool,
+    ) -> Result<Vec<DbCategory>, sqlx::Error> {
+        timed(
+            "list_categories",
+            self.inner.list_categories(include_archived),
+        )
+        .await
+    }
+
+    async fn find_category(&self, slug: &str) -> Result<Option<DbCategory>, sqlx::Error> {
+        timed("find_category", self.inner.find_category(slug)).await
+    }
+
+    async fn create_category(&self, category: &DbCategory, now: &str) -> Result<(), sqlx::Error> {
+        timed("create_category", self.inner.create_category(category, now)).await
+    }
+
+    async fn update_category(&self, category: &DbCategory) -> Result<(), sqlx::Error> {
+        timed("update_category", self.inner.update_category(category)).await
+    }
+
+    async fn category_in_use(&self, slug: &str) -> Result<bool, sqlx::Error> {
+        timed("category_in_use", self.inner.category_in_use(slug)).await
+    }
+
+    async fn delete_category(&self, slug: &str) -> Result<bool, sqlx::Error> {
+        timed("delete_category", self.inner.delete_category(slug)).await
+    }
+
+    async fn reveal_author(
+        &self,
+        target: ModerationTarget,
+        target_id: i64,
+        audit: &NewAuditEntry<'_>,
+    ) -> Result<Option<DbAdminUser>, sqlx::Error> {
+        timed(
+            "reveal_author",
+            self.inner.reveal_author(target, target_id, audit),
+        )
+        .await
+    }
+
+    async fn record_audit(&self, audit: &NewAuditEntry<'_>) -> Result<(), sqlx::Error> {
+        timed("record_audit", self.inner.record_audit(audit)).await
+    }
+
+    async fn list_audit(&self, limit: i64, offset: i64) -> Result<Vec<DbAuditEntry>, sqlx::Error> {
+        timed("list_audit", self.inner.list_audit(limit, offset)).await
+    }
+
+    async fn find_moderation_targets(
+        &self,
+        target: ModerationTarget,
+        filter: &ModerationFilter,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        timed(
+            "find_moderation_targets",
+            self.inner.find_moderation_targets(target, filter),
+        )
+        .await
+    }
+
+    async fn moderate(
+        &self,
+        target: ModerationTarget,
+        action: ModerationAction,
+        id: i64,
+        now: &str,
+    ) -> Result<bool, sqlx::Error> {
+        timed("moderate", self.inner.moderate(target, action, id, now)).await
+    }
+
+    async fn schema_columns(&self) -> Result<Vec<(String, String)>, sqlx::Error> {
+        timed("schema_columns", self.inner.schema_columns()).await
+    }
+
+    async fn backup(&self, path: &FsPath) -> ApiResult<()> {
+        timed("backup", self.inner.backup(path)).await
+    }
+
+    async fn close(&self) {
+        self.inner.close().await
+    }
+}
+
+const POSTGRES_SCHEMA: &[&str] = &[
+    r#"CREATE TABLE IF NOT EXISTS users (
+        id BIGSERIAL PRIMARY KEY,
+        uid TEXT NOT NULL UNIQUE,
+        username TEXT NOT NULL UNIQUE,
+        qq TEXT NOT NULL,
+        password_hash TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        qq_visibility TEXT NOT NULL DEFAULT 'everyone',
+        profile_hidden BOOLEAN NOT NULL DEFAULT FALSE,
+        posts_hidden BOOLEAN NOT NULL DEFAULT FALSE,
+        deletion_scheduled_at TEXT,
+        deletion_mode TEXT,
+        deleted_at TEXT,
+        banned_at TEXT
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS follows (
+        follower_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        followee_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY(follower_id, followee_id)
+    )"#,
+    "CREATE INDEX IF NOT EXISTS idx_follows_followee ON follows(followee_id)",
+    r#"CREATE TABLE IF NOT EXISTS sessions (
+        id TEXT PRIMARY KEY,
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        created_at TEXT NOT NULL,
+        expires_at TEXT NOT NULL
+    )"#,
+    "CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions(user_id)",
+    r#"CREATE TABLE IF NOT EXISTS posts (
+        id BIGSERIAL PRIMARY KEY,
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        title TEXT NOT NULL DEFAULT '',
+        content TEXT NOT NULL,
+        category TEXT NOT NULL DEFAULT '其它',
+        is_anonymous BOOLEAN NOT NULL,
+        created_at TEXT NOT NULL,
+        pin_scope TEXT,
+        pinned_at TEXT,
+        pin_expires_at TEXT,
+        status TEXT NOT NULL DEFAULT 'published',
+        publish_at TEXT,
+        updated_at TEXT,
+        expires_at TEXT,
+        archived_at TEXT,
+        hidden_at TEXT,
+        comment_count BIGINT NOT NULL DEFAULT 0,
+        last_comment_at TEXT,
+        last_activity_at TEXT NOT NULL DEFAULT '',
+        hot_score DOUBLE PRECISION NOT NULL DEFAULT 0
+    )"#,
+    "CREATE INDEX IF NOT EXISTS idx_posts_pinned ON posts(pin_scope) WHERE pin_scope IS NOT NULL",
+    "CREATE INDEX IF NOT EXISTS idx_posts_drafts ON posts(user_id, publish_at) WHERE status = 'draft'",
+    "CREATE INDEX IF NOT EXISTS idx_posts_expiry ON posts(expires_at) WHERE archived_at IS NULL",
+    "CREATE INDEX IF NOT EXISTS idx_posts_archived ON posts(archived_at) WHERE archived_at IS NOT NULL",
+    "CREATE INDEX IF NOT EXISTS idx_posts_created ON posts(created_at DESC)",
+    "CREATE INDEX IF NOT EXISTS idx_posts_activity ON posts(last_activity_at DESC)",
+    "CREATE INDEX IF NOT EXISTS idx_posts_comments ON posts(comment_count DESC)",
+    "CREATE INDEX IF NOT EXISTS idx_posts_hot ON posts(hot_score DESC)",
+    r#"CREATE TABLE IF NOT EXISTS categories (
+        slug TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        description TEXT NOT NULL DEFAULT '',
+        sort_order BIGINT NOT NULL DEFAULT 0,
+        archived BOOLEAN NOT NULL DEFAULT FALSE,
+        post_permission TEXT NOT NULL DEFAULT 'everyone',
+        created_at TEXT NOT NULL,
+        post_lifetime_days BIGINT
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS comments (
+        id BIGSERIAL PRIMARY KEY,
+        post_id BIGINT NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        content TEXT NOT NULL,
+        is_anonymous BOOLEAN NOT NULL,
+        created_at TEXT NOT NULL,
+        hidden_at TEXT
+    )"#,
+    "CREATE INDEX IF NOT EXISTS idx_comments_post ON comments(post_id)",
+    "CREATE INDEX IF NOT EXISTS idx_comments_user ON comments(user_id)",
+    r#"CREATE TABLE IF NOT EXISTS bookmarks (
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        post_id BIGINT NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY(user_id, post_id)
+    )"#,
+    "CREATE INDEX IF NOT EXISTS idx_bookmarks_post ON bookmarks(post_id)",
+    r#"CREATE TABLE IF NOT EXISTS post_subscriptions (
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        post_id BIGINT NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+        muted BOOLEAN NOT NULL DEFAULT FALSE,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY(user_id, post_id)
+    )"#,
+    "CREATE INDEX IF NOT EXISTS idx_post_subscriptions_post ON post_subscriptions(post_id)",
+    r#"CREATE TABLE IF NOT EXISTS notifications (
+        id BIGSERIAL PRIMARY KEY,
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        kind TEXT NOT NULL,
+        post_id BIGINT NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+        comment_id BIGINT REFERENCES comments(id) ON DELETE CASCADE,
+        created_at TEXT NOT NULL,
+        read_at TEXT
+    )"#,
+    "CREATE INDEX IF NOT EXISTS idx_notifications_user ON notifications(user_id, created_at)",
+    r#"CREATE TABLE IF NOT EXISTS tags (
+        id BIGSERIAL PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        banned BOOLEAN NOT NULL DEFAULT FALSE,
+        merged_into BIGINT REFERENCES tags(id) ON DELETE SET NULL DEFERRABLE INITIALLY DEFERRED,
+        created_at TEXT NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS post_tags (
+        post_id BIGINT NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+        tag_id BIGINT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+        PRIMARY KEY(post_id, tag_id)
+    )"#,
+    "CREATE INDEX IF NOT EXISTS idx_post_tags_tag ON post_tags(tag_id)",
+    r#"CREATE TABLE IF NOT EXISTS polls (
+        id BIGSERIAL PRIMARY KEY,
+        post_id BIGINT NOT NULL UNIQUE REFERENCES posts(id) ON DELETE CASCADE,
+        multiple BOOLEAN NOT NULL DEFAULT FALSE,
+        anonymous_votes BOOLEAN NOT NULL DEFAULT TRUE,
+        closes_at TEXT,
+        created_at TEXT NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS poll_options (
+        id BIGSERIAL PRIMARY KEY,
+        poll_id BIGINT NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+        position BIGINT NOT NULL,
+        label TEXT NOT NULL
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS poll_votes (
+        poll_id BIGINT NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+        user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY(poll_id, user_id)
+    )"#,
+    r#"CREATE TABLE IF NOT EXISTS poll_vote_choices (
+        poll_id BIGINT NOT NULL,
+        user_id BIGINT NOT NULL,
+        option_id BIGINT NOT NULL REFERENCES poll_options(id) ON DELETE CASCADE,
+        PRIMARY KEY(poll_id, user_id, option_id),
+        FOREIGN KEY(poll_id, user_id) REFERENCES poll_votes(poll_id, user_id) ON DELETE CASCADE
+    )"#,
+    "CREATE INDEX IF NOT EXISTS idx_poll_vote_choices_option ON poll_vote_choices(option_id)",
+    r#"CREATE TABLE IF NOT EXISTS admin_audit_log (
+        id BIGSERIAL PRIMARY KEY,
+        admin_id BIGINT NOT NULL,
+        action TEXT NOT NULL,
+        target_type TEXT NOT NULL,
+        target_id TEXT NOT NULL,
+        detail TEXT,
+        created_at TEXT NOT NULL
+    )"#,
+];
+
+struct SqlDialect {
+    now: &'static str,
+    like: &'static str,
+}
+
+const SQLITE_DIALECT: SqlDialect = SqlDialect {
+    now: "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')",
+    like: "LIKE",
+};
+
+const POSTGRES_DIALECT: SqlDialect = SqlDialect {
+    now: r#"to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"')"#,
+    like: "ILIKE",
+};
+
+macro_rules! sql_repository {
+    ($name:ident, $pool:ty, $conn:ty, $dialect:expr, { $($backend:tt)* }) => {
+        struct $name {
+            pool: $pool,
+        }
+
+        impl $name {
+            const DIALECT: SqlDialect = $dialect;
+
+            fn post_select(full: bool) -> String {
+                post_select(&Self::DIALECT, full)
+            }
+
+            async fn record_audit(
+                conn: &mut $conn,
+                audit: &NewAuditEntry<'_>,
+            ) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"INSERT INTO admin_audit_log (admin_id, action, target_type, target_id, detail, created_at)
+                       VALUES ($1, $2, $3, $4, $5, $6)"#,
+                )
+                .bind(audit.admin_id)
+                .bind(audit.action)
+                .bind(audit.target_type)
+                .bind(audit.target_id)
+                .bind(audit.detail)
+                .bind(now_iso())
+                .execute(conn)
+                .await?;
+                Ok(())
+            }
+
+            async fn replace_password(
+                conn: &mut $conn,
+                user_id: i64,
+                password_hash: &str,
+            ) -> Result<(), sqlx::Error> {
+                sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+                    .bind(password_hash)
+                    .bind(user_id)
+                    .execute(&mut *conn)
+                    .await?;
+                sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+                    .bind(user_id)
+                    .execute(&mut *conn)
+                    .await?;
+                Ok(())
+            }
+
+            async fn erase_account(
+                conn: &mut $conn,
+                user_id: i64,
+                mode: DeletionMode,
+                now: &str,
+            ) -> Result<(), sqlx::Error> {
+                sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+                    .bind(user_id)
+                    .execute(&mut *conn)
+                    .await?;
+                let removed: Vec<i64> = sqlx::query_scalar(
+                    "SELECT id FROM posts WHERE user_id = $1 AND ($2 OR status = 'draft')",
+                )
+                .bind(user_id)
+                .bind(mode == DeletionMode::Delete)
+                .fetch_all(&mut *conn)
+                .await?;
+                for post_id in removed {
+                    Self::remove_post(conn, post_id).await?;
+                }
+
+                match mode {
+                    DeletionMode::Delete => {
+                        let touched: Vec<i64> = sqlx::query_scalar(
+                            "SELECT DISTINCT post_id FROM comments WHERE user_id = $1",
+                        )
+                        .bind(user_id)
+                        .fetch_all(&mut *conn)
+                        .await?;
+                        sqlx::query(
+                            "DELETE FROM notifications WHERE comment_id IN (SELECT id FROM comments WHERE user_id = $1)",
+                        )
+                        .bind(user_id)
+                        .execute(&mut *conn)
+                        .await?;
+                        sqlx::query("DELETE FROM comments WHERE user_id = $1")
+                            .bind(user_id)
+                            .execute(&mut *conn)
+                            .await?;
+                        for post_id in touched {
+                            Self::refresh_post_counters(conn, post_id).await?;
+                        }
+                    }
+                    DeletionMode::Anonymize => {
+                        for statement in [
+                            "UPDATE posts SET is_anonymous = TRUE WHERE user_id = $1",
+                            "UPDATE comments SET is_anonymous = TRUE WHERE user_id = $1",
+                        ] {
+                            sqlx::query(statement)
+                                .bind(user_id)
+                                .execute(&mut *conn)
+                                .await?;
+                        }
+                    }
+                }
+
+                for statement in [
+                    "DELETE FROM follows WHERE follower_id = $1 OR followee_id = $1",
+                    "DELETE FROM bookmarks WHERE user_id = $1",
+                    "DELETE FROM post_subscriptions WHERE user_id = $1",
+                    "DELETE FROM notifications WHERE user_id = $1",
+                ] {
+                    sqlx::query(statement)
+                        .bind(user_id)
+                        .execute(&mut *conn)
+                        .await?;
+                }
+
+                match mode {
+                    DeletionMode::Delete => {
+                        for statement in [
+                            "DELETE FROM poll_vote_choices WHERE user_id = $1",
+                            "DELETE FROM poll_votes WHERE user_id = $1",
+                            "DELETE FROM users WHERE id = $1",
+                        ] {
+                            sqlx::query(statement)
+                                .bind(user_id)
+                                .execute(&mut *conn)
+                                .await?;
+                        }
+                    }
+                    DeletionMode::Anonymize => {
+                        sqlx::query(
+                            r#"UPDATE users
+                               SET username = $1, qq = '', password_hash = '', qq_visibility = 'nobody',
+                                   profile_hidden = TRUE, posts_hidden = TRUE, deleted_at = $2,
+                                   deletion_scheduled_at = NULL, deletion_mode = NULL
+                               WHERE id = $3"#,
+                        )
+                        .bind(format!("已注销用户-{}", Uuid::new_v4().simple()))
+                        .bind(now)
+                        .bind(user_id)
+                        .execute(&mut *conn)
+                        .await?;
+                    }
+                }
+                Ok(())
+            }
+
+            async fn refresh_post_counters(
+                conn: &mut $conn,
+                post_id: i64,
+            ) -> Result<(), sqlx::Error> {
+                let counters: Option<(String, i64)> = sqlx::query_as(
+                    r#"UPDATE posts SET
+                           comment_count = (SELECT COUNT(*) FROM comments c
+                                            WHERE c.post_id = posts.id AND c.hidden_at IS NULL),
+                           last_comment_at = (SELECT MAX(c.created_at) FROM comments c
+                                              WHERE c.post_id = posts.id AND c.hidden_at IS NULL),
+                           last_activity_at = COALESCE(
+                               (SELECT MAX(c.created_at) FROM comments c
+                                WHERE c.post_id = posts.id AND c.hidden_at IS NULL),
+                               created_at)
+                       WHERE id = $1
+                       RETURNING created_at, comment_count"#,
+                )
+                .bind(post_id)
+                .fetch_optional(&mut *conn)
+                .await?;
+                if let Some((created_at, comment_count)) = counters {
+                    sqlx::query("UPDATE posts SET hot_score = $1 WHERE id = $2")
+                        .bind(hot_score(&created_at, comment_count))
+                        .bind(post_id)
+                        .execute(&mut *conn)
+                        .await?;
+                }
+                Ok(())
+            }
+
+            async fn remove_comment(conn: &mut $conn, comment_id: i64) -> Result<bool, sqlx::Error> {
+                sqlx::query("DELETE FROM notifications WHERE comment_id = $1")
+                    .bind(comment_id)
+                    .execute(&mut *conn)
+                    .await?;
+                let post_id: Option<i64> =
+                    sqlx::query_scalar("DELETE FROM comments WHERE id = $1 RETURNING post_id")
+                        .bind(comment_id)
+                        .fetch_optional(&mut *conn)
+                        .await?;
+                let Some(post_id) = post_id else {
+                    return Ok(false);
+                };
+                Self::refresh_post_counters(conn, post_id).await?;
+                Ok(true)
+            }
+
+            async fn remove_post(conn: &mut $conn, post_id: i64) -> Result<u64, sqlx::Error> {
+                for statement in [
+                    "DELETE FROM bookmarks WHERE post_id = $1",
+                    "DELETE FROM post_subscriptions WHERE post_id = $1",
+                    "DELETE FROM notifications WHERE post_id = $1",
+                    "DELETE FROM poll_vote_choices WHERE poll_id IN (SELECT id FROM polls WHERE post_id = $1)",
+                    "DELETE FROM poll_votes WHERE poll_id IN (SELECT id FROM polls WHERE post_id = $1)",
+                    "DELETE FROM poll_options WHERE poll_id IN (SELECT id FROM polls WHERE post_id = $1)",
+                    "DELETE FROM polls WHERE post_id = $1",
+                    "DELETE FROM post_tags WHERE post_id = $1",
+                    "DELETE FROM comments WHERE post_id = $1",
+                ] {
+                    sqlx::query(statement)
+                        .bind(post_id)
+                        .execute(&mut *conn)
+                        .await?;
+                }
+                let result = sqlx::query("DELETE FROM posts WHERE id = $1")
+                    .bind(post_id)
+                    .execute(&mut *conn)
+                    .await?;
+                Ok(result.rows_affected())
+            }
+
+            async fn publish_post(
+                conn: &mut $conn,
+                post_id: i64,
+                now: &str,
+            ) -> Result<bool, sqlx::Error> {
+                let author: Option<i64> = sqlx::query_scalar(
+                    r#"UPDATE posts
+                       SET status = 'published', publish_at = NULL, created_at = $1, last_activity_at = $1,
+                           hot_score = $2
+                       WHERE id = $3 AND status = 'draft'
+                       RETURNING user_id"#,
+                )
+                .bind(now)
+                .bind(hot_score(now, 0))
+                .bind(post_id)
+                .fetch_optional(&mut *conn)
+                .await?;
+                let Some(user_id) = author else {
+                    return Ok(false);
+                };
+                Self::apply_default_expiry(conn, post_id, now).await?;
+                Self::auto_subscribe(conn, user_id, post_id, now).await?;
+                Ok(true)
+            }
+
+            async fn apply_default_expiry(
+                conn: &mut $conn,
+                post_id: i64,
+                now: &str,
+            ) -> Result<(), sqlx::Error> {
+                let lifetime: Option<Option<i64>> = sqlx::query_scalar(
+                    r#"SELECT c.post_lifetime_days
+                       FROM posts p JOIN categories c ON c.slug = p.category
+                       WHERE p.id = $1 AND p.expires_at IS NULL"#,
+                )
+                .bind(post_id)
+                .fetch_optional(&mut *conn)
+                .await?;
+                let Some(days) = lifetime.flatten() else {
+                    return Ok(());
+                };
+                let now = OffsetDateTime::parse(now, &Rfc3339)
+                    .map_err(|err| sqlx::Error::Decode(err.into()))?;
+
+                sqlx::query("UPDATE posts SET expires_at = $1 WHERE id = $2")
+                    .bind(format_time(now + Duration::days(days)))
+                    .bind(post_id)
+                    .execute(&mut *conn)
+                    .await?;
+                Ok(())
+            }
+
+            async fn auto_subscribe(
+                conn: &mut $conn,
+                user_id: i64,
+                post_id: i64,
+                now: &str,
+            ) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"INSERT INTO post_subscriptions (user_id, post_id, muted, created_at)
+                       VALUES ($1, $2, FALSE, $3)
+                       ON CONFLICT DO NOTHING"#,
+                )
+                .bind(user_id)
+                .bind(post_id)
+                .bind(now)
+                .execute(conn)
+                .await?;
+                Ok(())
+            }
+
+            async fn notify_subscribers(
+                conn: &mut $conn,
+                post_id: i64,
+                comment_id: i64,
+                actor_id: i64,
+                now: &str,
+            ) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"INSERT INTO notifications (user_id, kind, post_id, comment_id, created_at)
+                       SELECT user_id, $1, post_id, $2, $3
+                       FROM post_subscriptions
+                       WHERE post_id = $4 AND NOT muted AND user_id <> $5"#,
+                )
+                .bind(NOTIFICATION_NEW_COMMENT)
+                .bind(comment_id)
+                .bind(now)
+                .bind(post_id)
+                .bind(actor_id)
+                .execute(conn)
+                .await?;
+                Ok(())
+            }
+
+            async fn insert_poll(
+                conn: &mut $conn,
+                post_id: i64,
+                poll: &NewPoll,
+                now: &str,
+            ) -> Result<(), sqlx::Error> {
+                let poll_id: i64 = sqlx::query_scalar(
+                    r#"INSERT INTO polls (post_id, multiple, anonymous_votes, closes_at, created_at)
+                       VALUES ($1, $2, $3, $4, $5)
+                       RETURNING id"#,
+                )
+                .bind(post_id)
+                .bind(poll.multiple)
+                .bind(poll.anonymous_votes)
+                .bind(&poll.closes_at)
+                .bind(now)
+                .fetch_one(&mut *conn)
+                .await?;
+
+                for (position, label) in poll.options.iter().enumerate() {
+                    sqlx::query(
+                        "INSERT INTO poll_options (poll_id, position, label) VALUES ($1, $2, $3)",
+                    )
+                    .bind(poll_id)
+                    .bind(position as i64)
+                    .bind(label)
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                Ok(())
+            }
+
+            async fn attach_tags(
+                conn: &mut $conn,
+                post_id: i64,
+                names: &[String],
+                allow_new: bool,
+                now: &str,
+            ) -> ApiResult<()> {
+                for name in names {
+                    let tag_id = match Self::resolve_tag(conn, name).await? {
+                        Some(tag) if tag.banned => {
+                            return Err(ApiError::invalid_field(
+                                "tags",
+                                "tag.disabled",
+                                format!("标签 #{name} 已被禁用"),
+                                format!("Tag #{name} is disabled"),
+                            ));
+                        }
+                        Some(tag) => tag.id,
+                        None if allow_new => {
+                            sqlx::query_scalar(
+                                "INSERT INTO tags (name, created_at) VALUES ($1, $2) RETURNING id",
+                            )
+                            .bind(name)
+                            .bind(now)
+                            .fetch_one(&mut *conn)
+                            .await?
+                        }
+                        None => {
+                            return Err(ApiError::invalid_field(
+                                "tags",
+                                "tag.not_found",
+                                format!("标签 #{name} 不存在"),
+                                format!("Tag #{name} does not exist"),
+                            ));
+                        }
+                    };
+
+                    sqlx::query(
+                        "INSERT INTO post_tags (post_id, tag_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                    )
+                    .bind(post_id)
+                    .bind(tag_id)
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                Ok(())
+            }
+
+            async fn resolve_tag(conn: &mut $conn, name: &str) -> Result<Option<DbTag>, sqlx::Error> {
+                sqlx::query_as::<_, DbTag>(
+                    r#"SELECT t.id, t.name, t.banned, t.merged_into
+                       FROM tags source
+                       JOIN tags t ON t.id = COALESCE(source.merged_into, source.id)
+                       WHERE source.name = $1"#,
+                )
+                .bind(name)
+                .fetch_optional(conn)
+                .await
+            }
+        }
+
+        #[async_trait]
+        impl Repository for $name {
+            async fn create_user(
+                &self,
+                uid: &str,
+                username: &str,
+                qq: &str,
+                password_hash: &str,
+                created_at: &str,
+            ) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"INSERT INTO users (uid, username, qq, password_hash, created_at)
+                       VALUES ($1, $2, $3, $4, $5)"#,
+                )
+                .bind(uid)
+                .bind(username)
+                .bind(qq)
+                .bind(password_hash)
+                .bind(created_at)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn find_login_user(&self, username: &str) -> Result<Option<DbUser>, sqlx::Error> {
+                sqlx::query_as::<_, DbUser>(
+                    r#"SELECT id, uid, username, qq, password_hash, deletion_scheduled_at, banned_at
+                       FROM users WHERE username = $1 AND deleted_at IS NULL"#,
+                )
+                .bind(username)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn find_user_by_uid(&self, uid: &str) -> Result<Option<DbAdminUser>, sqlx::Error> {
+                let sql = format!("{ADMIN_USER_SELECT} WHERE u.uid = $1");
+                sqlx::query_as::<_, DbAdminUser>(&sql)
+                    .bind(uid)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+
+            async fn find_user_by_username(
+                &self,
+                username: &str,
+            ) -> Result<Option<DbAdminUser>, sqlx::Error> {
+                let sql = format!("{ADMIN_USER_SELECT} WHERE u.username = $1");
+                sqlx::query_as::<_, DbAdminUser>(&sql)
+                    .bind(username)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+
+            async fn find_public_user(&self, uid: &str) -> Result<Option<DbPublicUser>, sqlx::Error> {
+                sqlx::query_as::<_, DbPublicUser>(
+                    r#"SELECT id, uid, username, qq, created_at, qq_visibility, profile_hidden, posts_hidden
+                       FROM users WHERE uid = $1"#,
+                )
+                .bind(uid)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn search_users(
+                &self,
+                keyword: Option<&str>,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<DbAdminUser>, sqlx::Error> {
+                let pattern = keyword.map(like_pattern);
+                let sql = format!(
+                    r#"{ADMIN_USER_SELECT}
+                       WHERE ($1 IS NULL OR u.uid = $1
+                              OR u.username {like} $2 ESCAPE '\' OR u.qq {like} $2 ESCAPE '\')
+                       ORDER BY u.created_at DESC, u.id DESC
+                       LIMIT $3 OFFSET $4"#,
+                    like = Self::DIALECT.like
+                );
+                sqlx::query_as::<_, DbAdminUser>(&sql)
+                    .bind(keyword)
+                    .bind(pattern)
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn find_password_hash(&self, user_id: i64) -> Result<Option<String>, sqlx::Error> {
+                sqlx::query_scalar("SELECT password_hash FROM users WHERE id = $1")
+                    .bind(user_id)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+
+            async fn update_password(&self, user_id: i64, password_hash: &str) -> Result<(), sqlx::Error> {
+                sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+                    .bind(password_hash)
+                    .bind(user_id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn replace_password(
+                &self,
+                user_id: i64,
+                password_hash: &str,
+                audit: Option<&NewAuditEntry<'_>>,
+            ) -> Result<(), sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                Self::replace_password(&mut tx, user_id, password_hash).await?;
+                if let Some(audit) = audit {
+                    Self::record_audit(&mut tx, audit).await?;
+                }
+                tx.commit().await
+            }
+
+            async fn set_user_banned(
+                &self,
+                user_id: i64,
+                banned_at: Option<&str>,
+            ) -> Result<u64, sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query("UPDATE users SET banned_at = $1 WHERE id = $2")
+                    .bind(banned_at)
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await?;
+                let revoked = if banned_at.is_some() {
+                    sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+                        .bind(user_id)
+                        .execute(&mut *tx)
+                        .await?
+                        .rows_affected()
+                } else {
+                    0
+                };
+                tx.commit().await?;
+                Ok(revoked)
+            }
+
+            async fn update_profile(
+                &self,
+                user_id: i64,
+                username: &str,
+                qq: &str,
+            ) -> Result<(), sqlx::Error> {
+                sqlx::query("UPDATE users SET username = $1, qq = $2 WHERE id = $3")
+                    .bind(username)
+                    .bind(qq)
+                    .bind(user_id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn find_privacy(&self, user_id: i64) -> Result<Option<DbPrivacy>, sqlx::Error> {
+                sqlx::query_as::<_, DbPrivacy>(
+                    "SELECT qq_visibility, profile_hidden, posts_hidden FROM users WHERE id = $1",
+                )
+                .bind(user_id)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn update_privacy(&self, user_id: i64, privacy: &DbPrivacy) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"UPDATE users SET qq_visibility = $1, profile_hidden = $2, posts_hidden = $3
+                       WHERE id = $4"#,
+                )
+                .bind(privacy.qq_visibility)
+                .bind(privacy.profile_hidden)
+                .bind(privacy.posts_hidden)
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn follow_stats(
+                &self,
+                user_id: i64,
+                viewer_id: i64,
+            ) -> Result<DbFollowStats, sqlx::Error> {
+                sqlx::query_as::<_, DbFollowStats>(
+                    r#"SELECT
+                           (SELECT COUNT(*) FROM follows WHERE followee_id = $1) AS follower_count,
+                           (SELECT COUNT(*) FROM follows WHERE follower_id = $1) AS following_count,
+                           EXISTS(SELECT 1 FROM follows WHERE follower_id = $2 AND followee_id = $1)
+                               AS is_following,
+                           EXISTS(SELECT 1 FROM follows WHERE follower_id = $1 AND followee_id = $2)
+                               AS follows_viewer"#,
+                )
+                .bind(user_id)
+                .bind(viewer_id)
+                .fetch_one(&self.pool)
+                .await
+            }
+
+            async fn follow(
+                &self,
+                follower_id: i64,
+                followee_id: i64,
+                now: &str,
+            ) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"INSERT INTO follows (follower_id, followee_id, created_at)
+                       VALUES ($1, $2, $3)
+                       ON CONFLICT DO NOTHING"#,
+                )
+                .bind(follower_id)
+                .bind(followee_id)
+                .bind(now)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn unfollow(&self, follower_id: i64, followee_id: i64) -> Result<(), sqlx::Error> {
+                sqlx::query("DELETE FROM follows WHERE follower_id = $1 AND followee_id = $2")
+                    .bind(follower_id)
+                    .bind(followee_id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn admin_user_stats(&self, user_id: i64) -> Result<DbAdminUserStats, sqlx::Error> {
+                sqlx::query_as::<_, DbAdminUserStats>(
+                    r#"SELECT
+                           (SELECT COUNT(*) FROM posts WHERE user_id = $1 AND status = 'published') AS post_count,
+                           (SELECT COUNT(*) FROM posts
+                            WHERE user_id = $1 AND status = 'published' AND is_anonymous) AS anonymous_post_count,
+                           (SELECT COUNT(*) FROM comments WHERE user_id = $1) AS comment_count,
+                           (SELECT COUNT(*) FROM sessions WHERE user_id = $1) AS session_count,
+                           (SELECT MAX(created_at) FROM sessions WHERE user_id = $1) AS last_login_at"#,
+                )
+                .bind(user_id)
+                .fetch_one(&self.pool)
+                .await
+            }
+
+            async fn export_profile(&self, user_id: i64) -> Result<ExportProfile, sqlx::Error> {
+                sqlx::query_as::<_, ExportProfile>(
+                    r#"SELECT uid, username, qq, qq_visibility, profile_hidden, posts_hidden, created_at,
+                              deletion_scheduled_at
+                       FROM users WHERE id = $1"#,
+                )
+                .bind(user_id)
+                .fetch_one(&self.pool)
+                .await
+            }
+
+            async fn export_posts(&self, user_id: i64) -> Result<Vec<DbExportPost>, sqlx::Error> {
+                sqlx::query_as::<_, DbExportPost>(
+                    r#"SELECT p.id, p.title, p.content, p.category, p.is_anonymous, p.status, p.created_at,
+                              p.expires_at, p.archived_at,
+                              (SELECT string_agg(t.name, ',')
+                               FROM post_tags pt JOIN tags t ON t.id = pt.tag_id
+                               WHERE pt.post_id = p.id) AS tags
+                       FROM posts p
+                       WHERE p.user_id = $1
+                       ORDER BY p.created_at ASC"#,
+                )
+                .bind(user_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn export_comments(&self, user_id: i64) -> Result<Vec<ExportComment>, sqlx::Error> {
+                sqlx::query_as::<_, ExportComment>(
+                    r#"SELECT c.id, c.post_id, p.title AS post_title, c.content,
+                              c.is_anonymous AS anonymous, c.created_at
+                       FROM comments c
+                       LEFT JOIN posts p ON p.id = c.post_id
+                       WHERE c.user_id = $1
+                       ORDER BY c.created_at ASC"#,
+                )
+                .bind(user_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn export_sessions(
+                &self,
+                user_id: i64,
+                current: Option<&str>,
+            ) -> Result<Vec<ExportSession>, sqlx::Error> {
+                sqlx::query_as::<_, ExportSession>(
+                    r#"SELECT created_at, expires_at, COALESCE(id = $2, FALSE) AS current
+                       FROM sessions WHERE user_id = $1
+                       ORDER BY created_at ASC"#,
+                )
+                .bind(user_id)
+                .bind(current)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn request_account_deletion(
+                &self,
+                user_id: i64,
+                mode: DeletionMode,
+                scheduled_at: Option<&str>,
+                now: &str,
+            ) -> Result<(), sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await?;
+                if scheduled_at.is_some() {
+                    sqlx::query(
+                        "UPDATE users SET deletion_scheduled_at = $1, deletion_mode = $2 WHERE id = $3",
+                    )
+                    .bind(scheduled_at)
+                    .bind(mode)
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await?;
+                } else {
+                    Self::erase_account(&mut tx, user_id, mode, now).await?;
+                }
+                tx.commit().await
+            }
+
+            async fn cancel_account_deletion(&self, user_id: i64) -> Result<bool, sqlx::Error> {
+                let result = sqlx::query(
+                    r#"UPDATE users SET deletion_scheduled_at = NULL, deletion_mode = NULL
+                       WHERE id = $1 AND deletion_scheduled_at IS NOT NULL"#,
+                )
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+                Ok(result.rows_affected() > 0)
+            }
+
+            async fn due_account_deletions(
+                &self,
+                now: &str,
+            ) -> Result<Vec<(i64, DeletionMode)>, sqlx::Error> {
+                sqlx::query_as(
+                    r#"SELECT id, COALESCE(deletion_mode, 'delete')
+                       FROM users
+                       WHERE deletion_scheduled_at IS NOT NULL AND deletion_scheduled_at <= $1
+                         AND deleted_at IS NULL"#,
+                )
+                .bind(now)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn erase_account(
+                &self,
+                user_id: i64,
+                mode: DeletionMode,
+                now: &str,
+            ) -> Result<(), sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                Self::erase_account(&mut tx, user_id, mode, now).await?;
+                tx.commit().await
+            }
+
+            async fn create_session(
+                &self,
+                id: &str,
+                user_id: i64,
+                created_at: &str,
+                expires_at: &str,
+            ) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"INSERT INTO sessions (id, user_id, created_at, expires_at)
+                       VALUES ($1, $2, $3, $4)"#,
+                )
+                .bind(id)
+                .bind(user_id)
+                .bind(created_at)
+                .bind(expires_at)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn find_session(&self, id: &str) -> Result<Option<DbSession>, sqlx::Error> {
+                sqlx::query_as::<_, DbSession>(
+                    r#"SELECT s.user_id, s.expires_at, u.username, u.qq, u.uid, u.deletion_scheduled_at
+                       FROM sessions s
+                       JOIN users u ON u.id = s.user_id
+                       WHERE s.id = $1 AND u.banned_at IS NULL"#,
+                )
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn delete_session(&self, id: &str) -> Result<(), sqlx::Error> {
+                sqlx::query("DELETE FROM sessions WHERE id = $1")
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn revoke_sessions(
+                &self,
+                user_id: i64,
+                admin_id: i64,
+                uid: &str,
+            ) -> Result<u64, sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                let revoked = sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+                let detail = format!("{revoked} 个会话");
+                Self::record_audit(
+                    &mut tx,
+                    &NewAuditEntry {
+                        admin_id,
+                        action: AUDIT_REVOKE_SESSIONS,
+                        target_type: "user",
+                        target_id: uid,
+                        detail: Some(&detail),
+                    },
+                )
+                .await?;
+                tx.commit().await?;
+                Ok(revoked)
+            }
+
+            async fn purge_sessions(&self, expired_before: Option<&str>) -> Result<u64, sqlx::Error> {
+                let result = sqlx::query("DELETE FROM sessions WHERE $1 IS NULL OR expires_at <= $1")
+                    .bind(expired_before)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(result.rows_affected())
+            }
+
+            async fn count_active_sessions(&self, now: &str) -> Result<i64, sqlx::Error> {
+                sqlx::query_scalar("SELECT COUNT(*) FROM sessions WHERE expires_at > $1")
+                    .bind(now)
+                    .fetch_one(&self.pool)
+                    .await
+            }
+
+            async fn create_post(
+                &self,
+                post: &NewPost,
+                allow_new_tags: bool,
+                now: &str,
+            ) -> ApiResult<i64> {
+                let mut tx = self.pool.begin().await?;
+                let post_id: i64 = sqlx::query_scalar(
+                    r#"INSERT INTO posts (user_id, title, content, category, is_anonymous, created_at,
+                                          last_activity_at, hot_score, status, publish_at, expires_at)
+                       VALUES ($1, $2, $3, $4, $5, $6, $6, $7, $8, $9, $10)
+                       RETURNING id"#,
+                )
+                .bind(post.user_id)
+                .bind(&post.title)
+                .bind(&post.content)
+                .bind(&post.category)
+                .bind(post.anonymous)
+                .bind(now)
+                .bind(hot_score(now, 0))
+                .bind(post.status)
+                .bind(&post.publish_at)
+                .bind(&post.expires_at)
+                .fetch_one(&mut *tx)
+                .await?;
+                if let Some(poll) = &post.poll {
+                    Self::insert_poll(&mut tx, post_id, poll, now).await?;
+                }
+                Self::attach_tags(&mut tx, post_id, &post.tags, allow_new_tags, now).await?;
+                if post.status == PostStatus::Published {
+                    Self::apply_default_expiry(&mut tx, post_id, now).await?;
+                    Self::auto_subscribe(&mut tx, post.user_id, post_id, now).await?;
+                }
+                tx.commit().await?;
+                Ok(post_id)
+            }
+
+            async fn list_posts(&self, feed: &PostFeed) -> Result<Vec<DbPost>, sqlx::Error> {
+                let sql = format!(
+                    r#"{select}
+                       WHERE p.status = 'published' AND p.archived_at IS NULL AND p.hidden_at IS NULL
+                         AND ($2 IS NULL OR p.category = $2)
+                         AND (NOT $3 OR (NOT p.is_anonymous AND p.user_id IN (
+                              SELECT followee_id FROM follows WHERE follower_id = $1)))
+                         AND ($6 IS NULL OR EXISTS(
+                              SELECT 1 FROM post_tags pt JOIN tags t ON t.id = pt.tag_id
+                              WHERE pt.post_id = p.id AND NOT t.banned
+                                AND t.id = (SELECT COALESCE(merged_into, id) FROM tags WHERE name = $6)))
+                       ORDER BY CASE
+                                    WHEN NOT $3 AND {pinned} AND (p.pin_scope = 'global' OR $2 IS NOT NULL)
+                                    THEN p.pinned_at
+                                END DESC NULLS LAST,
+                                {order}
+                       LIMIT $4 OFFSET $5"#,
+                    select = Self::post_select(feed.full),
+                    pinned = pinned_expression(&Self::DIALECT),
+                    order = feed.sort.order_by()
+                );
+                sqlx::query_as::<_, DbPost>(&sql)
+                    .bind(feed.viewer_id)
+                    .bind(&feed.category)
+                    .bind(feed.following_only)
+                    .bind(feed.limit)
+                    .bind(feed.offset)
+                    .bind(&feed.tag)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn list_archived_posts(
+                &self,
+                viewer_id: i64,
+                category: Option<&str>,
+                full: bool,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<DbPost>, sqlx::Error> {
+                let sql = format!(
+                    r#"{select}
+                       WHERE p.status = 'published' AND p.archived_at IS NOT NULL AND p.hidden_at IS NULL
+                         AND ($2 IS NULL OR p.category = $2)
+                       ORDER BY p.archived_at DESC, p.id DESC
+                       LIMIT $3 OFFSET $4"#,
+                    select = Self::post_select(full)
+                );
+                sqlx::query_as::<_, DbPost>(&sql)
+                    .bind(viewer_id)
+                    .bind(category)
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn list_bookmarked_posts(
+                &self,
+                viewer_id: i64,
+                full: bool,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<DbPost>, sqlx::Error> {
+                let sql = format!(
+                    r#"{select}
+                       JOIN bookmarks saved ON saved.post_id = p.id AND saved.user_id = $1
+                       WHERE p.status = 'published' AND p.hidden_at IS NULL
+                       ORDER BY saved.created_at DESC, p.id DESC
+                       LIMIT $2 OFFSET $3"#,
+                    select = Self::post_select(full)
+                );
+                sqlx::query_as::<_, DbPost>(&sql)
+                    .bind(viewer_id)
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn list_user_posts(
+                &self,
+                user_id: i64,
+                include_anonymous: bool,
+                viewer_id: i64,
+                full: bool,
+            ) -> Result<Vec<DbPost>, sqlx::Error> {
+                let sql = format!(
+                    r#"{select}
+                       WHERE p.user_id = $2 AND p.status = 'published'
+                         AND ($3 OR (NOT p.is_anonymous AND p.archived_at IS NULL AND p.hidden_at IS NULL))
+                       ORDER BY p.created_at DESC"#,
+                    select = Self::post_select(full)
+                );
+                sqlx::query_as::<_, DbPost>(&sql)
+                    .bind(viewer_id)
+                    .bind(user_id)
+                    .bind(include_anonymous)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn find_post(
+                &self,
+                post_id: i64,
+                viewer_id: i64,
+            ) -> Result<Option<DbPost>, sqlx::Error> {
+                let sql = format!(
+                    r#"{select}
+                       WHERE p.id = $2 AND p.status = 'published' AND (p.hidden_at IS NULL OR p.user_id = $1)"#,
+                    select = Self::post_select(true)
+                );
+                sqlx::query_as::<_, DbPost>(&sql)
+                    .bind(viewer_id)
+                    .bind(post_id)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+
+            async fn find_post_archived(&self, post_id: i64) -> Result<Option<bool>, sqlx::Error> {
+                sqlx::query_scalar(
+                    r#"SELECT archived_at IS NOT NULL FROM posts
+                       WHERE id = $1 AND status = 'published' AND hidden_at IS NULL"#,
+                )
+                .bind(post_id)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn post_exists(&self, post_id: i64) -> Result<bool, sqlx::Error> {
+                sqlx::query_scalar(
+                    "SELECT EXISTS(SELECT 1 FROM posts WHERE id = $1 AND status = 'published' AND hidden_at IS NULL)",
+                )
+                .bind(post_id)
+                .fetch_one(&self.pool)
+                .await
+            }
+
+            async fn delete_post(&self, post_id: i64) -> Result<bool, sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                let removed = Self::remove_post(&mut tx, post_id).await?;
+                tx.commit().await?;
+                Ok(removed > 0)
+            }
+
+            async fn pin_post(
+                &self,
+                post_id: i64,
+                scope: PinScope,
+                pinned_at: &str,
+                expires_at: Option<&str>,
+            ) -> Result<bool, sqlx::Error> {
+                let result = sqlx::query(
+                    r#"UPDATE posts SET pin_scope = $1, pinned_at = $2, pin_expires_at = $3
+                       WHERE id = $4 AND status = 'published'"#,
+                )
+                .bind(scope)
+                .bind(pinned_at)
+                .bind(expires_at)
+                .bind(post_id)
+                .execute(&self.pool)
+                .await?;
+                Ok(result.rows_affected() > 0)
+            }
+
+            async fn unpin_post(&self, post_id: i64) -> Result<bool, sqlx::Error> {
+                let result = sqlx::query(
+                    "UPDATE posts SET pin_scope = NULL, pinned_at = NULL, pin_expires_at = NULL WHERE id = $1",
+                )
+                .bind(post_id)
+                .execute(&self.pool)
+                .await?;
+                Ok(result.rows_affected() > 0)
+            }
+
+            async fn move_post(&self, post_id: i64, category: &str) -> Result<bool, sqlx::Error> {
+                let result = sqlx::query(
+                    r#"UPDATE posts SET category = $1,
+                           pinned_at = CASE WHEN pin_scope = 'category' THEN NULL ELSE pinned_at END,
+                           pin_expires_at = CASE WHEN pin_scope = 'category' THEN NULL ELSE pin_expires_at END,
+                           pin_scope = CASE WHEN pin_scope = 'category' THEN NULL ELSE pin_scope END
+                       WHERE id = $2 AND status = 'published'"#,
+                )
+                .bind(category)
+                .bind(post_id)
+                .execute(&self.pool)
+                .await?;
+                Ok(result.rows_affected() > 0)
+            }
+
+            async fn archive_expired_posts(&self, now: &str) -> Result<u64, sqlx::Error> {
+                let result = sqlx::query(
+                    r#"UPDATE posts SET archived_at = $1
+                       WHERE status = 'published' AND archived_at IS NULL
+                         AND expires_at IS NOT NULL AND expires_at <= $1"#,
+                )
+                .bind(now)
+                .execute(&self.pool)
+                .await?;
+                Ok(result.rows_affected())
+            }
+
+            async fn due_scheduled_posts(&self, now: &str) -> Result<Vec<i64>, sqlx::Error> {
+                sqlx::query_scalar(
+                    r#"SELECT id FROM posts
+                       WHERE status = 'draft' AND publish_at IS NOT NULL AND publish_at <= $1
+                       ORDER BY publish_at ASC"#,
+                )
+                .bind(now)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn publish_post(&self, post_id: i64, now: &str) -> Result<bool, sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                let published = Self::publish_post(&mut tx, post_id, now).await?;
+                tx.commit().await?;
+                Ok(published)
+            }
+
+            async fn list_drafts(&self, user_id: i64) -> Result<Vec<DbDraft>, sqlx::Error> {
+                let sql = format!(
+                    r#"{DRAFT_SELECT}
+                       WHERE p.user_id = $1 AND p.status = 'draft'
+                       ORDER BY COALESCE(p.updated_at, p.created_at) DESC, p.id DESC"#
+                );
+                sqlx::query_as::<_, DbDraft>(&sql)
+                    .bind(user_id)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn find_draft(
+                &self,
+                user_id: i64,
+                draft_id: i64,
+            ) -> Result<Option<DbDraft>, sqlx::Error> {
+                let sql =
+                    format!("{DRAFT_SELECT} WHERE p.id = $1 AND p.user_id = $2 AND p.status = 'draft'");
+                sqlx::query_as::<_, DbDraft>(&sql)
+                    .bind(draft_id)
+                    .bind(user_id)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+
+            async fn create_draft(
+                &self,
+                user_id: i64,
+                draft: &NewDraft,
+                allow_new_tags: bool,
+                now: &str,
+            ) -> ApiResult<i64> {
+                let mut tx = self.pool.begin().await?;
+                let draft_id: i64 = sqlx::query_scalar(
+                    r#"INSERT INTO posts (user_id, title, content, category, is_anonymous, created_at,
+                                          last_activity_at, status, publish_at, updated_at)
+                       VALUES ($1, $2, $3, $4, $5, $6, $6, $7, $8, $6)
+                       RETURNING id"#,
+                )
+                .bind(user_id)
+                .bind(&draft.title)
+                .bind(&draft.content)
+                .bind(&draft.category)
+                .bind(draft.anonymous)
+                .bind(now)
+                .bind(PostStatus::Draft)
+                .bind(&draft.publish_at)
+                .fetch_one(&mut *tx)
+                .await?;
+                Self::attach_tags(&mut tx, draft_id, &draft.tags, allow_new_tags, now).await?;
+                tx.commit().await?;
+                Ok(draft_id)
+            }
+
+            async fn update_draft(
+                &self,
+                user_id: i64,
+                draft_id: i64,
+                draft: &NewDraft,
+                allow_new_tags: bool,
+                now: &str,
+            ) -> ApiResult<bool> {
+                let mut tx = self.pool.begin().await?;
+                let result = sqlx::query(
+                    r#"UPDATE posts
+                       SET title = $1, content = $2, category = $3, is_anonymous = $4, publish_at = $5,
+                           updated_at = $6
+                       WHERE id = $7 AND user_id = $8 AND status = 'draft'"#,
+                )
+                .bind(&draft.title)
+                .bind(&draft.content)
+                .bind(&draft.category)
+                .bind(draft.anonymous)
+                .bind(&draft.publish_at)
+                .bind(now)
+                .bind(draft_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+                if result.rows_affected() == 0 {
+                    return Ok(false);
+                }
+                sqlx::query("DELETE FROM post_tags WHERE post_id = $1")
+                    .bind(draft_id)
+                    .execute(&mut *tx)
+                    .await?;
+                Self::attach_tags(&mut tx, draft_id, &draft.tags, allow_new_tags, now).await?;
+                tx.commit().await?;
+                Ok(true)
+            }
+
+            async fn create_comment(
+                &self,
+                post_id: i64,
+                user_id: i64,
+                content: &str,
+                anonymous: bool,
+                now: &str,
+            ) -> Result<i64, sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                let comment_id: i64 = sqlx::query_scalar(
+                    r#"INSERT INTO comments (post_id, user_id, content, is_anonymous, created_at)
+                       VALUES ($1, $2, $3, $4, $5)
+                       RETURNING id"#,
+                )
+                .bind(post_id)
+                .bind(user_id)
+                .bind(content)
+                .bind(anonymous)
+                .bind(now)
+                .fetch_one(&mut *tx)
+                .await?;
+                let (created_at, comment_count): (String, i64) = sqlx::query_as(
+                    r#"UPDATE posts
+                       SET comment_count = comment_count + 1, last_comment_at = $1, last_activity_at = $1
+                       WHERE id = $2
+                       RETURNING created_at, comment_count"#,
+                )
+                .bind(now)
+                .bind(post_id)
+                .fetch_one(&mut *tx)
+                .await?;
+                sqlx::query("UPDATE posts SET hot_score = $1 WHERE id = $2")
+                    .bind(hot_score(&created_at, comment_count))
+                    .bind(post_id)
+                    .execute(&mut *tx)
+                    .await?;
+                Self::auto_subscribe(&mut tx, user_id, post_id, now).await?;
+                Self::notify_subscribers(&mut tx, post_id, comment_id, user_id, now).await?;
+                tx.commit().await?;
+                Ok(comment_id)
+            }
+
+            async fn list_comments(
+                &self,
+                post_id: i64,
+                viewer_id: i64,
+            ) -> Result<Vec<DbComment>, sqlx::Error> {
+                sqlx::query_as::<_, DbComment>(
+                    r#"SELECT c.id, c.user_id, c.content, c.is_anonymous, c.created_at,
+                              u.username, u.qq, u.uid, u.qq_visibility,
+                              EXISTS(SELECT 1 FROM follows f
+                                     WHERE f.follower_id = c.user_id AND f.followee_id = $2) AS follows_viewer
+                       FROM comments c
+                       LEFT JOIN users u ON c.user_id = u.id
+                       WHERE c.post_id = $1 AND c.hidden_at IS NULL
+                       ORDER BY c.created_at ASC"#,
+                )
+                .bind(post_id)
+                .bind(viewer_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn delete_comment(
+                &self,
+                comment_id: i64,
+                audit: Option<&NewAuditEntry<'_>>,
+            ) -> Result<bool, sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                if !Self::remove_comment(&mut tx, comment_id).await? {
+                    return Ok(false);
+                }
+                if let Some(audit) = audit {
+                    Self::record_audit(&mut tx, audit).await?;
+                }
+                tx.commit().await?;
+                Ok(true)
+            }
+
+            async fn bookmark(&self, user_id: i64, post_id: i64, now: &str) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"INSERT INTO bookmarks (user_id, post_id, created_at)
+                       VALUES ($1, $2, $3)
+                       ON CONFLICT DO NOTHING"#,
+                )
+                .bind(user_id)
+                .bind(post_id)
+                .bind(now)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn unbookmark(&self, user_id: i64, post_id: i64) -> Result<(), sqlx::Error> {
+                sqlx::query("DELETE FROM bookmarks WHERE user_id = $1 AND post_id = $2")
+                    .bind(user_id)
+                    .bind(post_id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn find_subscription(
+                &self,
+                user_id: i64,
+                post_id: i64,
+            ) -> Result<Option<bool>, sqlx::Error> {
+                sqlx::query_scalar(
+                    "SELECT muted FROM post_subscriptions WHERE user_id = $1 AND post_id = $2",
+                )
+                .bind(user_id)
+                .bind(post_id)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn subscribe(
+                &self,
+                user_id: i64,
+                post_id: i64,
+                muted: bool,
+                now: &str,
+            ) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"INSERT INTO post_subscriptions (user_id, post_id, muted, created_at)
+                       VALUES ($1, $2, $3, $4)
+                       ON CONFLICT(user_id, post_id) DO UPDATE SET muted = excluded.muted"#,
+                )
+                .bind(user_id)
+                .bind(post_id)
+                .bind(muted)
+                .bind(now)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn unsubscribe(&self, user_id: i64, post_id: i64) -> Result<(), sqlx::Error> {
+                sqlx::query("DELETE FROM post_subscriptions WHERE user_id = $1 AND post_id = $2")
+                    .bind(user_id)
+                    .bind(post_id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn list_notifications(
+                &self,
+                user_id: i64,
+                limit: i64,
+                offset: i64,
+            ) -> Result<Vec<DbNotification>, sqlx::Error> {
+                sqlx::query_as::<_, DbNotification>(
+                    r#"SELECT n.id, n.kind, n.post_id, n.comment_id, n.created_at, n.read_at,
+                              p.title AS post_title, c.content AS comment_content
+                       FROM notifications n
+                       JOIN posts p ON p.id = n.post_id
+                       LEFT JOIN comments c ON c.id = n.comment_id
+                       WHERE n.user_id = $1 AND p.hidden_at IS NULL AND c.hidden_at IS NULL
+                       ORDER BY n.created_at DESC, n.id DESC
+                       LIMIT $2 OFFSET $3"#,
+                )
+                .bind(user_id)
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn mark_notifications_read(&self, user_id: i64, now: &str) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    "UPDATE notifications SET read_at = $1 WHERE user_id = $2 AND read_at IS NULL",
+                )
+                .bind(now)
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn find_poll(&self, post_id: i64) -> Result<Option<DbPoll>, sqlx::Error> {
+                sqlx::query_as::<_, DbPoll>(
+                    "SELECT id, multiple, anonymous_votes, closes_at FROM polls WHERE post_id = $1",
+                )
+                .bind(post_id)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn list_poll_options(&self, poll_id: i64) -> Result<Vec<DbPollOption>, sqlx::Error> {
+                sqlx::query_as::<_, DbPollOption>(
+                    r#"SELECT o.id, o.label,
+                              (SELECT COUNT(*) FROM poll_vote_choices v WHERE v.option_id = o.id) AS votes
+                       FROM poll_options o
+                       WHERE o.poll_id = $1
+                       ORDER BY o.position ASC"#,
+                )
+                .bind(poll_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn list_poll_choices(
+                &self,
+                poll_id: i64,
+                user_id: i64,
+            ) -> Result<Vec<i64>, sqlx::Error> {
+                sqlx::query_scalar(
+                    "SELECT option_id FROM poll_vote_choices WHERE poll_id = $1 AND user_id = $2",
+                )
+                .bind(poll_id)
+                .bind(user_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn count_poll_voters(&self, poll_id: i64) -> Result<i64, sqlx::Error> {
+                sqlx::query_scalar("SELECT COUNT(*) FROM poll_votes WHERE poll_id = $1")
+                    .bind(poll_id)
+                    .fetch_one(&self.pool)
+                    .await
+            }
+
+            async fn list_poll_voters(&self, poll_id: i64) -> Result<Vec<DbPollVoter>, sqlx::Error> {
+                sqlx::query_as::<_, DbPollVoter>(
+                    r#"SELECT v.option_id, u.username, u.uid
+                       FROM poll_vote_choices v
+                       JOIN users u ON u.id = v.user_id
+                       WHERE v.poll_id = $1
+                       ORDER BY u.username ASC"#,
+                )
+                .bind(poll_id)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn cast_vote(
+                &self,
+                poll_id: i64,
+                user_id: i64,
+                option_ids: &[i64],
+                now: &str,
+            ) -> Result<(), sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query("INSERT INTO poll_votes (poll_id, user_id, created_at) VALUES ($1, $2, $3)")
+                    .bind(poll_id)
+                    .bind(user_id)
+                    .bind(now)
+                    .execute(&mut *tx)
+                    .await?;
+                for option_id in option_ids {
+                    sqlx::query(
+                        r#"INSERT INTO poll_vote_choices (poll_id, user_id, option_id)
+                           VALUES ($1, $2, $3)"#,
+                    )
+                    .bind(poll_id)
+                    .bind(user_id)
+                    .bind(option_id)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                tx.commit().await
+            }
+
+            async fn trending_tags(&self, since: &str, limit: i64) -> Result<Vec<DbTagCount>, sqlx::Error> {
+                sqlx::query_as::<_, DbTagCount>(
+                    r#"SELECT t.name, COUNT(*) AS post_count
+                       FROM post_tags pt
+                       JOIN tags t ON t.id = pt.tag_id
+                       JOIN posts p ON p.id = pt.post_id
+                       WHERE NOT t.banned AND p.status = 'published' AND p.hidden_at IS NULL
+                         AND p.created_at >= $1
+                       GROUP BY t.id, t.name
+                       ORDER BY post_count DESC, t.name ASC
+                       LIMIT $2"#,
+                )
+                .bind(since)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn create_tag(&self, name: &str, now: &str) -> Result<(), sqlx::Error> {
+                sqlx::query("INSERT INTO tags (name, created_at) VALUES ($1, $2)")
+                    .bind(name)
+                    .bind(now)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+
+            async fn set_tag_banned(&self, name: &str, banned: bool) -> Result<bool, sqlx::Error> {
+                let result = sqlx::query("UPDATE tags SET banned = $1 WHERE name = $2")
+                    .bind(banned)
+                    .bind(name)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(result.rows_affected() > 0)
+            }
+
+            async fn find_tag(&self, name: &str) -> Result<Option<DbTag>, sqlx::Error> {
+                sqlx::query_as::<_, DbTag>(
+                    "SELECT id, name, banned, merged_into FROM tags WHERE name = $1",
+                )
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn resolve_tag(&self, name: &str) -> Result<Option<DbTag>, sqlx::Error> {
+                let mut conn = self.pool.acquire().await?;
+                Self::resolve_tag(&mut conn, name).await
+            }
+
+            async fn merge_tag(&self, source_id: i64, target_id: i64) -> Result<(), sqlx::Error> {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query(
+                    r#"INSERT INTO post_tags (post_id, tag_id)
+                       SELECT post_id, $1 FROM post_tags WHERE tag_id = $2
+                       ON CONFLICT DO NOTHING"#,
+                )
+                .bind(target_id)
+                .bind(source_id)
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query("DELETE FROM post_tags WHERE tag_id = $1")
+                    .bind(source_id)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("UPDATE tags SET merged_into = $1 WHERE id = $2 OR merged_into = $2")
+                    .bind(target_id)
+                    .bind(source_id)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await
+            }
+
+            async fn list_categories(
+                &self,
+                include_archived: bool,
+            ) -> Result<Vec<DbCategory>, sqlx::Error> {
+                sqlx::query_as::<_, DbCategory>(
+                    r#"SELECT slug, name, description, sort_order, archived, post_permission, post_lifetime_days
+                       FROM categories
+                       WHERE ($1 OR NOT archived)
+                       ORDER BY sort_order ASC, created_at ASC"#,
+                )
+                .bind(include_archived)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn find_category(&self, slug: &str) -> Result<Option<DbCategory>, sqlx::Error> {
+                sqlx::query_as::<_, DbCategory>(
+                    r#"SELECT slug, name, description, sort_order, archived, post_permission, post_lifetime_days
+                       FROM categories WHERE slug = $1"#,
+                )
+                .bind(slug)
+                .fetch_optional(&self.pool)
+                .await
+            }
+
+            async fn create_category(&self, category: &DbCategory, now: &str) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"INSERT INTO categories (slug, name, description, sort_order, archived, post_permission,
+                                               post_lifetime_days, created_at)
+                       VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                )
+                .bind(&category.slug)
+                .bind(&category.name)
+                .bind(&category.description)
+                .bind(category.sort_order)
+                .bind(category.archived)
+                .bind(category.post_permission)
+                .bind(category.post_lifetime_days)
+                .bind(now)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn update_category(&self, category: &DbCategory) -> Result<(), sqlx::Error> {
+                sqlx::query(
+                    r#"UPDATE categories
+                       SET name = $1, description = $2, sort_order = $3, archived = $4, post_permission = $5,
+                           post_lifetime_days = $6
+                       WHERE slug = $7"#,
+                )
+                .bind(&category.name)
+                .bind(&category.description)
+                .bind(category.sort_order)
+                .bind(category.archived)
+                .bind(category.post_permission)
+                .bind(category.post_lifetime_days)
+                .bind(&category.slug)
+                .execute(&self.pool)
+                .await?;
+                Ok(())
+            }
+
+            async fn category_in_use(&self, slug: &str) -> Result<bool, sqlx::Error> {
+                sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM posts WHERE category = $1)")
+                    .bind(slug)
+                    .fetch_one(&self.pool)
+                    .await
+            }
+
+            async fn delete_category(&self, slug: &str) -> Result<bool, sqlx::Error> {
+                let result = sqlx::query("DELETE FROM categories WHERE slug = $1")
+                    .bind(slug)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(result.rows_affected() > 0)
+            }
+
+            async fn reveal_author(
+                &self,
+                target: ModerationTarget,
+                target_id: i64,
+                audit: &NewAuditEntry<'_>,
+            ) -> Result<Option<DbAdminUser>, sqlx::Error> {
+                let sql = match target {
+                    ModerationTarget::Post => {
+                        format!("{ADMIN_USER_SELECT} JOIN posts p ON p.user_id = u.id WHERE p.id = $1")
+                    }
+                    ModerationTarget::Comment => format!(
+                        "{ADMIN_USER_SELECT} JOIN comments c ON c.user_id = u.id WHERE c.id = $1"
+                    ),
+                };
+                let mut tx = self.pool.begin().await?;
+                Self::record_audit(&mut tx, audit).await?;
+                let author = sqlx::query_as::<_, DbAdminUser>(&sql)
+                    .bind(target_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                if author.is_some() {
+                    tx.commit().await?;
+                }
+                Ok(author)
+            }
+
+            async fn record_audit(&self, audit: &NewAuditEntry<'_>) -> Result<(), sqlx::Error> {
+                let mut conn = self.pool.acquire().await?;
+                Self::record_audit(&mut conn, audit).await
+            }
+
+            async fn list_audit(&self, limit: i64, offset: i64) -> Result<Vec<DbAuditEntry>, sqlx::Error> {
+                sqlx::query_as::<_, DbAuditEntry>(
+                    r#"SELECT a.id, u.uid AS admin_uid, u.username AS admin_username, a.action,
+                              a.target_type, a.target_id, a.detail, a.created_at
+                       FROM admin_audit_log a
+                       LEFT JOIN users u ON u.id = a.admin_id
+                       ORDER BY a.created_at DESC, a.id DESC
+                       LIMIT $1 OFFSET $2"#,
+                )
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(&self.pool)
+                .await
+            }
+
+            async fn find_moderation_targets(
+                &self,
+                target: ModerationTarget,
+                filter: &ModerationFilter,
+            ) -> Result<Vec<i64>, sqlx::Error> {
+                let like = Self::DIALECT.like;
+                let sql = match target {
+                    ModerationTarget::Comment => format!(
+                        r#"SELECT id FROM comments
+                           WHERE ($1 IS NULL OR user_id = $1)
+                             AND ($2 IS NULL OR content {like} $2 ESCAPE '\')
+                             AND ($3 IS NULL OR created_at >= $3)
+                             AND ($4 IS NULL OR created_at <= $4)
+                           ORDER BY id ASC
+                           LIMIT $5"#
+                    ),
+                    ModerationTarget::Post => format!(
+                        r#"SELECT id FROM posts
+                           WHERE status = 'published'
+                             AND ($1 IS NULL OR user_id = $1)
+                             AND ($2 IS NULL OR title {like} $2 ESCAPE '\' OR content {like} $2 ESCAPE '\')
+                             AND ($3 IS NULL OR created_at >= $3)
+                             AND ($4 IS NULL OR created_at <= $4)
+                           ORDER BY id ASC
+                           LIMIT $5"#
+                    ),
+                };
+                sqlx::query_scalar(&sql)
+                    .bind(filter.user_id)
+                    .bind(filter.keyword.as_deref().map(like_pattern))
+                    .bind(&filter.since)
+                    .bind(&filter.until)
+                    .bind(MAX_BULK_ITEMS)
+                    .fetch_all(&self.pool)
+                    .await
+            }
+
+            async fn moderate(
+                &self,
+                target: ModerationTarget,
+                action: ModerationAction,
+                id: i64,
+                now: &str,
+            ) -> Result<bool, sqlx::Error> {
+                let hidden_at = (action == ModerationAction::Hide).then_some(now);
+                let mut tx = self.pool.begin().await?;
+                let changed = match (target, action) {
+                    (ModerationTarget::Post, ModerationAction::Delete) => {
+                        Self::remove_post(&mut tx, id).await? > 0
+                    }
+                    (ModerationTarget::Comment, ModerationAction::Delete) => {
+                        Self::remove_comment(&mut tx, id).await?
+                    }
+                    (ModerationTarget::Post, _) => {
+                        sqlx::query("UPDATE posts SET hidden_at = $1 WHERE id = $2")
+                            .bind(hidden_at)
+                            .bind(id)
+                            .execute(&mut *tx)
+                            .await?
+                            .rows_affected()
+                            > 0
+                    }
+                    (ModerationTarget::Comment, _) => {
+                        let post_id: Option<i64> = sqlx::query_scalar(
+                            "UPDATE comments SET hidden_at = $1 WHERE id = $2 RETURNING post_id",
+                        )
+                        .bind(hidden_at)
+                        .bind(id)
+                        .fetch_optional(&mut *tx)
+                        .await?;
+                        if let Some(post_id) = post_id {
+                            Self::refresh_post_counters(&mut tx, post_id).await?;
+                        }
+                        post_id.is_some()
+                    }
+                };
+                if changed {
+                    tx.commit().await?;
+                }
+                Ok(changed)
+            }
+
+            async fn close(&self) {
+                self.pool.close().await;
+            }
+
+            $($backend)*
+        }
+    };
+}
+
+sql_repository!(
+    SqliteRepository,
+    SqlitePool,
+    SqliteConnection,
+    SQLITE_DIALECT,
+    {
+        async fn schema_columns(&self) -> Result<Vec<(String, String)>, sqlx::Error> {
+            sqlx::query_as(
+                r#"SELECT m.name, c.name
+               FROM sqlite_master m
+               JOIN pragma_table_info(m.name) c
+               WHERE m.type = 'table'"#,
+            )
+            .fetch_all(&self.pool)
+            .await
+        }
+
+        async fn backup(&self, path: &FsPath) -> ApiResult<()> {
+            sqlx::query("VACUUM INTO ?1")
+                .bind(path.to_string_lossy().into_owned())
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        }
+    }
+);
+
+sql_repository!(
+    PostgresRepository,
+    PgPool,
+    PgConnection,
+    POSTGRES_DIALECT,
+    {
+        async fn schema_columns(&self) -> Result<Vec<(String, String)>, sqlx::Error> {
+            sqlx::query_as(
+                r#"SELECT table_name::TEXT, column_name::TEXT
+               FROM information_schema.columns
+               WHERE table_schema = current_schema()"#,
+            )
+            .fetch_all(&self.pool)
+            .await
+        }
+
+        async fn backup(&self, _path: &FsPath) -> ApiResult<()> {
+            Err(ApiError::invalid(
+                "backup.unsupported",
+                "postgres 后端不支持在线备份，请使用 pg_dump",
+                "Online backups are not available on the postgres backend; use pg_dump instead",
+            ))
+        }
+    }
+);
+
+impl SqliteRepository {
+    async fn connect(database: &DatabaseSection) -> Result<Self, sqlx::Error> {
+        let pool = connect_database(database).await?;
+        init_db(&pool).await?;
+        Ok(Self { pool })
+    }
+}
+
+impl PostgresRepository {
+    async fn connect(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+        let repo = Self::open(PgConnectOptions::from_str(url)?, max_connections).await?;
+        repo.init_schema().await?;
+        Ok(repo)
+    }
+
+    async fn open(options: PgConnectOptions, max_connections: u32) -> Result<Self, sqlx::Error> {
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect_with(options.options([("client_min_messages", "warning")]))
+            .await?;
+        Ok(Self { pool })
+    }
+
+    async fn init_schema(&self) -> Result<(), sqlx::Error> {
+        let has_categories: bool =
+            sqlx::query_scalar("SELECT to_regclass('categories') IS NOT NULL")
+                .fetch_one(&self.pool)
+                .await?;
+        for statement in POSTGRES_SCHEMA {
+            sqlx::query(statement).execute(&self.pool).await?;
+        }
+        if !has_categories {
+            let now = now_iso();
+            for (index, name) in SEED_CATEGORIES.iter().enumerate() {
+                sqlx::query(
+                    r#"INSERT INTO categories (slug, name, sort_order, created_at)
+                       VALUES ($1, $1, $2, $3)
+                       ON CONFLICT DO NOTHING"#,
+                )
+                .bind(name)
+                .bind(index as i64)
+                .bind(&now)
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn lock_database(database: &DatabaseSection) -> Result<fs::File, Box<dyn std::error::Error>> {
+    let mut path = database.path.clone().into_os_string();
+    path.push(".lock");
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(fs::TryLockError::WouldBlock) => Err(format!(
+            "数据库 {} 正被运行中的 talk-wall 服务占用",
+            database.path.display()
+        )
+        .into()),
+        Err(fs::TryLockError::Error(err)) => Err(err.into()),
+    }
+}
+
+async fn serve(
+    state: SharedState,
+    config_path: PathBuf,
+    required: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    tokio::spawn(wait_for_shutdown_signal(state.clone()));
+
+    let mut tasks = JoinSet::new();
+    tasks.spawn(watch_config_file(
+        state.clone(),
+        config_path.clone(),
+        required,
+    ));
+    #[cfg(unix)]
+    tasks.spawn(reload_config_on_hangup(
+        state.clone(),
+        config_path,
+        required,
+    ));
+    tasks.spawn(publish_scheduled_posts(
+        state.repo.clone(),
+        state.shutdown.subscribe(),
+    ));
+    tasks.spawn(archive_expired_posts(
+        state.repo.clone(),
+        state.shutdown.subscribe(),
+    ));
+    tasks.spawn(purge_deleted_accounts(
+        state.repo.clone(),
+        state.shutdown.subscribe(),
+    ));
+    match state.config().database.backend {
+        DatabaseBackend::Sqlite => {
+            tasks.spawn(run_scheduled_backups(state.clone()));
+        }
+        DatabaseBackend::Postgres => {
+            if state.config().backup.interval_hours > 0 {
+                warn!(
+                    "postgres 后端不支持定时备份，backup.interval_hours 将被忽略，请使用 pg_dump"
+                );
+            }
+        }
+    }
+
+    let metrics = state.config().metrics.clone();
+    let metrics_routes = if metrics.enabled {
+        let handle = PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Suffix("duration_seconds".into()),
+                &METRICS_DURATION_BUCKETS,
+            )?
+            .install_recorder()?;
+        tasks.spawn(run_metrics_upkeep(
+            handle.clone(),
+            state.shutdown.subscribe(),
+        ));
+        Some(
+            Router::new()
+                .route("/metrics", get(render_metrics))
+                .with_state(MetricsState {
+                    app: state.clone(),
+                    handle,
+                }),
+        )
+    } else {
+        None
+    };
+
+    if fs::metadata(STATIC_DIR).is_err() {
+        warn!("{STATIC_DIR} 不存在，运行 `npm install && npm run build` 以构建 Svelte 前端");
+    } else {
+        let entry = PathBuf::from(STATIC_DIR).join(FRONTEND_ENTRY);
+        if fs::metadata(&entry).is_err() {
+            warn!(
+                "未找到 {}，请确认 `npm run build` 是否成功并输出入口文件",
+                entry.display()
+            );
+        }
+    }
+
+    let static_service = ServeDir::new(STATIC_DIR).not_found_service(ServeFile::new(
+        PathBuf::from(STATIC_DIR).join(FRONTEND_ENTRY),
+    ));
+
+    let api_routes = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/api/register", post(register))
+        .route("/api/login", post(login))
+        .route("/api/logout", post(logout))
+        .route(
+            "/api/me",
+            get(me).patch(update_profile).delete(delete_account),
+        )
+        .route("/api/me/deletion", delete(cancel_account_deletion))
+        .route("/api/me/export", get(export_account))
+        .route("/api/me/posts", get(list_my_posts))
+        .route("/api/me/password", post(change_password))
+        .route("/api/me/privacy", get(get_privacy).patch(update_privacy))
+        .route("/api/me/bookmarks", get(list_bookmarks))
+        .route("/api/me/notifications", get(list_notifications))
+        .route("/api/me/notifications/read", post(mark_notifications_read))
+        .route("/api/me/drafts", get(list_drafts).post(create_draft))
+        .route(
+            "/api/me/drafts/:draft_id",
+            get(get_draft).put(save_draft).delete(delete_draft),
+        )
+        .route("/api/me/drafts/:draft_id/publish", post(publish_draft))
+        .route("/api/users/:uid", get(get_user_profile))
+        .route(
+            "/api/users/:uid/follow",
+            post(follow_user).delete(unfollow_user),
+        )
+        .route("/api/posts", post(create_post).get(list_posts))
+        .route("/api/posts/:post_id", get(get_post))
+        .route("/api/posts/:post_id/comments", post(create_comment))
+        .route("/api/posts/:post_id/poll/vote", post(vote_poll))
+        .route(
+            "/api/posts/:post_id/subscription",
+            post(subscribe_post)
+                .patch(update_subscription)
+                .delete(unsubscribe_post),
+        )
+        .route(
+            "/api/posts/:post_id/bookmark",
+            post(bookmark_post).delete(unbookmark_post),
+        )
+        .route("/api/archive", get(list_archived_posts))
+        .route("/api/categories", get(list_categories))
+        .route("/api/tags/trending", get(trending_tags))
+        .route("/api/admin/tags", post(create_tag))
+        .route("/api/admin/tags/:name", patch(update_tag))
+        .route("/api/admin/tags/:name/merge", post(merge_tag))
+        .route(
+            "/api/admin/categories",
+            get(admin_list_categories).post(create_category),
+        )
+        .route(
+            "/api/admin/categories/:slug",
+            patch(update_category).delete(delete_category),
+        )
+        .route("/api/admin/posts/:post_id", delete(delete_post))
+        .route("/api/admin/posts/:post_id/author", get(reveal_post_author))
+        .route(
+            "/api/admin/comments/:comment_id/author",
+            get(reveal_comment_author),
+        )
+        .route("/api/admin/users", get(admin_list_users))
+        .route("/api/admin/users/:uid", get(admin_get_user))
+        .route(
+            "/api/admin/users/:uid/reset-password",
+            post(admin_reset_password),
+        )
+        .route(
+            "/api/admin/users/:uid/sessions",
+            delete(admin_revoke_sessions),
+        )
+        .route("/api/admin/audit", get(admin_list_audit))
+        .route(
+            "/api/admin/comments/:comment_id",
+            delete(admin_delete_comment),
+        )
+        .route("/api/admin/moderation/bulk", post(bulk_moderate))
+        .route("/api/admin/moderation/move", post(move_posts))
+        .route(
+            "/api/admin/backups",
+            get(admin_list_backups).post(admin_create_backup),
+        )
+        .route(
+            "/api/admin/posts/:post_id/pin",
+            post(pin_post).delete(unpin_post),
+        )
+        .with_state(state.clone());
+
+    let mut app = Router::new()
+        .merge(api_routes)
+        .layer(CookieManagerLayer::new())
+        .fallback_service(static_service);
+    if let Some(metrics_routes) = metrics_routes {
+        match &metrics.addr {
+            Some(addr) => {
+                let listener = TcpListener::bind(addr.parse::<SocketAddr>()?).await?;
+                info!("metrics listening on {}", listener.local_addr()?);
+                let mut shutdown = state.shutdown.subscribe();
+                tasks.spawn(async move {
+                    let served = axum::serve(listener, metrics_routes)
+                        .with_graceful_shutdown(
+                            async move { shutdown_requested(&mut shutdown).await },
+                        )
+                        .await;
+                    if let Err(err) = served {
+                        warn!("指标服务异常退出: {err}");
+                    }
+                });
+            }
+            None => app = app.merge(metrics_routes),
+        }
+        app = app.layer(middleware::from_fn(track_requests));
+    }
+    let app = app
+        .layer(middleware::from_fn(scope_request_context))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
+
+    let addr: SocketAddr = state.config().server.addr.parse()?;
+    let drain_timeout = StdDuration::from_secs(state.config().server.shutdown_timeout_secs);
+    let listener = TcpListener::bind(addr).await?;
+    info!("listening on {}", listener.local_addr()?);
+
+    let mut shutdown = state.shutdown.subscribe();
+    let server = async {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(async move { shutdown_requested(&mut shutdown).await })
+        .await
+    };
+    let mut shutdown = state.shutdown.subscribe();
+    let drain_deadline = async {
+        shutdown_requested(&mut shutdown).await;
+        tokio::time::sleep(drain_timeout).await;
+    };
+    tokio::select! {
+        result = server => result?,
+        _ = drain_deadline => warn!(
+            "{} 秒内仍有请求未完成，强制关闭连接",
+            drain_timeout.as_secs()
+        ),
+    }
+
+    state.shutdown.send_replace(true);
+    let stopped = tokio::time::timeout(drain_timeout, async {
+        while tasks.join_next().await.is_some() {}
+    })
+    .await;
+    if stopped.is_err() {
+        warn!("后台任务未能按时结束，已强制取消");
+        tasks.shutdown().await;
+    }
+    state.repo.close().await;
+    info!("服务已停止");
+    Ok(())
+}
+
+async fn wait_for_shutdown_signal(state: SharedState) {
+    let interrupt = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            warn!("无法监听 SIGINT: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                warn!("无法监听 SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => info!("收到 SIGINT，开始优雅关闭"),
+        _ = terminate => info!("收到 SIGTERM，开始优雅关闭"),
+    }
+    state.shutdown.send_replace(true);
+}
+
+async fn shutdown_requested(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|stopping| *stopping).await;
+}
+
+fn request_span(request: &Request) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("static", MatchedPath::as_str);
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        request_id,
+        uid = field::Empty,
+    )
+}
+
+async fn scope_request_context(request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let request_id = headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let locale = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(Locale::from_accept_language)
+        .unwrap_or_default();
+    let context = RequestContext { request_id, locale };
+    REQUEST_CONTEXT.scope(context, next.run(request)).await
+}
+
+#[derive(Clone)]
+struct MetricsState {
+    app: SharedState,
+    handle: PrometheusHandle,
+}
+
+async fn track_requests(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "static".into());
+    let method = request.method().to_string();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    counter!(METRIC_HTTP_REQUESTS, &labels).increment(1);
+    histogram!(METRIC_HTTP_DURATION, &labels).record(started.elapsed().as_secs_f64());
+    response
+}
+
+async fn render_metrics(
+    State(metrics): State<MetricsState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let config = metrics.app.config();
+    if config.metrics.addr.is_none() {
+        let expected = config.metrics.token.as_deref().unwrap_or_default();
+        let provided = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !provided.is_some_and(|provided| constant_time_eq(provided, expected)) {
+            return Err(ApiError::Unauthorized);
+        }
+    }
+
+    let active = metrics.app.repo.count_active_sessions(&now_iso()).await?;
+    gauge!(METRIC_ACTIVE_SESSIONS).set(active as f64);
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.handle.render(),
+    )
+        .into_response())
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+async fn run_metrics_upkeep(handle: PrometheusHandle, mut shutdown: watch::Receiver<bool>) {
+    let mut ticker = tokio::time::interval(StdDuration::from_secs(METRICS_UPKEEP_INTERVAL_SECS));
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => handle.run_upkeep(),
+            _ = shutdown_requested(&mut shutdown) => break,
+        }
+    }
+}
+
+async fn healthz() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok" })
+}
+
+async fn readyz(State(state): State<SharedState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let mut checks = Vec::new();
+    let stopping = *state.shutdown.borrow();
+    checks.push(ReadinessCheck::new(
+        "shutdown",
+        if stopping {
+            Err("服务正在关闭".into())
+        } else {
+            Ok(())
+        },
+    ));
+
+    let columns = state.repo.schema_columns().await;
+    if let Err(err) = &columns {
+        warn!("就绪检查无法访问数据库: {err}");
+    }
+    checks.push(ReadinessCheck::new(
+        "database",
+        match &columns {
+            Ok(_) => Ok(()),
+            Err(_) => Err("数据库不可用".into()),
+        },
+    ));
+    checks.push(ReadinessCheck::new(
+        "migrations",
+        match columns.map(|columns| missing_schema(&columns)) {
+            Ok(missing) if missing.is_empty() => Ok(()),
+            Ok(missing) => Err(format!("缺少表或字段: {}", missing.join(", "))),
+            Err(_) => Err("无法读取数据库表结构".into()),
+        },
+    ));
+
+    let entry = PathBuf::from(STATIC_DIR).join(FRONTEND_ENTRY);
+    checks.push(ReadinessCheck::new(
+        "frontend",
+        if entry.is_file() {
+            Ok(())
+        } else {
+            Err(format!("未找到 {}", entry.display()))
+        },
+    ));
+
+    let ready = checks.iter().all(|check| check.ok);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            status: if ready { "ready" } else { "unavailable" },
+            checks,
+        }),
+    )
+}
+
+fn missing_schema(columns: &[(String, String)]) -> Vec<String> {
+    REQUIRED_SCHEMA
+        .iter()
+        .flat_map(|(table, required)| required.iter().map(move |column| (*table, *column)))
+        .filter(|(table, column)| {
+            !columns
+                .iter()
+                .any(|(found_table, found_column)| found_table == table && found_column == column)
+        })
+        .map(|(table, column)| format!("{table}.{column}"))
+        .collect()
+}
+
+async fn watch_config_file(state: SharedState, path: PathBuf, required: bool) {
+    let mut ticker = tokio::time::interval(StdDuration::from_secs(CONFIG_POLL_INTERVAL_SECS));
+    let mut last_modified = config_modified_at(&path);
+    let mut shutdown = state.shutdown.subscribe();
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown_requested(&mut shutdown) => break,
+        }
+        let modified = config_modified_at(&path);
+        if modified != last_modified {
+            last_modified = modified;
+            info!("检测到 {} 变化，重新加载配置", path.display());
+            reload_config(&state, &path, required);
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn reload_config_on_hangup(state: SharedState, path: PathBuf, required: bool) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            warn!("无法监听 SIGHUP，仅通过文件变化重新加载配置: {err}");
+            return;
+        }
+    };
+    let mut shutdown = state.shutdown.subscribe();
+    loop {
+        tokio::select! {
+            received = hangup.recv() => {
+                if received.is_none() {
+                    break;
+                }
+            }
+            _ = shutdown_requested(&mut shutdown) => break,
+        }
+        info!("收到 SIGHUP，重新加载配置");
+        reload_config(&state, &path, required);
+    }
+}
+
+fn config_modified_at(path: &FsPath) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn reload_config(state: &AppState, path: &FsPath, required: bool) {
+    let mut next = match load_config(path, required) {
+        Ok(config) => config,
+        Err(err) => {
+            warn!("配置重新加载失败，继续使用当前配置: {err}");
+            return;
+        }
+    };
+    let current = state.config();
+
+    let mut applied = 0;
+    for (key, previous, value) in config_changes(&current, &next) {
+        let section = key.split('.').next().unwrap_or(&key);
+        if RESTART_REQUIRED_SECTIONS.contains(&section) {
+            warn!("配置项 {key} 由 {previous} 改为 {value}，该项需要重启服务才能生效，当前仍使用 {previous}");
+        } else {
+            info!("配置项 {key} 由 {previous} 改为 {value}");
+            applied += 1;
+        }
+    }
+    if applied == 0 {
+        info!("配置已重新加载，没有可立即生效的变更");
+        return;
+    }
+
+    next.server = current.server.clone();
+    next.database = current.database.clone();
+    next.metrics = current.metrics.clone();
+    next.logging = current.logging.clone();
+    state.replace_config(next);
+    info!("配置已重新加载，{applied} 项变更已生效");
+}
+
+/// 逐项比较两份配置，返回 `(配置项, 旧值, 新值)`。
+///
+/// 未设置的可选项在展开时不存在，所以要取两边键的并集，否则删掉的配置项不会出现在日志里。
+fn config_changes(before: &AppConfig, after: &AppConfig) -> Vec<(String, String, String)> {
+    let before = flatten_config(before);
+    let after = flatten_config(after);
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    keys.into_iter()
+        .filter(|key| before.get(*key) != after.get(*key))
+        .map(|key| {
+            let describe = |value: Option<&String>| match value {
+                None => "(未设置)".to_owned(),
+                Some(_) if SECRET_CONFIG_KEYS.contains(&key.as_str()) => "(已隐藏)".to_owned(),
+                Some(value) => value.clone(),
+            };
+            (
+                key.clone(),
+                describe(before.get(key)),
+                describe(after.get(key)),
+            )
+        })
+        .collect()
+}
+
+fn flatten_config(config: &AppConfig) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    let Ok(toml::Value::Table(sections)) = toml::Value::try_from(config) else {
+        return fields;
+    };
+    for (section, value) in sections {
+        match value {
+            toml::Value::Table(table) => {
+                for (key, value) in table {
+                    fields.insert(format!("{section}.{key}"), value.to_string());
+                }
+            }
+            value => {
+                fields.insert(section, value.to_string());
+            }
+        }
+    }
+    fields
+}
+
+async fn run_user_command(
+    state: &SharedState,
+    command: UserCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        UserCommand::Create {
+            username,
+            qq,
+            password,
+        } => {
+            let temporary = password.is_none();
+            let password = password.unwrap_or_else(generate_temporary_password);
+            let uid = create_user(state.repo.as_ref(), &username, &qq, &password).await?;
+            println!("已创建用户 {} (UID {uid})", username.trim());
+            if temporary {
+                println!("临时密码：{password}");
+            }
+        }
+        UserCommand::List { query, limit } => {
+            let keyword = query.as_deref().map(str::trim).filter(|q| !q.is_empty());
+            let rows = state.repo.search_users(keyword, limit, 0).await?;
+            let config = state.config();
+            for row in rows {
+                let user = AdminUserResponse::from_db(row, &config);
+                let mut flags = Vec::new();
+                if user.is_admin {
+                    flags.push("管理员");
+                }
+                if user.banned {
+                    flags.push("已封禁");
+                }
+                if user.deleted {
+                    flags.push("已注销");
+                } else if user.deletion_scheduled_at.is_some() {
+                    flags.push("等待注销");
+                }
+                println!(
+                    "{}\t{}\tQQ {}\t{}\t{}",
+                    user.uid,
+                    user.username,
+                    user.qq,
+                    user.created_at,
+                    flags.join(",")
+                );
+            }
+        }
+        UserCommand::ResetPassword { user } => {
+            let user = find_cli_user(state, &user).await?;
+            let temporary_password = generate_temporary_password();
+            let password_hash = hash_password(&temporary_password)?;
+            state
+                .repo
+                .replace_password(user.id, &password_hash, None)
+                .await?;
+            println!(
+                "已重置 {} 的密码，临时密码：{temporary_password}",
+                user.username
+            );
+        }
+        UserCommand::Ban { user } => {
+            let user = find_cli_user(state, &user).await?;
+            let revoked = state
+                .repo
+                .set_user_banned(user.id, Some(&now_iso()))
+                .await?;
+            println!("已封禁 {}，注销 {revoked} 个登录会话", user.username);
+        }
+        UserCommand::Unban { user } => {
+            let user = find_cli_user(state, &user).await?;
+            state.repo.set_user_banned(user.id, None).await?;
+            println!("已解除 {} 的封禁", user.username);
+        }
+    }
+    Ok(())
+}
+
+async fn run_admin_command(
+    state: &SharedState,
+    config_path: &FsPath,
+    command: AdminCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (uid, grant) = match command {
+        AdminCommand::Grant { uid } => (uid, true),
+        AdminCommand::Revoke { uid } => (uid, false),
+    };
+    let uid = uid.trim();
+    if grant == state.config().is_admin(uid) {
+        println!(
+            "{uid} {}",
+            if grant {
+                "已是管理员"
+            } else {
+                "不是管理员"
+            }
+        );
+        return Ok(());
+    }
+    if grant {
+        match state.repo.find_user_by_uid(uid).await? {
+            Some(user) if user.deleted_at.is_none() => {}
+            Some(_) => return Err(format!("UID 为 {uid} 的账号已注销").into()),
+            None => return Err(format!("找不到 UID 为 {uid} 的用户").into()),
+        }
+    }
+
+    let contents = match fs::read_to_string(config_path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let mut document: DocumentMut = contents.parse()?;
+    let admins = document["admins"].or_insert(toml_edit::table());
+    let uids = admins["uids"]
+        .or_insert(toml_edit::value(Array::new()))
+        .as_array_mut()
+        .ok_or_else(|| ConfigError::new("admins.uids", "必须是数组"))?;
+    if grant {
+        uids.push(uid);
+    } else {
+        uids.retain(|value| value.as_str() != Some(uid));
+    }
+    fs::write(config_path, document.to_string())?;
+    println!(
+        "已{} {uid} 的管理员权限（写入 {}），运行中的服务会自动重新加载",
+        if grant { "授予" } else { "撤销" },
+        config_path.display()
+    );
+    Ok(())
+}
+
+async fn run_backup_command(
+    state: &SharedState,
+    command: BackupCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let settings = state.config().backup.clone();
+    match command {
+        BackupCommand::Create => {
+            let backup = create_backup(state.repo.as_ref(), &settings).await?;
+            println!(
+                "已创建备份 {}（{} 字节）",
+                settings.dir.join(&backup.file).display(),
+                backup.size_bytes
+            );
+        }
+        BackupCommand::List => {
+            for backup in list_backups(&settings.dir)? {
+                println!(
+                    "{}\t{}\t{} 字节",
+                    backup.file, backup.created_at, backup.size_bytes
+                );
+            }
+        }
+        BackupCommand::Verify { file } => {
+            let file = resolve_backup_file(&settings, &file)?;
+            verify_backup(&file).await?;
+            println!("{} 完整性检查通过", file.display());
+        }
+    }
+    Ok(())
+}
+
+async fn restore_backup(
+    config: &AppConfig,
+    file: &FsPath,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if config.database.backend != DatabaseBackend::Sqlite {
+        return Err("postgres 后端不支持从备份恢复，请使用 pg_restore".into());
+    }
+    let _instance_lock =
+        lock_database(&config.database).map_err(|err| format!("{err}，请先停止服务再恢复备份"))?;
+    let file = resolve_backup_file(&config.backup, file)?;
+    verify_backup(&file).await?;
+
+    let target = &config.database.path;
+    let mut staged = target.clone().into_os_string();
+    staged.push(".restoring");
+    let staged = PathBuf::from(staged);
+    fs::copy(&file, &staged)?;
+
+    if target.exists() {
+        let current = SqliteRepository::connect(&config.database).await?;
+        let snapshot = create_backup(&current, &config.backup).await;
+        current.close().await;
+        match snapshot {
+            Ok(snapshot) => println!("已将当前数据库备份为 {}", snapshot.file),
+            Err(err) => {
+                let _ = fs::remove_file(&staged);
+                return Err(format!("备份当前数据库失败，已放弃恢复: {err}").into());
+            }
+        }
+    }
+
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = target.clone().into_os_string();
+        sidecar.push(suffix);
+        match fs::remove_file(&sidecar) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+    }
+    fs::rename(&staged, target)?;
+    println!("已从 {} 恢复数据库 {}", file.display(), target.display());
+    Ok(())
+}
+
+async fn migrate_to_postgres(
+    config: &AppConfig,
+    to: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let url = to
+        .or_else(|| config.database.url.clone())
+        .ok_or("缺少目标 Postgres 地址，请使用 --to 或填写 database.url")?;
+    let _instance_lock =
+        lock_database(&config.database).map_err(|err| format!("{err}，请先停止服务再迁移数据"))?;
+    let source = SqliteRepository::connect(&config.database).await?;
+    let unknown: Vec<String> = source
+        .schema_columns()
+        .await?
+        .into_iter()
+        .filter(|(table, column)| {
+            table != "sqlite_sequence"
+                && !REQUIRED_SCHEMA.iter().any(|(known_table, columns)| {
+                    known_table == table && columns.contains(&column.as_str())
+                })
+        })
+        .map(|(table, column)| format!("{table}.{column}"))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!(
+            "源数据库包含无法迁移的表或字段: {}，已放弃迁移",
+            unknown.join(", ")
+        )
+        .into());
+    }
+
+    let target = PostgresRepository::open(PgConnectOptions::from_str(&url)?, 1).await?;
+    for (table, _) in REQUIRED_SCHEMA {
+        let exists: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+            .bind(table)
+            .fetch_one(&target.pool)
+            .await?;
+        if exists && table != "categories" {
+            let has_rows: bool =
+                sqlx::query_scalar(&format!("SELECT EXISTS(SELECT 1 FROM {table})"))
+                    .fetch_one(&target.pool)
+                    .await?;
+            if has_rows {
+                return Err(format!("目标数据库的 {table} 表中已有数据，已放弃迁移").into());
+            }
+        }
+    }
+    target.init_schema().await?;
+
+    let mut tx = target.pool.begin().await?;
+    sqlx::query("DELETE FROM categories")
+        .execute(&mut *tx)
+        .await?;
+    let mut migrated = Vec::new();
+    for (table, columns) in REQUIRED_SCHEMA {
+        let mut types = Vec::with_capacity(columns.len());
+        for column in columns {
+            let data_type: String = sqlx::query_scalar(
+                r#"SELECT data_type::TEXT FROM information_schema.columns
+                   WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2"#,
+            )
+            .bind(table)
+            .bind(column)
+            .fetch_one(&mut *tx)
+            .await?;
+            types.push(data_type);
+        }
+
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM {table} ORDER BY rowid",
+            columns.join(", ")
+        ))
+        .fetch_all(&source.pool)
+        .await?;
+        let insert = format!(
+            "INSERT INTO {table} ({}) VALUES ({})",
+            columns.join(", "),
+            (1..=columns.len())
+                .map(|index| format!("${index}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        for row in &rows {
+            let mut query = sqlx::query(&insert);
+            for (index, data_type) in types.iter().enumerate() {
+                query = match data_type.as_str() {
+                    "bigint" => query.bind(row.try_get::<Option<i64>, _>(index)?),
+                    "boolean" => query.bind(row.try_get::<Option<bool>, _>(index)?),
+                    "double precision" => query.bind(row.try_get::<Option<f64>, _>(index)?),
+                    _ => query.bind(row.try_get::<Option<String>, _>(index)?),
+                };
+            }
+            query.execute(&mut *tx).await?;
+        }
+        if columns.first() == Some(&"id") && types.first().map(String::as_str) == Some("bigint") {
+            sqlx::query(&format!(
+                r#"SELECT setval(pg_get_serial_sequence('{table}', 'id'),
+                                 COALESCE((SELECT MAX(id) FROM {table}), 0) + 1, false)"#
+            ))
+            .execute(&mut *tx)
+            .await?;
+        }
+        migrated.push(format!("{table} {} 行", rows.len()));
+    }
+    tx.commit().await?;
+    source.close().await;
+    target.close().await;
+
+    println!("已迁移: {}", migrated.join("、"));
+    Ok(())
+}
+
+fn run_config_check(
+    config: &AppConfig,
+    config_path: &FsPath,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if config_path.exists() {
+        println!("配置文件: {}", config_path.display());
+    } else {
+        println!("配置文件: {}（不存在，使用默认值）", config_path.display());
+    }
+    for (name, keys, _) in config_env_overrides() {
+        println!("环境变量覆盖: {name} -> {}", keys.join("."));
+    }
+    println!("监听地址: {}", config.server.addr);
+    match config.database.backend {
+        DatabaseBackend::Sqlite => println!("数据库: {}", config.database.path.display()),
+        DatabaseBackend::Postgres => println!("数据库: postgres"),
+    }
+    println!("管理员: {} 个", config.admins.uids.len());
+    println!("配置有效");
+    Ok(())
+}
+
+async fn run_post_command(
+    state: &SharedState,
+    command: PostCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        PostCommand::Delete { id } => {
+            if !state.repo.delete_post(id).await? {
+                return Err(ApiError::NotFound.into());
+            }
+            println!("已删除帖子 {id}");
+        }
+        PostCommand::DeleteComment { id } => {
+            if !state.repo.delete_comment(id, None).await? {
+                return Err(ApiError::NotFound.into());
+            }
+            println!("已删除评论 {id}");
+        }
+    }
+    Ok(())
+}
+
+async fn run_sessions_command(
+    state: &SharedState,
+    command: SessionsCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        SessionsCommand::Purge { all } => {
+            let expired_before = (!all).then(now_iso);
+            let purged = state.repo.purge_sessions(expired_before.as_deref()).await?;
+            println!("已清理 {purged} 个登录会话");
+        }
+    }
+    Ok(())
+}
+
+async fn find_cli_user(state: &SharedState, key: &str) -> ApiResult<DbAdminUser> {
+    let key = key.trim();
+    let user = match state.repo.find_user_by_uid(key).await? {
+        Some(user) => user,
+        None => state
+            .repo
+            .find_user_by_username(key)
+            .await?
+            .ok_or(ApiError::NotFound)?,
+    };
+    if user.deleted_at.is_some() {
+        return Err(ApiError::invalid(
+            "user.deleted",
+            "该账号已注销",
+            "This account has been deleted",
+        ));
+    }
+    Ok(user)
+}
+
+async fn register(
+    State(state): State<SharedState>,
+    ApiJson(payload): ApiJson<RegisterPayload>,
+) -> ApiResult<impl IntoResponse> {
+    create_user(
+        state.repo.as_ref(),
+        &payload.username,
+        &payload.qq,
+        &payload.password,
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(MessageResponse {
+            message: "注册成功".into(),
+        }),
+    ))
+}
+
+async fn create_user(
+    repo: &dyn Repository,
+    username: &str,
+    qq: &str,
+    password: &str,
+) -> ApiResult<String> {
+    let mut problems = Vec::new();
+    if username.trim().is_empty() {
+        problems.push(
+            ErrorDetail::new(
+                "user.username_empty",
+                "用户名不能为空",
+                "Username must not be empty",
+            )
+            .field("username"),
+        );
+    }
+    if qq.trim().is_empty() {
+        problems.push(
+            ErrorDetail::new(
+                "user.qq_empty",
+                "QQ号不能为空",
+                "QQ number must not be empty",
+            )
+            .field("qq"),
+        );
+    }
+    if password.len() < 6 {
+        problems.push(
+            ErrorDetail::new(
+                "user.password_too_short",
+                "密码至少需要6位",
+                "Password must be at least 6 characters",
+            )
+            .field("password"),
+        );
+    }
+    if !problems.is_empty() {
+        return Err(ApiError::Validation(problems));
+    }
+
+    let hashed = hash_password(password)?;
+    let now = now_iso();
+    let uid = Uuid::new_v4().to_string();
+
+    let result = repo
+        .create_user(&uid, username.trim(), qq.trim(), &hashed, &now)
+        .await;
+
+    if let Err(err) = result {
+        if is_unique_violation(&err) {
+            return Err(ApiError::conflict(
+                "user.username_taken",
+                "用户名已存在",
+                "Username is already taken",
+            ));
+        }
+        return Err(ApiError::from(err));
+    }
+
+    Ok(uid)
+}
+
+async fn login(
+    State(state): State<SharedState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<LoginPayload>,
+) -> ApiResult<Json<UserResponse>> {
+    let client = client_ip(peer, &headers).to_string();
+    state.rate_limit(RateLimitAction::Login, &client)?;
+    state.rate_limit(
+        RateLimitAction::LoginAccount,
+        &format!("{client} {}", payload.username.trim().to_lowercase()),
+    )?;
+    let Some(user) = state.repo.find_login_user(payload.username.trim()).await? else {
+        counter!(METRIC_LOGIN_FAILURES, "reason" => "invalid_credentials").increment(1);
+        return Err(ApiError::Unauthorized);
+    };
+
+    if !verify_password(&user.password_hash, &payload.password)? {
+        counter!(METRIC_LOGIN_FAILURES, "reason" => "invalid_credentials").increment(1);
+        return Err(ApiError::Unauthorized);
+    }
+    if user.banned_at.is_some() {
+        counter!(METRIC_LOGIN_FAILURES, "reason" => "banned").increment(1);
+        return Err(ApiError::Forbidden);
+    }
+
+    let session_id = Uuid::new_v4().to_string();
+    let now = OffsetDateTime::now_utc();
+    let expires_at = now + Duration::days(SESSION_TTL_DAYS);
+
+    state
+        .repo
+        .create_session(
+            &session_id,
+            user.id,
+            &format_time(now),
+            &format_time(expires_at),
+        )
+        .await?;
+
+    let mut cookie = Cookie::new(SESSION_COOKIE, session_id);
+    cookie.set_http_only(true);
+    cookie.set_path("/");
+    cookie.set_max_age(Duration::days(SESSION_TTL_DAYS));
+    cookies.add(cookie);
+
+    let is_admin = state.config().is_admin(&user.uid);
+
+    Ok(Json(UserResponse {
+        id: user.id,
+        username: user.username,
+        qq: user.qq,
+        uid: user.uid,
+        is_admin,
+        deletion_scheduled_at: user.deletion_scheduled_at,
+    }))
+}
+
+async fn logout(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+) -> ApiResult<impl IntoResponse> {
+    if let Some(cookie) = cookies.get(SESSION_COOKIE) {
+        state.repo.delete_session(cookie.value()).await?;
+
+        let mut expired = Cookie::from(SESSION_COOKIE);
+        expired.set_path("/");
+        cookies.remove(expired);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "退出成功".into(),
+    }))
+}
+
+async fn me(State(state): State<SharedState>, cookies: Cookies) -> ApiResult<Json<UserResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    let is_admin = state.config().is_admin(&user.uid);
+    Ok(Json(UserResponse {
+        id: user.id,
+        username: user.username,
+        qq: user.qq,
+        uid: user.uid,
+        is_admin,
+        deletion_scheduled_at: user.deletion_scheduled_at,
+    }))
+}
+
+async fn create_post(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<CreatePostPayload>,
+) -> ApiResult<impl IntoResponse> {
+    validate_post_text(&payload.title, &payload.content)?;
+
+    let user = authenticate(&state, &cookies).await?;
+    state.rate_limit(RateLimitAction::Post, &user.uid)?;
+    let now = now_iso();
+    let anonymous = payload.anonymous.unwrap_or(false);
+    let category = normalize_post_category(&state, &user, payload.category).await?;
+    let tags = normalize_post_tags(payload.tags.unwrap_or_default())?;
+    let publish_at = normalize_publish_at(payload.publish_at.as_deref())?;
+    let poll = payload
+        .poll
+        .map(|poll| validate_poll(poll, publish_at.as_deref()))
+        .transpose()?;
+    let expires_at = normalize_expires_at(payload.expires_at.as_deref(), publish_at.as_deref())?;
+    let status = if publish_at.is_some() {
+        PostStatus::Draft
+    } else {
+        PostStatus::Published
+    };
+
+    let post = NewPost {
+        user_id: user.id,
+        title: payload.title.trim().to_owned(),
+        content: payload.content.trim().to_owned(),
+        category,
+        anonymous,
+        status,
+        publish_at,
+        expires_at,
+        tags,
+        poll,
+    };
+    let allow_new_tags = state.config().tags.allow_new;
+    state.repo.create_post(&post, allow_new_tags, &now).await?;
+    if status == PostStatus::Published {
+        counter!(METRIC_POSTS_CREATED).increment(1);
+    }
+
+    let message = if post.publish_at.is_some() {
+        "已加入定时发布"
+    } else {
+        "发布成功"
+    };
+    Ok((
+        StatusCode::CREATED,
+        Json(MessageResponse {
+            message: message.into(),
+        }),
+    ))
+}
+
+async fn list_posts(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiQuery(query): ApiQuery<PostListQuery>,
+) -> ApiResult<Json<Vec<PostSummary>>> {
+    let user = authenticate(&state, &cookies).await?;
+    let viewer = Viewer { id: user.id };
+    let category_filter = normalize_query_category(&state, query.category).await?;
+    let following_only = normalize_feed(query.feed)?;
+    let sort = normalize_sort(query.sort)?;
+    let full = query.full.unwrap_or(false);
+    let (limit, offset) = page_bounds(query.page, query.page_size);
+
+    let tag_filter = query.tag.as_deref().map(normalize_tag_name).transpose()?;
+
+    let posts = state
+        .repo
+        .list_posts(&PostFeed {
+            viewer_id: user.id,
+            category: category_filter.clone(),
+            following_only,
+            tag: tag_filter,
+            sort,
+            full,
+            limit,
+            offset,
+        })
+        .await?;
+    let response = posts
+        .into_iter()
+        .map(|post| {
+            let pinned = post.pinned
+                && !following_only
+                && (category_filter.is_some() || post.pin_scope == Some(PinScope::Global));
+            let mut summary = PostSummary::from_db(post, &viewer, full);
+            summary.pinned = pinned;
+            summary
+        })
+        .collect();
+
+    Ok(Json(response))
+}
+
+async fn list_archived_posts(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiQuery(query): ApiQuery<ArchiveQuery>,
+) -> ApiResult<Json<Vec<PostSummary>>> {
+    let user = authenticate(&state, &cookies).await?;
+    let viewer = Viewer { id: user.id };
+    let category_filter = normalize_query_category(&state, query.category).await?;
+    let full = query.full.unwrap_or(false);
+    let (limit, offset) = page_bounds(query.page, query.page_size);
+
+    let rows = state
+        .repo
+        .list_archived_posts(user.id, category_filter.as_deref(), full, limit, offset)
+        .await?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|post| PostSummary::from_db(post, &viewer, full))
+            .collect(),
+    ))
+}
+
+async fn create_comment(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+    ApiJson(payload): ApiJson<CreateCommentPayload>,
+) -> ApiResult<impl IntoResponse> {
+    if payload.content.trim().is_empty() {
+        return Err(ApiError::invalid_field(
+            "content",
+            "comment.content_empty",
+            "内容不能为空",
+            "Content must not be empty",
+        ));
+    }
+
+    let user = authenticate(&state, &cookies).await?;
+    state.rate_limit(RateLimitAction::Comment, &user.uid)?;
+    let now = now_iso();
+    let anonymous = payload.anonymous.unwrap_or(false);
+    match state.repo.find_post_archived(post_id).await? {
+        None => return Err(ApiError::NotFound),
+        Some(true) => {
+            return Err(ApiError::invalid(
+                "comment.post_archived",
+                "帖子已归档，不能评论",
+                "This post is archived and can no longer be commented on",
+            ))
+        }
+        Some(false) => {}
+    }
+
+    state
+        .repo
+        .create_comment(post_id, user.id, payload.content.trim(), anonymous, &now)
+        .await?;
+    counter!(METRIC_COMMENTS_CREATED).increment(1);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(MessageResponse {
+            message: "评论成功".into(),
+        }),
+    ))
+}
+
+async fn get_post(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+) -> ApiResult<Json<PostDetailResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    let viewer = Viewer { id: user.id };
+    let post = load_post(&state, post_id, viewer.id).await?;
+    let comments = fetch_comments(&state, post.id, &viewer).await?;
+    let muted = state.repo.find_subscription(viewer.id, post.id).await?;
+    let subscription = SubscriptionResponse {
+        subscribed: muted.is_some(),
+        muted: muted.unwrap_or(false),
+    };
+    let poll = load_poll(&state, post.id, viewer.id).await?;
+    Ok(Json(PostDetailResponse::from_parts(
+        post,
+        comments,
+        subscription,
+        poll,
+        &viewer,
+    )))
+}
+
+async fn list_my_posts(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiQuery(query): ApiQuery<ContentQuery>,
+) -> ApiResult<Json<Vec<PostSummary>>> {
+    let user = authenticate(&state, &cookies).await?;
+    let viewer = Viewer { id: user.id };
+    let full = query.full.unwrap_or(false);
+    let posts = fetch_posts_for_user(&state, user.id, true, &viewer, full).await?;
+    Ok(Json(posts))
+}
+
+async fn list_drafts(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+) -> ApiResult<Json<Vec<DraftResponse>>> {
+    let user = authenticate(&state, &cookies).await?;
+    let rows = state.repo.list_drafts(user.id).await?;
+
+    Ok(Json(rows.into_iter().map(DraftResponse::from).collect()))
+}
+
+async fn get_draft(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(draft_id): ApiPath<i64>,
+) -> ApiResult<Json<DraftResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    let draft = load_draft(&state, user.id, draft_id).await?;
+    Ok(Json(draft.into()))
+}
+
+async fn create_draft(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<SaveDraftPayload>,
+) -> ApiResult<impl IntoResponse> {
+    let user = authenticate(&state, &cookies).await?;
+    let draft = validate_draft(&state, &user, payload).await?;
+    let now = now_iso();
+
+    let allow_new_tags = state.config().tags.allow_new;
+    let draft_id = state
+        .repo
+        .create_draft(user.id, &draft, allow_new_tags, &now)
+        .await?;
+
+    let draft = load_draft(&state, user.id, draft_id).await?;
+    Ok((StatusCode::CREATED, Json(DraftResponse::from(draft))))
+}
+
+async fn save_draft(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(draft_id): ApiPath<i64>,
+    ApiJson(payload): ApiJson<SaveDraftPayload>,
+) -> ApiResult<Json<DraftResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    let draft = validate_draft(&state, &user, payload).await?;
+    let now = now_iso();
+
+    let allow_new_tags = state.config().tags.allow_new;
+    if !state
+        .repo
+        .update_draft(user.id, draft_id, &draft, allow_new_tags, &now)
+        .await?
+    {
+        return Err(ApiError::NotFound);
+    }
+
+    let draft = load_draft(&state, user.id, draft_id).await?;
+    Ok(Json(draft.into()))
+}
+
+async fn delete_draft(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(draft_id): ApiPath<i64>,
+) -> ApiResult<Json<MessageResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    load_draft(&state, user.id, draft_id).await?;
+    state.repo.delete_post(draft_id).await?;
+
+    Ok(Json(MessageResponse {
+        message: "草稿已删除".into(),
+    }))
+}
+
+async fn publish_draft(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(draft_id): ApiPath<i64>,
+) -> ApiResult<Json<MessageResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    let draft = load_draft(&state, user.id, draft_id).await?;
+    validate_post_text(&draft.title, &draft.content)?;
+    normalize_post_category(&state, &user, Some(draft.category)).await?;
+
+    if !state.repo.publish_post(draft_id, &now_iso()).await? {
+        return Err(ApiError::NotFound);
+    }
+    counter!(METRIC_POSTS_CREATED).increment(1);
+
+    Ok(Json(MessageResponse {
+        message: "发布成功".into(),
+    }))
+}
+
+async fn load_draft(state: &SharedState, user_id: i64, draft_id: i64) -> ApiResult<DbDraft> {
+    state
+        .repo
+        .find_draft(user_id, draft_id)
+        .await?
+        .ok_or(ApiError::NotFound)
+}
+
+async fn validate_draft(
+    state: &SharedState,
+    user: &AuthedUser,
+    payload: SaveDraftPayload,
+) -> ApiResult<NewDraft> {
+    let title = payload.title.unwrap_or_default().trim().to_string();
+    let content = payload.content.unwrap_or_default().trim().to_string();
+    let publish_at = normalize_publish_at(payload.publish_at.as_deref())?;
+    if publish_at.is_some() {
+        validate_post_text(&title, &content)?;
+    }
+
+    Ok(NewDraft {
+        title,
+        content,
+        category: normalize_post_category(state, user, payload.category).await?,
+        anonymous: payload.anonymous.unwrap_or(false),
+        tags: normalize_post_tags(payload.tags.unwrap_or_default())?,
+        publish_at,
+    })
+}
+
+async fn archive_expired_posts(repo: Arc<dyn Repository>, mut shutdown: watch::Receiver<bool>) {
+    let mut ticker = tokio::time::interval(StdDuration::from_secs(ARCHIVE_SWEEP_INTERVAL_SECS));
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown_requested(&mut shutdown) => break,
+        }
+        match repo.archive_expired_posts(&now_iso()).await {
+            Ok(0) => {}
+            Ok(count) => info!("已归档 {count} 篇过期帖子"),
+            Err(err) => warn!("归档过期帖子失败: {err}"),
+        }
+    }
+}
+
+async fn publish_scheduled_posts(repo: Arc<dyn Repository>, mut shutdown: watch::Receiver<bool>) {
+    let mut ticker = tokio::time::interval(StdDuration::from_secs(SCHEDULED_PUBLISH_INTERVAL_SECS));
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown_requested(&mut shutdown) => break,
+        }
+        match publish_due_posts(repo.as_ref()).await {
+            Ok(0) => {}
+            Ok(count) => info!("定时发布了 {count} 篇帖子"),
+            Err(err) => warn!("定时发布失败: {err}"),
+        }
+    }
+}
+
+async fn publish_due_posts(repo: &dyn Repository) -> Result<usize, sqlx::Error> {
+    let now = now_iso();
+    let mut published = 0;
+    for post_id in repo.due_scheduled_posts(&now).await? {
+        if repo.publish_post(post_id, &now).await? {
+            counter!(METRIC_POSTS_CREATED).increment(1);
+            published += 1;
+        }
+    }
+    Ok(published)
+}
+
+async fn get_user_profile(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(uid): ApiPath<String>,
+    ApiQuery(query): ApiQuery<ContentQuery>,
+) -> ApiResult<Json<UserProfileResponse>> {
+    let current = authenticate(&state, &cookies).await?;
+    let viewer = Viewer { id: current.id };
+    let user = find_user_by_uid(&state, &uid).await?;
+    let is_self = user.id == viewer.id;
+    if user.profile_hidden && !is_self {
+        return Err(ApiError::Forbidden);
+    }
+
+    let posts_hidden = user.posts_hidden && !is_self;
+    let posts = if posts_hidden {
+        Vec::new()
+    } else {
+        fetch_posts_for_user(&state, user.id, false, &viewer, query.full.unwrap_or(false)).await?
+    };
+    let stats = state.repo.follow_stats(user.id, viewer.id).await?;
+    let qq = viewer
+        .can_see_qq(user.id, user.qq_visibility, stats.follows_viewer)
+        .then_some(user.qq);
+
+    Ok(Json(UserProfileResponse {
+        username: user.username,
+        qq,
+        uid: user.uid,
+        joined_at: user.created_at,
+        follower_count: stats.follower_count,
+        following_count: stats.following_count,
+        is_following: stats.is_following,
+        posts_hidden,
+        posts,
+    }))
+}
+
+async fn follow_user(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(uid): ApiPath<String>,
+) -> ApiResult<Json<MessageResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    let target = find_user_by_uid(&state, &uid).await?;
+    if target.id == user.id {
+        return Err(ApiError::invalid(
+            "follow.self",
+            "不能关注自己",
+            "You cannot follow yourself",
+        ));
+    }
+
+    state.repo.follow(user.id, target.id, &now_iso()).await?;
+
+    Ok(Json(MessageResponse {
+        message: "关注成功".into(),
+    }))
+}
+
+async fn unfollow_user(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(uid): ApiPath<String>,
+) -> ApiResult<Json<MessageResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    let target = find_user_by_uid(&state, &uid).await?;
+
+    state.repo.unfollow(user.id, target.id).await?;
+
+    Ok(Json(MessageResponse {
+        message: "已取消关注".into(),
+    }))
+}
+
+async fn get_privacy(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+) -> ApiResult<Json<PrivacySettingsResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    let settings = load_privacy(&state, user.id).await?;
+    Ok(Json(settings.into()))
+}
+
+async fn update_privacy(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<UpdatePrivacyPayload>,
+) -> ApiResult<Json<PrivacySettingsResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    let current = load_privacy(&state, user.id).await?;
+    let updated = DbPrivacy {
+        qq_visibility: payload.qq_visibility.unwrap_or(current.qq_visibility),
+        profile_hidden: payload.profile_hidden.unwrap_or(current.profile_hidden),
+        posts_hidden: payload.posts_hidden.unwrap_or(current.posts_hidden),
+    };
+
+    state.repo.update_privacy(user.id, &updated).await?;
+
+    Ok(Json(updated.into()))
+}
+
+async fn update_profile(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<UpdateProfilePayload>,
+) -> ApiResult<Json<UserResponse>> {
+    let mut user = authenticate(&state, &cookies).await?;
+    let new_username = payload
+        .username
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| user.username.clone());
+    let new_qq = payload
+        .qq
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| user.qq.clone());
+
+    if new_username == user.username && new_qq == user.qq {
+        return Err(ApiError::invalid(
+            "profile.no_changes",
+            "没有需要更新的内容",
+            "Nothing to update",
+        ));
+    }
+
+    let result = state
+        .repo
+        .update_profile(user.id, &new_username, &new_qq)
+        .await;
+
+    if let Err(err) = result {
+        if is_unique_violation(&err) {
+            return Err(ApiError::conflict(
+                "user.username_taken",
+                "用户名已存在",
+                "Username is already taken",
+            ));
+        }
+        return Err(ApiError::from(err));
+    }
+
+    user.username = new_username;
+    user.qq = new_qq;
+
+    let is_admin = state.config().is_admin(&user.uid);
+    Ok(Json(UserResponse {
+        id: user.id,
+        username: user.username,
+        qq: user.qq,
+        uid: user.uid,
+        is_admin,
+        deletion_scheduled_at: user.deletion_scheduled_at,
+    }))
+}
+
+async fn change_password(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<ChangePasswordPayload>,
+) -> ApiResult<Json<MessageResponse>> {
+    if payload.new_password.len() < 6 {
+        return Err(ApiError::invalid_field(
+            "new_password",
+            "user.password_too_short",
+            "新密码至少需要6位",
+            "New password must be at least 6 characters",
+        ));
+    }
+
+    let user = authenticate(&state, &cookies).await?;
+    let Some(current_hash) = state.repo.find_password_hash(user.id).await? else {
+        return Err(ApiError::Internal("用户不存在".into()));
+    };
+
+    if !verify_password(&current_hash, &payload.current_password)? {
+        return Err(ApiError::invalid_field(
+            "current_password",
+            "user.password_incorrect",
+            "原密码错误",
+            "Current password is incorrect",
+        ));
+    }
+
+    let new_hash = hash_password(&payload.new_password)?;
+    state.repo.update_password(user.id, &new_hash).await?;
+
+    Ok(Json(MessageResponse {
+        message: "密码修改成功".into(),
+    }))
+}
+
+async fn export_account(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+) -> ApiResult<impl IntoResponse> {
+    let user = authenticate(&state, &cookies).await?;
+    let profile = state.repo.export_profile(user.id).await?;
+    let posts = state.repo.export_posts(user.id).await?;
+    let comments = state.repo.export_comments(user.id).await?;
+    let current = cookies
+        .get(SESSION_COOKIE)
+        .map(|cookie| cookie.value().to_string());
+    let sessions = state
+        .repo
+        .export_sessions(user.id, current.as_deref())
+        .await?;
+
+    let export = AccountExport {
+        exported_at: now_iso(),
+        profile,
+        posts: posts.into_iter().map(ExportPost::from).collect(),
+        comments,
+        sessions,
+    };
+    let disposition = format!("attachment; filename=\"talk-wall-{}.json\"", user.uid);
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(export)))
+}
+
+async fn delete_account(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<DeleteAccountPayload>,
+) -> ApiResult<Json<AccountDeletionResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    let password_hash = state
+        .repo
+        .find_password_hash(user.id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !verify_password(&password_hash, &payload.password)? {
+        return Err(ApiError::invalid_field(
+            "password",
+            "user.password_incorrect",
+            "密码错误",
+            "Password is incorrect",
+        ));
+    }
+
+    let mode = payload.mode.unwrap_or(DeletionMode::Delete);
+    let grace_days = state.config().accounts.deletion_grace_days;
+    let now = OffsetDateTime::now_utc();
+    let scheduled_at = (grace_days > 0).then(|| format_time(now + Duration::days(grace_days)));
+
+    state
+        .repo
+        .request_account_deletion(user.id, mode, scheduled_at.as_deref(), &format_time(now))
+        .await?;
+
+    let mut expired = Cookie::from(SESSION_COOKIE);
+    expired.set_path("/");
+    cookies.remove(expired);
+
+    let message = match &scheduled_at {
+        Some(at) => format!("账号将于 {at} 注销，在此之前登录后可随时撤销"),
+        None => "账号已注销".into(),
+    };
+    Ok(Json(AccountDeletionResponse {
+        message,
+        mode,
+        scheduled_at,
+    }))
+}
+
+async fn cancel_account_deletion(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+) -> ApiResult<Json<MessageResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    if !state.repo.cancel_account_deletion(user.id).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "已撤销注销申请".into(),
+    }))
+}
+
+async fn purge_deleted_accounts(repo: Arc<dyn Repository>, mut shutdown: watch::Receiver<bool>) {
+    let mut ticker = tokio::time::interval(StdDuration::from_secs(ACCOUNT_PURGE_INTERVAL_SECS));
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown_requested(&mut shutdown) => break,
+        }
+        match purge_due_accounts(repo.as_ref()).await {
+            Ok(0) => {}
+            Ok(count) => info!("已注销 {count} 个账号"),
+            Err(err) => warn!("注销账号失败: {err}"),
+        }
+    }
+}
+
+async fn purge_due_accounts(repo: &dyn Repository) -> Result<usize, sqlx::Error> {
+    let now = now_iso();
+    let due = repo.due_account_deletions(&now).await?;
+    let count = due.len();
+    for (user_id, mode) in due {
+        repo.erase_account(user_id, mode, &now).await?;
+    }
+    Ok(count)
+}
+
+async fn list_bookmarks(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiQuery(query): ApiQuery<PageQuery>,
+    ApiQuery(content): ApiQuery<ContentQuery>,
+) -> ApiResult<Json<Vec<PostSummary>>> {
+    let user = authenticate(&state, &cookies).await?;
+    let viewer = Viewer { id: user.id };
+    let (limit, offset) = page_bounds(query.page, query.page_size);
+    let full = content.full.unwrap_or(false);
+
+    let rows = state
+        .repo
+        .list_bookmarked_posts(user.id, full, limit, offset)
+        .await?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|post| PostSummary::from_db(post, &viewer, full))
+            .collect(),
+    ))
+}
+
+async fn bookmark_post(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+) -> ApiResult<Json<MessageResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    ensure_post_exists(&state, post_id).await?;
+
+    state.repo.bookmark(user.id, post_id, &now_iso()).await?;
+
+    Ok(Json(MessageResponse {
+        message: "已收藏".into(),
+    }))
+}
+
+async fn unbookmark_post(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+) -> ApiResult<Json<MessageResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+
+    state.repo.unbookmark(user.id, post_id).await?;
+
+    Ok(Json(MessageResponse {
+        message: "已取消收藏".into(),
+    }))
+}
+
+async fn vote_poll(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+    ApiJson(payload): ApiJson<VotePollPayload>,
+) -> ApiResult<Json<PollResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    ensure_post_exists(&state, post_id).await?;
+    let poll = state
+        .repo
+        .find_poll(post_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if poll_is_closed(poll.closes_at.as_deref())? {
+        return Err(ApiError::invalid(
+            "poll.closed",
+            "投票已截止",
+            "This poll is closed",
+        ));
+    }
+
+    let mut option_ids = payload.option_ids;
+    option_ids.sort_unstable();
+    option_ids.dedup();
+    if option_ids.is_empty() {
+        return Err(ApiError::invalid_field(
+            "option_ids",
+            "poll.no_choice",
+            "请至少选择一个选项",
+            "Select at least one option",
+        ));
+    }
+    if !poll.multiple && option_ids.len() > 1 {
+        return Err(ApiError::invalid_field(
+            "option_ids",
+            "poll.single_choice",
+            "该投票为单选",
+            "This poll allows only one choice",
+        ));
+    }
+    let options = state.repo.list_poll_options(poll.id).await?;
+    if option_ids
+        .iter()
+        .any(|id| !options.iter().any(|option| option.id == *id))
+    {
+        return Err(ApiError::invalid_field(
+            "option_ids",
+            "poll.option_invalid",
+            "投票选项无效",
+            "Invalid poll option",
+        ));
+    }
+
+    let result = state
+        .repo
+        .cast_vote(poll.id, user.id, &option_ids, &now_iso())
+        .await;
+    if let Err(err) = result {
+        if is_unique_violation(&err) {
+            return Err(ApiError::conflict(
+                "poll.already_voted",
+                "已经投过票了",
+                "You have already voted",
+            ));
+        }
+        return Err(ApiError::from(err));
+    }
+
+    load_poll(&state, post_id, user.id)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::NotFound)
+}
+
+async fn load_poll(
+    state: &SharedState,
+    post_id: i64,
+    viewer_id: i64,
+) -> ApiResult<Option<PollResponse>> {
+    let Some(poll) = state.repo.find_poll(post_id).await? else {
+        return Ok(None);
+    };
+
+    let options = state.repo.list_poll_options(poll.id).await?;
+    let my_choices = state.repo.list_poll_choices(poll.id, viewer_id).await?;
+    let total_voters = state.repo.count_poll_voters(poll.id).await?;
+
+    let closed = poll_is_closed(poll.closes_at.as_deref())?;
+    let voted = !my_choices.is_empty();
+    let show_results = voted || closed;
+    let voters = if show_results && !poll.anonymous_votes {
+        state.repo.list_poll_voters(poll.id).await?
+    } else {
+        Vec::new()
+    };
+
+    let options = options
+        .into_iter()
+        .map(|option| PollOptionResponse {
+            id: option.id,
+            label: option.label,
+            votes: show_results.then_some(option.votes),
+            voters: (show_results && !poll.anonymous_votes).then(|| {
+                voters
+                    .iter()
+                    .filter(|voter| voter.option_id == option.id)
+                    .map(|voter| PollVoterInfo {
+                        username: voter.username.clone(),
+                        uid: voter.uid.clone(),
+                    })
+                    .collect()
+            }),
+        })
+        .collect();
+
+    Ok(Some(PollResponse {
+        id: poll.id,
+        multiple: poll.multiple,
+        anonymous_votes: poll.anonymous_votes,
+        closes_at: poll.closes_at,
+        closed,
+        voted,
+        my_choices,
+        total_voters: show_results.then_some(total_voters),
+        options,
+    }))
+}
+
+fn validate_poll(
+    payload: CreatePollPayload,
+    publish_at: Option<&str>,
+) -> Result<NewPoll, ApiError> {
+    let mut options: Vec<String> = Vec::new();
+    for option in payload.options {
+        let label = option.trim();
+        if label.is_empty() {
+            return Err(ApiError::invalid_field(
+                "poll.options",
+                "poll.option_empty",
+                "投票选项不能为空",
+                "Poll options must not be empty",
+            ));
+        }
+        if label.chars().count() > MAX_POLL_OPTION_CHARS {
+            return Err(ApiError::invalid_field(
+                "poll.options",
+                "poll.option_too_long",
+                format!("投票选项不能超过{MAX_POLL_OPTION_CHARS}个字"),
+                format!("Poll options must be at most {MAX_POLL_OPTION_CHARS} characters"),
+            ));
+        }
+        if options.iter().any(|existing| existing == label) {
+            return Err(ApiError::invalid_field(
+                "poll.options",
+                "poll.option_duplicate",
+                "投票选项不能重复",
+                "Poll options must be unique",
+            ));
+        }
+        options.push(label.to_string());
+    }
+    if !(MIN_POLL_OPTIONS..=MAX_POLL_OPTIONS).contains(&options.len()) {
+        return Err(ApiError::invalid_field(
+            "poll.options",
+            "poll.option_count",
+            format!("投票选项需要{MIN_POLL_OPTIONS}到{MAX_POLL_OPTIONS}个"),
+            format!("A poll needs {MIN_POLL_OPTIONS} to {MAX_POLL_OPTIONS} options"),
+        ));
+    }
+
+    let closes_at = match payload.closes_at.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(raw) => {
+            let closes_at = OffsetDateTime::parse(raw, &Rfc3339).map_err(|_| {
+                ApiError::invalid_field(
+                    "poll.closes_at",
+                    "poll.closes_at_invalid",
+                    "截止时间格式无效",
+                    "Invalid poll closing time",
+                )
+            })?;
+            let earliest = match publish_at {
+                Some(publish_at) => OffsetDateTime::parse(publish_at, &Rfc3339)
+                    .map_err(|err| ApiError::Internal(format!("时间解析失败: {err}")))?,
+                None => OffsetDateTime::now_utc(),
+            };
+            if closes_at <= earliest {
+                return Err(if publish_at.is_some() {
+                    ApiError::invalid_field(
+                        "poll.closes_at",
+                        "poll.closes_at_before_publish",
+                        "截止时间必须晚于定时发布时间",
+                        "Poll closing time must be after the scheduled publish time",
+                    )
+                } else {
+                    ApiError::invalid_field(
+                        "poll.closes_at",
+                        "poll.closes_at_past",
+                        "截止时间必须晚于当前时间",
+                        "Poll closing time must be in the future",
+                    )
+                });
+            }
+            Some(format_time(closes_at))
+        }
+    };
+
+    Ok(NewPoll {
+        options,
+        multiple: payload.multiple.unwrap_or(false),
+        anonymous_votes: payload.anonymous_votes.unwrap_or(true),
+        closes_at,
+    })
+}
+
+fn poll_is_closed(closes_at: Option<&str>) -> ApiResult<bool> {
+    let Some(closes_at) = closes_at else {
+        return Ok(false);
+    };
+    let closes_at = OffsetDateTime::parse(closes_at, &Rfc3339)
+        .map_err(|err| ApiError::Internal(format!("时间解析失败: {err}")))?;
+    Ok(closes_at <= OffsetDateTime::now_utc())
+}
+
+async fn subscribe_post(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+) -> ApiResult<Json<SubscriptionResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    ensure_post_exists(&state, post_id).await?;
+
+    state
+        .repo
+        .subscribe(user.id, post_id, false, &now_iso())
+        .await?;
+
+    Ok(Json(SubscriptionResponse {
+        subscribed: true,
+        muted: false,
+    }))
+}
+
+async fn update_subscription(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+    ApiJson(payload): ApiJson<UpdateSubscriptionPayload>,
+) -> ApiResult<Json<SubscriptionResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+    ensure_post_exists(&state, post_id).await?;
+
+    state
+        .repo
+        .subscribe(user.id, post_id, payload.muted, &now_iso())
+        .await?;
+
+    Ok(Json(SubscriptionResponse {
+        subscribed: true,
+        muted: payload.muted,
+    }))
+}
+
+async fn unsubscribe_post(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+) -> ApiResult<Json<SubscriptionResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+
+    state.repo.unsubscribe(user.id, post_id).await?;
+
+    Ok(Json(SubscriptionResponse {
+        subscribed: false,
+        muted: false,
+    }))
+}
+
+async fn list_notifications(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiQuery(query): ApiQuery<PageQuery>,
+) -> ApiResult<Json<Vec<NotificationResponse>>> {
+    let user = authenticate(&state, &cookies).await?;
+    let (limit, offset) = page_bounds(query.page, query.page_size);
+
+    let rows = state
+        .repo
+        .list_notifications(user.id, limit, offset)
+        .await?;
+
+    Ok(Json(
+        rows.into_iter().map(NotificationResponse::from).collect(),
+    ))
+}
+
+async fn mark_notifications_read(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+) -> ApiResult<Json<MessageResponse>> {
+    let user = authenticate(&state, &cookies).await?;
+
+    state
+        .repo
+        .mark_notifications_read(user.id, &now_iso())
+        .await?;
+
+    Ok(Json(MessageResponse {
+        message: "已全部标为已读".into(),
+    }))
+}
+
+async fn ensure_post_exists(state: &SharedState, post_id: i64) -> ApiResult<()> {
+    if state.repo.post_exists(post_id).await? {
+        Ok(())
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+async fn fetch_comments(
+    state: &SharedState,
+    post_id: i64,
+    viewer: &Viewer,
+) -> ApiResult<Vec<CommentResponse>> {
+    let rows = state.repo.list_comments(post_id, viewer.id).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|comment| CommentResponse::from_db(comment, viewer))
+        .collect())
+}
+
+async fn load_post(state: &SharedState, post_id: i64, viewer_id: i64) -> ApiResult<DbPost> {
+    state
+        .repo
+        .find_post(post_id, viewer_id)
+        .await?
+        .ok_or(ApiError::NotFound)
+}
+
+async fn fetch_posts_for_user(
+    state: &SharedState,
+    user_id: i64,
+    include_anonymous: bool,
+    viewer: &Viewer,
+    full: bool,
+) -> ApiResult<Vec<PostSummary>> {
+    let rows = state
+        .repo
+        .list_user_posts(user_id, include_anonymous, viewer.id, full)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|post| PostSummary::from_db(post, viewer, full))
+        .collect())
+}
+
+async fn find_user_by_uid(state: &SharedState, uid: &str) -> ApiResult<DbPublicUser> {
+    state
+        .repo
+        .find_public_user(uid)
+        .await?
+        .ok_or(ApiError::NotFound)
+}
+
+async fn load_privacy(state: &SharedState, user_id: i64) -> ApiResult<DbPrivacy> {
+    state
+        .repo
+        .find_privacy(user_id)
+        .await?
+        .ok_or_else(|| ApiError::Internal("用户不存在".into()))
+}
+
+fn validate_post_text(title: &str, content: &str) -> ApiResult<()> {
+    if title.trim().is_empty() {
+        return Err(ApiError::invalid_field(
+            "title",
+            "post.title_empty",
+            "标题不能为空",
+            "Title must not be empty",
+        ));
+    }
+    if content.trim().is_empty() {
+        return Err(ApiError::invalid_field(
+            "content",
+            "post.content_empty",
+            "内容不能为空",
+            "Content must not be empty",
+        ));
+    }
+    Ok(())
+}
+
+fn normalize_publish_at(raw: Option<&str>) -> ApiResult<Option<String>> {
+    match raw.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(raw) => {
+            let publish_at = OffsetDateTime::parse(raw, &Rfc3339).map_err(|_| {
+                ApiError::invalid_field(
+                    "publish_at",
+                    "post.publish_at_invalid",
+                    "定时发布时间格式无效",
+                    "Invalid scheduled publish time",
+                )
+            })?;
+            if publish_at <= OffsetDateTime::now_utc() {
+                return Err(ApiError::invalid_field(
+                    "publish_at",
+                    "post.publish_at_past",
+                    "定时发布时间必须晚于当前时间",
+                    "Scheduled publish time must be in the future",
+                ));
+            }
+            Ok(Some(format_time(publish_at)))
+        }
+    }
+}
+
+fn normalize_expires_at(raw: Option<&str>, publish_at: Option<&str>) -> ApiResult<Option<String>> {
+    let expires_at = match raw.map(str::trim) {
+        None | Some("") => return Ok(None),
+        Some(raw) => OffsetDateTime::parse(raw, &Rfc3339).map_err(|_| {
+            ApiError::invalid_field(
+                "expires_at",
+                "post.expires_at_invalid",
+                "到期时间格式无效",
+                "Invalid expiry time",
+            )
+        })?,
+    };
+    let earliest = match publish_at {
+        Some(publish_at) => OffsetDateTime::parse(publish_at, &Rfc3339)
+            .map_err(|err| ApiError::Internal(format!("时间解析失败: {err}")))?,
+        None => OffsetDateTime::now_utc(),
+    };
+    if expires_at <= earliest {
+        return Err(if publish_at.is_some() {
+            ApiError::invalid_field(
+                "expires_at",
+                "post.expires_at_before_publish",
+                "到期时间必须晚于定时发布时间",
+                "Expiry time must be after the scheduled publish time",
+            )
+        } else {
+            ApiError::invalid_field(
+                "expires_at",
+                "post.expires_at_past",
+                "到期时间必须晚于当前时间",
+                "Expiry time must be in the future",
+            )
+        });
+    }
+    Ok(Some(format_time(expires_at)))
+}
+
+fn normalize_post_lifetime(days: Option<i64>) -> ApiResult<Option<i64>> {
+    match days {
+        None | Some(0) => Ok(None),
+        Some(days) if (1..=MAX_POST_LIFETIME_DAYS).contains(&days) => Ok(Some(days)),
+        Some(_) => Err(ApiError::invalid_field(
+            "post_lifetime_days",
+            "category.lifetime_invalid",
+            format!("帖子有效期需要在1到{MAX_POST_LIFETIME_DAYS}天之间，0 表示永不过期"),
+            format!(
+                "Post lifetime must be between 1 and {MAX_POST_LIFETIME_DAYS} days, or 0 for no expiry"
+            ),
+        )),
+    }
+}
+
+fn split_tags(tags: Option<String>) -> Vec<String> {
+    tags.map(|tags| {
+        let mut tags: Vec<String> = tags.split(',').map(str::to_string).collect();
+        tags.sort();
+        tags
+    })
+    .unwrap_or_default()
+}
+
+async fn normalize_post_category(
+    state: &SharedState,
+    user: &AuthedUser,
+    category: Option<String>,
+) -> Result<String, ApiError> {
+    let value = category.unwrap_or_else(|| DEFAULT_CATEGORY.to_string());
+    let trimmed = value.trim();
+    let normalized = if trimmed.is_empty() {
+        DEFAULT_CATEGORY
+    } else {
+        trimmed
+    };
+    let Some(category) = find_category(state, normalized).await? else {
+        return Err(ApiError::invalid_field(
+            "category",
+            "category.invalid",
+            "请选择有效的分区",
+            "Please choose a valid category",
+        ));
+    };
+    if category.archived {
+        return Err(ApiError::invalid_field(
+            "category",
+            "category.archived",
+            "该分区已归档，不能发帖",
+            "This category is archived and no longer accepts posts",
+        ));
+    }
+    if category.post_permission == CategoryPermission::Admins && !state.config().is_admin(&user.uid)
+    {
+        return Err(ApiError::invalid_field(
+            "category",
+            "category.admin_only",
+            "该分区仅管理员可以发帖",
+            "Only administrators can post in this category",
+        ));
+    }
+    Ok(category.slug)
+}
+
+fn hot_score(created_at: &str, comment_count: i64) -> f64 {
+    let created = OffsetDateTime::parse(created_at, &Rfc3339)
+        .unwrap_or_else(|err| {
+            warn!("帖子创建时间 {created_at:?} 无法解析，按当前时间计算热度: {err}");
+            OffsetDateTime::now_utc()
+        })
+        .unix_timestamp();
+    (comment_count.max(1) as f64).log10() + created as f64 / HOT_SCORE_DECAY_SECONDS
+}
+
+fn post_select(dialect: &SqlDialect, full: bool) -> String {
+    let content = if full {
+        "p.content".to_string()
+    } else {
+        format!(
+            "substr(p.content, 1, {}) AS content",
+            POST_EXCERPT_CHARS + 1
+        )
+    };
+    format!(
+        "SELECT {POST_COLUMNS},\n       {} AS pinned,\n       {content}\n{POST_FROM}",
+        pinned_expression(dialect)
+    )
+}
+
+fn pinned_expression(dialect: &SqlDialect) -> String {
+    format!(
+        "(p.pin_scope IS NOT NULL AND (p.pin_expires_at IS NULL OR p.pin_expires_at > {}))",
+        dialect.now
+    )
+}
+
+fn excerpt(text: &str, max_chars: usize) -> String {
+    let mut chars = text.chars();
+    let mut result: String = chars.by_ref().take(max_chars).collect();
+    if chars.next().is_some() {
+        result.push('…');
+    }
+    result
+}
+
+fn normalize_feed(feed: Option<String>) -> Result<bool, ApiError> {
+    match feed.as_deref().map(str::trim) {
+        None | Some("") => Ok(false),
+        Some(FOLLOWING_FEED) => Ok(true),
+        Some(_) => Err(ApiError::invalid_field(
+            "feed",
+            "post.feed_unknown",
+            "未知的信息流类型",
+            "Unknown feed type",
+        )),
+    }
+}
+
+fn normalize_sort(sort: Option<String>) -> Result<PostSort, ApiError> {
+    match sort.as_deref().map(str::trim) {
+        None | Some("") | Some("new") => Ok(PostSort::New),
+        Some("active") => Ok(PostSort::Active),
+        Some("comments") => Ok(PostSort::Comments),
+        Some("hot") => Ok(PostSort::Hot),
+        Some(_) => Err(ApiError::invalid_field(
+            "sort",
+            "post.sort_unknown",
+            "未知的排序方式",
+            "Unknown sort order",
+        )),
+    }
+}
+
+fn page_bounds(page: Option<i64>, page_size: Option<i64>) -> (i64, i64) {
+    let page = page.unwrap_or(1).max(1);
+    let page_size = page_size
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    (page_size, (page - 1) * page_size)
+}
+
+async fn normalize_query_category(
+    state: &SharedState,
+    category: Option<String>,
+) -> Result<Option<String>, ApiError> {
+    if let Some(raw) = category {
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed == LATEST_CATEGORY {
+            return Ok(None);
+        }
+        if let Some(category) = find_category(state, trimmed).await? {
+            return Ok(Some(category.slug));
+        }
+        return Err(ApiError::invalid_field(
+            "category",
+            "category.filter_unknown",
+            "未知的分区筛选",
+            "Unknown category filter",
+        ));
+    }
+    Ok(None)
+}
+
+fn normalize_tag_name(raw: &str) -> Result<String, ApiError> {
+    let name = raw.trim().trim_start_matches('#').trim().to_lowercase();
+    if name.is_empty() {
+        return Err(ApiError::invalid(
+            "tag.name_empty",
+            "标签不能为空",
+            "Tag must not be empty",
+        ));
+    }
+    if name.chars().count() > MAX_TAG_CHARS {
+        return Err(ApiError::invalid(
+            "tag.name_too_long",
+            format!("标签不能超过{MAX_TAG_CHARS}个字符"),
+            format!("Tags must be at most {MAX_TAG_CHARS} characters"),
+        ));
+    }
+    if name
+        .chars()
+        .any(|c| c.is_whitespace() || c == ',' || c == '#')
+    {
+        return Err(ApiError::invalid(
+            "tag.name_invalid",
+            "标签不能包含空格、逗号或 #",
+            "Tags cannot contain spaces, commas or #",
+        ));
+    }
+    Ok(name)
+}
+
+fn normalize_post_tags(raw: Vec<String>) -> Result<Vec<String>, ApiError> {
+    let mut tags: Vec<String> = Vec::new();
+    for value in raw {
+        let name = normalize_tag_name(&value)?;
+        if !tags.contains(&name) {
+            tags.push(name);
+        }
+    }
+    if tags.len() > MAX_TAGS_PER_POST {
+        return Err(ApiError::invalid_field(
+            "tags",
+            "post.too_many_tags",
+            format!("每个帖子最多{MAX_TAGS_PER_POST}个标签"),
+            format!("A post can have at most {MAX_TAGS_PER_POST} tags"),
+        ));
+    }
+    Ok(tags)
+}
+
+fn normalize_category_slug(slug: &str) -> Result<String, ApiError> {
+    let slug = slug.trim();
+    if slug.is_empty() {
+        return Err(ApiError::invalid_field(
+            "slug",
+            "category.slug_empty",
+            "分区标识不能为空",
+            "Category slug must not be empty",
+        ));
+    }
+    if slug.chars().count() > MAX_CATEGORY_SLUG_CHARS {
+        return Err(ApiError::invalid_field(
+            "slug",
+            "category.slug_too_long",
+            format!("分区标识不能超过{MAX_CATEGORY_SLUG_CHARS}个字符"),
+            format!("Category slug must be at most {MAX_CATEGORY_SLUG_CHARS} characters"),
+        ));
+    }
+    if slug == LATEST_CATEGORY
+        || !slug
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ApiError::invalid_field(
+            "slug",
+            "category.slug_invalid",
+            "分区标识只能包含文字、数字、- 和 _",
+            "Category slug may only contain letters, digits, - and _",
+        ));
+    }
+    Ok(slug.to_string())
+}
+
+async fn ensure_column(
+    pool: &SqlitePool,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<bool, sqlx::Error> {
+    let query = format!("ALTER TABLE {table} ADD COLUMN {column} {definition}");
+    match sqlx::query(&query).execute(pool).await {
+        Ok(_) => Ok(true),
+        Err(sqlx::Error::Database(err)) if err.message().contains("duplicate column name") => {
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+async fn trending_tags(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiQuery(query): ApiQuery<TrendingTagsQuery>,
+) -> ApiResult<Json<Vec<TagCountResponse>>> {
+    authenticate(&state, &cookies).await?;
+    let days = query.days.unwrap_or(DEFAULT_TRENDING_DAYS).clamp(1, 90);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_TRENDING_LIMIT)
+        .clamp(1, MAX_PAGE_SIZE);
+    let since = format_time(OffsetDateTime::now_utc() - Duration::days(days));
+
+    let rows = state.repo.trending_tags(&since, limit).await?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| TagCountResponse {
+                name: row.name,
+                post_count: row.post_count,
+            })
+            .collect(),
+    ))
+}
+
+async fn create_tag(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<CreateTagPayload>,
+) -> ApiResult<impl IntoResponse> {
+    authenticate_admin(&state, &cookies).await?;
+    let name = normalize_tag_name(&payload.name)?;
+
+    if let Err(err) = state.repo.create_tag(&name, &now_iso()).await {
+        if is_unique_violation(&err) {
+            return Err(ApiError::conflict(
+                "tag.exists",
+                "标签已存在",
+                "Tag already exists",
+            ));
+        }
+        return Err(ApiError::from(err));
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(MessageResponse {
+            message: "标签已创建".into(),
+        }),
+    ))
+}
+
+async fn update_tag(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(name): ApiPath<String>,
+    ApiJson(payload): ApiJson<UpdateTagPayload>,
+) -> ApiResult<Json<MessageResponse>> {
+    authenticate_admin(&state, &cookies).await?;
+    let name = normalize_tag_name(&name)?;
+
+    if !state.repo.set_tag_banned(&name, payload.banned).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: if payload.banned {
+            "标签已禁用".into()
+        } else {
+            "标签已恢复".into()
+        },
+    }))
+}
+
+async fn merge_tag(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(name): ApiPath<String>,
+    ApiJson(payload): ApiJson<MergeTagPayload>,
+) -> ApiResult<Json<MessageResponse>> {
+    authenticate_admin(&state, &cookies).await?;
+    let source_name = normalize_tag_name(&name)?;
+    let target_name = normalize_tag_name(&payload.into)?;
+
+    let source = state
+        .repo
+        .find_tag(&source_name)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let target = state
+        .repo
+        .resolve_tag(&target_name)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if source.merged_into.is_some() {
+        return Err(ApiError::conflict(
+            "tag.already_merged",
+            "该标签已被合并",
+            "This tag has already been merged",
+        ));
+    }
+    if source.id == target.id {
+        return Err(ApiError::invalid(
+            "tag.merge_into_self",
+            "不能把标签合并到自身",
+            "A tag cannot be merged into itself",
+        ));
+    }
+
+    state.repo.merge_tag(source.id, target.id).await?;
+
+    Ok(Json(MessageResponse {
+        message: format!("已将 #{} 合并到 #{}", source.name, target.name),
+    }))
+}
+
+async fn list_categories(
+    State(state): State<SharedState>,
+) -> ApiResult<Json<Vec<CategoryResponse>>> {
+    let rows = fetch_categories(&state, false).await?;
+    Ok(Json(rows.into_iter().map(CategoryResponse::from).collect()))
+}
+
+async fn admin_list_categories(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+) -> ApiResult<Json<Vec<CategoryResponse>>> {
+    authenticate_admin(&state, &cookies).await?;
+    let rows = fetch_categories(&state, true).await?;
+    Ok(Json(rows.into_iter().map(CategoryResponse::from).collect()))
+}
+
+async fn create_category(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<CreateCategoryPayload>,
+) -> ApiResult<impl IntoResponse> {
+    authenticate_admin(&state, &cookies).await?;
+    let slug = normalize_category_slug(&payload.slug)?;
+    let post_lifetime_days = normalize_post_lifetime(payload.post_lifetime_days)?;
+    let name = payload
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or(&slug)
+        .to_string();
+
+    let category = DbCategory {
+        slug,
+        name,
+        description: payload
+            .description
+            .as_deref()
+            .map(str::trim)
+            .unwrap_or("")
+            .to_string(),
+        sort_order: payload.sort_order.unwrap_or(0),
+        archived: false,
+        post_permission: payload
+            .post_permission
+            .unwrap_or(CategoryPermission::Everyone),
+        post_lifetime_days,
+    };
+    if let Err(err) = state.repo.create_category(&category, &now_iso()).await {
+        if is_unique_violation(&err) {
+            return Err(ApiError::conflict(
+                "category.exists",
+                "分区已存在",
+                "Category already exists",
+            ));
+        }
+        return Err(ApiError::from(err));
+    }
+
+    Ok((StatusCode::CREATED, Json(CategoryResponse::from(category))))
+}
+
+async fn update_category(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(slug): ApiPath<String>,
+    ApiJson(payload): ApiJson<UpdateCategoryPayload>,
+) -> ApiResult<Json<CategoryResponse>> {
+    authenticate_admin(&state, &cookies).await?;
+    let current = find_category(&state, &slug)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let category = DbCategory {
+        name: payload
+            .name
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or(current.name),
+        description: payload
+            .description
+            .map(|v| v.trim().to_string())
+            .unwrap_or(current.description),
+        sort_order: payload.sort_order.unwrap_or(current.sort_order),
+        archived: payload.archived.unwrap_or(current.archived),
+        post_permission: payload.post_permission.unwrap_or(current.post_permission),
+        post_lifetime_days: match payload.post_lifetime_days {
+            Some(days) => normalize_post_lifetime(Some(days))?,
+            None => current.post_lifetime_days,
+        },
+        slug: current.slug,
+    };
+    state.repo.update_category(&category).await?;
+
+    Ok(Json(CategoryResponse::from(category)))
+}
+
+async fn delete_category(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(slug): ApiPath<String>,
+) -> ApiResult<Json<MessageResponse>> {
+    authenticate_admin(&state, &cookies).await?;
+    if state.repo.category_in_use(&slug).await? {
+        return Err(ApiError::conflict(
+            "category.not_empty",
+            "分区下仍有帖子，请改为归档",
+            "This category still has posts; archive it instead",
+        ));
+    }
+
+    if !state.repo.delete_category(&slug).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "分区已删除".into(),
+    }))
+}
+
+async fn fetch_categories(
+    state: &SharedState,
+    include_archived: bool,
+) -> ApiResult<Vec<DbCategory>> {
+    Ok(state.repo.list_categories(include_archived).await?)
+}
+
+async fn find_category(state: &SharedState, slug: &str) -> ApiResult<Option<DbCategory>> {
+    Ok(state.repo.find_category(slug).await?)
+}
+
+async fn pin_post(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+    ApiJson(payload): ApiJson<PinPostPayload>,
+) -> ApiResult<Json<MessageResponse>> {
+    authenticate_admin(&state, &cookies).await?;
+    let expires_at = match payload.expires_at.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(raw) => {
+            let expires_at = OffsetDateTime::parse(raw, &Rfc3339).map_err(|_| {
+                ApiError::invalid_field(
+                    "expires_at",
+                    "pin.expires_at_invalid",
+                    "置顶到期时间格式无效",
+                    "Invalid pin expiry time",
+                )
+            })?;
+            if expires_at <= OffsetDateTime::now_utc() {
+                return Err(ApiError::invalid_field(
+                    "expires_at",
+                    "pin.expires_at_past",
+                    "置顶到期时间必须晚于当前时间",
+                    "Pin expiry time must be in the future",
+                ));
+            }
+            Some(format_time(expires_at))
+        }
+    };
+
+    let pinned = state
+        .repo
+        .pin_post(post_id, payload.scope, &now_iso(), expires_at.as_deref())
+        .await?;
+    if !pinned {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "帖子已置顶".into(),
+    }))
+}
+
+async fn unpin_post(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+) -> ApiResult<Json<MessageResponse>> {
+    authenticate_admin(&state, &cookies).await?;
+
+    if !state.repo.unpin_post(post_id).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "已取消置顶".into(),
+    }))
+}
+
+async fn delete_post(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+) -> ApiResult<Json<MessageResponse>> {
+    authenticate_admin(&state, &cookies).await?;
+    if !state.repo.delete_post(post_id).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "帖子已删除".into(),
+    }))
+}
+
+async fn admin_list_users(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiQuery(query): ApiQuery<AdminUserQuery>,
+) -> ApiResult<Json<Vec<AdminUserResponse>>> {
+    authenticate_admin(&state, &cookies).await?;
+    let (limit, offset) = page_bounds(query.page, query.page_size);
+    let keyword = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let rows = state.repo.search_users(keyword, limit, offset).await?;
+
+    let config = state.config();
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| AdminUserResponse::from_db(row, &config))
+            .collect(),
+    ))
+}
+
+async fn admin_get_user(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(uid): ApiPath<String>,
+) -> ApiResult<Json<AdminUserDetailResponse>> {
+    authenticate_admin(&state, &cookies).await?;
+    let user = find_admin_user(&state, &uid).await?;
+    let stats = state.repo.admin_user_stats(user.id).await?;
+
+    Ok(Json(AdminUserDetailResponse {
+        user: AdminUserResponse::from_db(user, &state.config()),
+        post_count: stats.post_count,
+        anonymous_post_count: stats.anonymous_post_count,
+        comment_count: stats.comment_count,
+        session_count: stats.session_count,
+        last_login_at: stats.last_login_at,
+    }))
+}
+
+async fn reveal_post_author(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(post_id): ApiPath<i64>,
+    ApiQuery(query): ApiQuery<AuditReasonQuery>,
+) -> ApiResult<Json<AuthorAttributionResponse>> {
+    let admin = authenticate_admin(&state, &cookies).await?;
+    reveal_author(
+        &state,
+        &admin,
+        ModerationTarget::Post,
+        post_id,
+        AUDIT_REVEAL_POST_AUTHOR,
+        query.reason,
+    )
+    .await
+}
+
+async fn reveal_comment_author(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(comment_id): ApiPath<i64>,
+    ApiQuery(query): ApiQuery<AuditReasonQuery>,
+) -> ApiResult<Json<AuthorAttributionResponse>> {
+    let admin = authenticate_admin(&state, &cookies).await?;
+    reveal_author(
+        &state,
+        &admin,
+        ModerationTarget::Comment,
+        comment_id,
+        AUDIT_REVEAL_COMMENT_AUTHOR,
+        query.reason,
     )
-    .bind(&token)
-    .fetch_optional(&state.db)
-    .await?
-    .ok_or(ApiError::Unauthorized)?;
+    .await
+}
+
+async fn reveal_author(
+    state: &SharedState,
+    admin: &AuthedUser,
+    target: ModerationTarget,
+    target_id: i64,
+    action: &str,
+    reason: Option<String>,
+) -> ApiResult<Json<AuthorAttributionResponse>> {
+    let reason = reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty())
+        .ok_or_else(|| {
+            ApiError::invalid_field(
+                "reason",
+                "admin.reason_required",
+                "请填写查看原因",
+                "Please provide a reason",
+            )
+        })?;
+
+    let audit = NewAuditEntry {
+        admin_id: admin.id,
+        action,
+        target_type: target.as_str(),
+        target_id: &target_id.to_string(),
+        detail: Some(&reason),
+    };
+    let author = state
+        .repo
+        .reveal_author(target, target_id, &audit)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(Json(AuthorAttributionResponse {
+        author: AdminUserResponse::from_db(author, &state.config()),
+    }))
+}
+
+async fn admin_reset_password(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(uid): ApiPath<String>,
+) -> ApiResult<Json<PasswordResetResponse>> {
+    let admin = authenticate_admin(&state, &cookies).await?;
+    let user = find_admin_user(&state, &uid).await?;
+    if user.deleted_at.is_some() {
+        return Err(ApiError::invalid(
+            "user.deleted",
+            "该账号已注销",
+            "This account has been deleted",
+        ));
+    }
+
+    let temporary_password = generate_temporary_password();
+    let password_hash = hash_password(&temporary_password)?;
+
+    let audit = NewAuditEntry {
+        admin_id: admin.id,
+        action: AUDIT_RESET_PASSWORD,
+        target_type: "user",
+        target_id: &user.uid,
+        detail: None,
+    };
+    state
+        .repo
+        .replace_password(user.id, &password_hash, Some(&audit))
+        .await?;
+
+    Ok(Json(PasswordResetResponse { temporary_password }))
+}
+
+fn generate_temporary_password() -> String {
+    Uuid::new_v4()
+        .simple()
+        .to_string()
+        .chars()
+        .take(TEMPORARY_PASSWORD_CHARS)
+        .collect()
+}
+
+async fn admin_revoke_sessions(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(uid): ApiPath<String>,
+) -> ApiResult<Json<MessageResponse>> {
+    let admin = authenticate_admin(&state, &cookies).await?;
+    let user = find_admin_user(&state, &uid).await?;
+
+    let revoked = state
+        .repo
+        .revoke_sessions(user.id, admin.id, &user.uid)
+        .await?;
+
+    Ok(Json(MessageResponse {
+        message: format!("已注销 {revoked} 个登录会话"),
+    }))
+}
+
+async fn admin_list_audit(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiQuery(query): ApiQuery<PageQuery>,
+) -> ApiResult<Json<Vec<AuditEntryResponse>>> {
+    authenticate_admin(&state, &cookies).await?;
+    let (limit, offset) = page_bounds(query.page, query.page_size);
+
+    let rows = state.repo.list_audit(limit, offset).await?;
+
+    Ok(Json(
+        rows.into_iter().map(AuditEntryResponse::from).collect(),
+    ))
+}
+
+async fn find_admin_user(state: &SharedState, uid: &str) -> ApiResult<DbAdminUser> {
+    state
+        .repo
+        .find_user_by_uid(uid)
+        .await?
+        .ok_or(ApiError::NotFound)
+}
+
+fn like_pattern(keyword: &str) -> String {
+    let escaped = keyword
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+async fn admin_delete_comment(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiPath(comment_id): ApiPath<i64>,
+) -> ApiResult<Json<MessageResponse>> {
+    let admin = authenticate_admin(&state, &cookies).await?;
+
+    let audit = NewAuditEntry {
+        admin_id: admin.id,
+        action: AUDIT_DELETE_COMMENT,
+        target_type: "comment",
+        target_id: &comment_id.to_string(),
+        detail: None,
+    };
+    if !state.repo.delete_comment(comment_id, Some(&audit)).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(MessageResponse {
+        message: "评论已删除".into(),
+    }))
+}
+
+async fn bulk_moderate(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<BulkModerationPayload>,
+) -> ApiResult<Json<BulkModerationResponse>> {
+    let admin = authenticate_admin(&state, &cookies).await?;
+    let user_id = match payload.user.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(uid) => Some(find_admin_user(&state, uid).await?.id),
+    };
+    let keyword = payload
+        .keyword
+        .map(|keyword| keyword.trim().to_string())
+        .filter(|keyword| !keyword.is_empty());
+    if user_id.is_none() && keyword.is_none() {
+        return Err(ApiError::invalid(
+            "moderation.filter_required",
+            "请至少指定用户或关键词",
+            "Specify at least a user or a keyword",
+        ));
+    }
+    let filter = ModerationFilter {
+        user_id,
+        keyword,
+        since: normalize_filter_time(payload.since.as_deref())?,
+        until: normalize_filter_time(payload.until.as_deref())?,
+    };
+    let targets = payload
+        .targets
+        .unwrap_or_else(|| vec![ModerationTarget::Comment, ModerationTarget::Post]);
+
+    let mut items = Vec::new();
+    for target in [ModerationTarget::Comment, ModerationTarget::Post] {
+        if targets.contains(&target) {
+            let ids = state.repo.find_moderation_targets(target, &filter).await?;
+            items.extend(ids.into_iter().map(|id| (target, id)));
+        }
+    }
+
+    let now = now_iso();
+    let mut results = Vec::with_capacity(items.len());
+    for (target, id) in items {
+        let outcome = moderate_item(&state, payload.action, target, id, &now).await;
+        results.push(ModerationItemResult::new(target, id, outcome));
+    }
+
+    let succeeded = results.iter().filter(|result| result.ok).count();
+    let detail = format!(
+        "关键词: {}, 时间: {} ~ {}, 成功 {succeeded}/{}",
+        filter.keyword.as_deref().unwrap_or("-"),
+        filter.since.as_deref().unwrap_or("-"),
+        filter.until.as_deref().unwrap_or("-"),
+        results.len()
+    );
+    state
+        .repo
+        .record_audit(&NewAuditEntry {
+            admin_id: admin.id,
+            action: AUDIT_BULK_MODERATION,
+            target_type: payload.action.as_str(),
+            target_id: payload.user.as_deref().unwrap_or_default(),
+            detail: Some(&detail),
+        })
+        .await?;
+
+    Ok(Json(BulkModerationResponse {
+        matched: results.len(),
+        succeeded,
+        results,
+    }))
+}
+
+async fn moderate_item(
+    state: &SharedState,
+    action: ModerationAction,
+    target: ModerationTarget,
+    id: i64,
+    now: &str,
+) -> ApiResult<()> {
+    if !state.repo.moderate(target, action, id, now).await? {
+        return Err(ApiError::NotFound);
+    }
+    Ok(())
+}
+
+async fn move_posts(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+    ApiJson(payload): ApiJson<MovePostsPayload>,
+) -> ApiResult<Json<BulkModerationResponse>> {
+    let admin = authenticate_admin(&state, &cookies).await?;
+    let category = find_category(&state, payload.category.trim())
+        .await?
+        .ok_or_else(|| {
+            ApiError::invalid_field(
+                "category",
+                "category.invalid",
+                "请选择有效的分区",
+                "Please choose a valid category",
+            )
+        })?;
+    if payload.post_ids.is_empty() || payload.post_ids.len() > MAX_BULK_ITEMS as usize {
+        return Err(ApiError::invalid_field(
+            "post_ids",
+            "moderation.move_count",
+            format!("一次需要移动1到{MAX_BULK_ITEMS}篇帖子"),
+            format!("Between 1 and {MAX_BULK_ITEMS} posts can be moved at once"),
+        ));
+    }
+
+    let mut results = Vec::with_capacity(payload.post_ids.len());
+    for post_id in payload.post_ids {
+        let outcome = state
+            .repo
+            .move_post(post_id, &category.slug)
+            .await
+            .map_err(ApiError::from)
+            .and_then(|moved| {
+                if moved {
+                    Ok(())
+                } else {
+                    Err(ApiError::NotFound)
+                }
+            });
+        results.push(ModerationItemResult::new(
+            ModerationTarget::Post,
+            post_id,
+            outcome,
+        ));
+    }
+
+    let succeeded = results.iter().filter(|result| result.ok).count();
+    state
+        .repo
+        .record_audit(&NewAuditEntry {
+            admin_id: admin.id,
+            action: AUDIT_MOVE_POSTS,
+            target_type: "category",
+            target_id: &category.slug,
+            detail: Some(&format!("成功 {succeeded}/{}", results.len())),
+        })
+        .await?;
+
+    Ok(Json(BulkModerationResponse {
+        matched: results.len(),
+        succeeded,
+        results,
+    }))
+}
+
+async fn admin_create_backup(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+) -> ApiResult<(StatusCode, Json<BackupResponse>)> {
+    let admin = authenticate_admin(&state, &cookies).await?;
+    let settings = state.config().backup.clone();
+    let backup = create_backup(state.repo.as_ref(), &settings).await?;
+
+    state
+        .repo
+        .record_audit(&NewAuditEntry {
+            admin_id: admin.id,
+            action: AUDIT_CREATE_BACKUP,
+            target_type: "backup",
+            target_id: &backup.file,
+            detail: None,
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(backup)))
+}
+
+async fn admin_list_backups(
+    State(state): State<SharedState>,
+    cookies: Cookies,
+) -> ApiResult<Json<Vec<BackupResponse>>> {
+    authenticate_admin(&state, &cookies).await?;
+    Ok(Json(list_backups(&state.config().backup.dir)?))
+}
+
+async fn run_scheduled_backups(state: SharedState) {
+    let mut ticker = tokio::time::interval(StdDuration::from_secs(BACKUP_CHECK_INTERVAL_SECS));
+    let mut shutdown = state.shutdown.subscribe();
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown_requested(&mut shutdown) => break,
+        }
+        let settings = state.config().backup.clone();
+        if settings.interval_hours == 0 {
+            continue;
+        }
+        let latest = match list_backups(&settings.dir) {
+            Ok(backups) => backups.first().map(|backup| backup.modified),
+            Err(err) => {
+                warn!("读取备份目录失败: {err}");
+                continue;
+            }
+        };
+        let interval = StdDuration::from_secs(settings.interval_hours * 3600);
+        let due = match latest {
+            None => true,
+            Some(modified) => modified.elapsed().is_ok_and(|age| age >= interval),
+        };
+        if !due {
+            continue;
+        }
+        match create_backup(state.repo.as_ref(), &settings).await {
+            Ok(backup) => info!("已创建定时备份 {}", backup.file),
+            Err(err) => warn!("定时备份失败: {err}"),
+        }
+    }
+}
+
+async fn create_backup(
+    repo: &dyn Repository,
+    settings: &BackupSection,
+) -> ApiResult<BackupResponse> {
+    fs::create_dir_all(&settings.dir).map_err(backup_io_error)?;
+    let now = OffsetDateTime::now_utc();
+    let name = format!(
+        "{BACKUP_FILE_PREFIX}{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}{BACKUP_FILE_SUFFIX}",
+        now.year(),
+        u8::from(now.month()),
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second(),
+        now.millisecond()
+    );
+    let target = settings.dir.join(&name);
+    let partial = settings.dir.join(format!("{name}.partial"));
+
+    repo.backup(&partial).await?;
+    if let Err(err) = verify_backup(&partial).await {
+        let _ = fs::remove_file(&partial);
+        return Err(err);
+    }
+    fs::rename(&partial, &target).map_err(backup_io_error)?;
+    prune_backups(&settings.dir, settings.keep)?;
+
+    let meta = fs::metadata(&target).map_err(backup_io_error)?;
+    BackupResponse::from_file(name, &meta)
+}
+
+async fn verify_backup(path: &FsPath) -> ApiResult<()> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await?;
+    let report: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(&mut conn)
+        .await?;
+    conn.close().await?;
+    if report != ["ok"] {
+        return Err(ApiError::Internal(format!(
+            "备份 {} 完整性检查失败: {}",
+            path.display(),
+            report.join("; ")
+        )));
+    }
+    Ok(())
+}
+
+fn list_backups(dir: &FsPath) -> ApiResult<Vec<BackupResponse>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(backup_io_error(err)),
+    };
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(backup_io_error)?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with(BACKUP_FILE_PREFIX) || !name.ends_with(BACKUP_FILE_SUFFIX) {
+            continue;
+        }
+        let meta = entry.metadata().map_err(backup_io_error)?;
+        backups.push(BackupResponse::from_file(name, &meta)?);
+    }
+    backups.sort_by(|a, b| b.file.cmp(&a.file));
+    Ok(backups)
+}
+
+fn prune_backups(dir: &FsPath, keep: usize) -> ApiResult<()> {
+    if keep == 0 {
+        return Ok(());
+    }
+    for backup in list_backups(dir)?.into_iter().skip(keep) {
+        fs::remove_file(dir.join(&backup.file)).map_err(backup_io_error)?;
+        info!("已删除过期备份 {}", backup.file);
+    }
+    Ok(())
+}
+
+fn resolve_backup_file(settings: &BackupSection, file: &FsPath) -> ApiResult<PathBuf> {
+    if file.exists() {
+        return Ok(file.to_path_buf());
+    }
+    let candidate = settings.dir.join(file);
+    if candidate.exists() {
+        return Ok(candidate);
+    }
+    Err(ApiError::NotFound)
+}
+
+fn backup_io_error(err: std::io::Error) -> ApiError {
+    ApiError::Internal(format!("备份文件操作失败: {err}"))
+}
+
+fn normalize_filter_time(raw: Option<&str>) -> ApiResult<Option<String>> {
+    match raw.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(raw) => OffsetDateTime::parse(raw, &Rfc3339)
+            .map(|time| Some(format_time(time)))
+            .map_err(|_| {
+                ApiError::invalid(
+                    "moderation.time_range_invalid",
+                    "时间范围格式无效",
+                    "Invalid time range",
+                )
+            }),
+    }
+}
+
+fn hash_password(password: &str) -> ApiResult<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(ApiError::from)
+}
+
+fn verify_password(hash: &str, password: &str) -> ApiResult<bool> {
+    let parsed = PasswordHash::new(hash).map_err(ApiError::from)?;
+    match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+        Ok(_) => Ok(true),
+        Err(PasswordHashError::Password) => Ok(false),
+        Err(err) => Err(ApiError::from(err)),
+    }
+}
+
+async fn authenticate(state: &SharedState, cookies: &Cookies) -> ApiResult<AuthedUser> {
+    let Some(cookie) = cookies.get(SESSION_COOKIE) else {
+        return Err(ApiError::Unauthorized);
+    };
+    let token = cookie.value().to_string();
+
+    let session = state
+        .repo
+        .find_session(&token)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
 
     let expires = OffsetDateTime::parse(&session.expires_at, &Rfc3339)
         .map_err(|err| ApiError::Internal(format!("时间解析失败: {err}")))?;
     if expires <= OffsetDateTime::now_utc() {
-        sqlx::query("DELETE FROM sessions WHERE id = ?1")
-            .bind(&token)
-            .execute(&state.db)
-            .await?;
+        state.repo.delete_session(&token).await?;
         return Err(ApiError::Unauthorized);
     }
+    Span::current().record("uid", session.uid.as_str());
 
     Ok(AuthedUser {
         id: session.user_id,
         username: session.username,
         qq: session.qq,
         uid: session.uid,
+        deletion_scheduled_at: session.deletion_scheduled_at,
     })
 }
 
+async fn authenticate_admin(state: &SharedState, cookies: &Cookies) -> ApiResult<AuthedUser> {
+    let user = authenticate(state, cookies).await?;
+    if !state.config().is_admin(&user.uid) {
+        return Err(ApiError::Forbidden);
+    }
+    Ok(user)
+}
+
 fn now_iso() -> String {
-    OffsetDateTime::now_utc().format(&Rfc3339).unwrap()
+    format_time(OffsetDateTime::now_utc())
+}
+
+fn format_time(time: OffsetDateTime) -> String {
+    time.to_offset(time::UtcOffset::UTC)
+        .format(&Rfc3339)
+        .unwrap()
 }
 
-fn load_config() -> Result<AppConfig, Box<dyn std::error::Error>> {
-    let contents = fs::read_to_string("config.toml")?;
-    let config: AppConfig = toml::from_str(&contents)?;
+fn parse_env_value(raw: String, expected: Option<&toml::Value>) -> toml::Value {
+    let literal = format!("value = {raw}")
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"));
+    let quoted = raw.starts_with(['"', '\'']);
+    match (expected, literal) {
+        (Some(toml::Value::String(_)), Some(value @ toml::Value::String(_))) if quoted => value,
+        (Some(toml::Value::String(_)), _) => toml::Value::String(raw),
+        (Some(_), Some(value)) => value,
+        (None, Some(value)) if quoted || raw.starts_with(['[', '{']) => value,
+        _ => toml::Value::String(raw),
+    }
+}
+
+fn load_config(path: &FsPath, required: bool) -> Result<AppConfig, Box<dyn std::error::Error>> {
+    let mut table = match fs::read_to_string(path) {
+        Ok(contents) => contents
+            .parse::<toml::Table>()
+            .map_err(|err| format!("{} 解析失败: {err}", path.display()))?,
+        Err(err) if err.kind() == ErrorKind::NotFound && !required => {
+            warn!("未找到 {}，使用默认配置", path.display());
+            toml::Table::new()
+        }
+        Err(err) => return Err(format!("无法读取配置文件 {}: {err}", path.display()).into()),
+    };
+
+    for (name, keys, value) in config_env_overrides() {
+        let (last, parents) = keys.split_last().expect("override keys are never empty");
+        let mut current = &mut table;
+        for key in parents {
+            current = current
+                .entry(key.clone())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| ConfigError::new(&name, format!("{key} 不是配置小节")))?;
+        }
+        current.insert(last.clone(), value);
+    }
+
+    let config: AppConfig = serde_path_to_error::deserialize(toml::Value::Table(table))
+        .map_err(|err| ConfigError::new(err.path().to_string(), err.inner().message()))?;
+    config.validate()?;
     Ok(config)
 }
 
-async fn init_db(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-    sqlx::query("PRAGMA foreign_keys = ON;")
+fn config_env_overrides() -> Vec<(String, Vec<String>, toml::Value)> {
+    let defaults = AppConfig::deserialize(toml::Value::Table(toml::Table::new()))
+        .ok()
+        .and_then(|config| toml::Value::try_from(config).ok());
+    let mut overrides: Vec<_> = env::vars()
+        .filter_map(|(name, raw)| {
+            let keys: Vec<String> = name
+                .strip_prefix(CONFIG_ENV_PREFIX)?
+                .split("__")
+                .map(str::to_lowercase)
+                .collect();
+            if keys.iter().any(String::is_empty) {
+                return None;
+            }
+            let expected = defaults
+                .as_ref()
+                .and_then(|defaults| keys.iter().try_fold(defaults, |value, key| value.get(key)));
+            let value = parse_env_value(raw, expected);
+            Some((name, keys, value))
+        })
+        .collect();
+    overrides.sort_by(|a, b| a.0.cmp(&b.0));
+    overrides
+}
+
+async fn init_db(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            uid TEXT NOT NULL UNIQUE,
+            username TEXT NOT NULL UNIQUE,
+            qq TEXT NOT NULL,
+            password_hash TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+
+    ensure_column(
+        pool,
+        "users",
+        "qq_visibility",
+        "TEXT NOT NULL DEFAULT 'everyone'",
+    )
+    .await?;
+    ensure_column(
+        pool,
+        "users",
+        "profile_hidden",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+    .await?;
+    ensure_column(pool, "users", "posts_hidden", "INTEGER NOT NULL DEFAULT 0").await?;
+    ensure_column(pool, "users", "deletion_scheduled_at", "TEXT").await?;
+    ensure_column(pool, "users", "deletion_mode", "TEXT").await?;
+    ensure_column(pool, "users", "deleted_at", "TEXT").await?;
+    ensure_column(pool, "users", "banned_at", "TEXT").await?;
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS follows (
+            follower_id INTEGER NOT NULL,
+            followee_id INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY(follower_id, followee_id),
+            FOREIGN KEY(follower_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY(followee_id) REFERENCES users(id) ON DELETE CASCADE
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_follows_followee ON follows(followee_id);")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS sessions (
+            id TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS posts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER NOT NULL,
+            title TEXT NOT NULL,
+            content TEXT NOT NULL,
+            category TEXT NOT NULL DEFAULT '其它',
+            is_anonymous INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+
+    ensure_column(pool, "posts", "title", "TEXT NOT NULL DEFAULT ''").await?;
+    ensure_column(pool, "posts", "category", "TEXT NOT NULL DEFAULT '其它'").await?;
+    ensure_column(pool, "posts", "pin_scope", "TEXT").await?;
+    ensure_column(pool, "posts", "pinned_at", "TEXT").await?;
+    ensure_column(pool, "posts", "pin_expires_at", "TEXT").await?;
+    ensure_column(pool, "posts", "status", "TEXT NOT NULL DEFAULT 'published'").await?;
+    ensure_column(pool, "posts", "publish_at", "TEXT").await?;
+    ensure_column(pool, "posts", "updated_at", "TEXT").await?;
+    ensure_column(pool, "posts", "expires_at", "TEXT").await?;
+    ensure_column(pool, "posts", "archived_at", "TEXT").await?;
+    ensure_column(pool, "posts", "hidden_at", "TEXT").await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_posts_pinned ON posts(pin_scope) WHERE pin_scope IS NOT NULL;",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_posts_drafts ON posts(user_id, publish_at) WHERE status = 'draft';",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_posts_expiry ON posts(expires_at) WHERE archived_at IS NULL;",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_posts_archived ON posts(archived_at) WHERE archived_at IS NOT NULL;",
+    )
+    .execute(pool)
+    .await?;
+
+    let has_categories: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'categories')",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS categories (
+            slug TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            description TEXT NOT NULL DEFAULT '',
+            sort_order INTEGER NOT NULL DEFAULT 0,
+            archived INTEGER NOT NULL DEFAULT 0,
+            post_permission TEXT NOT NULL DEFAULT 'everyone',
+            created_at TEXT NOT NULL
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+    ensure_column(pool, "categories", "post_lifetime_days", "INTEGER").await?;
+
+    if !has_categories {
+        let now = now_iso();
+        for (index, name) in SEED_CATEGORIES.iter().enumerate() {
+            sqlx::query(
+                r#"INSERT OR IGNORE INTO categories (slug, name, sort_order, created_at)
+                   VALUES (?1, ?1, ?2, ?3)"#,
+            )
+            .bind(name)
+            .bind(index as i64)
+            .bind(&now)
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS comments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            post_id INTEGER NOT NULL,
+            user_id INTEGER NOT NULL,
+            content TEXT NOT NULL,
+            is_anonymous INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE,
+            FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+    ensure_column(pool, "comments", "hidden_at", "TEXT").await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_comments_user ON comments(user_id);")
+        .execute(pool)
+        .await?;
+
+    let added_counters =
+        ensure_column(pool, "posts", "comment_count", "INTEGER NOT NULL DEFAULT 0").await?;
+    ensure_column(pool, "posts", "last_comment_at", "TEXT").await?;
+    ensure_column(
+        pool,
+        "posts",
+        "last_activity_at",
+        "TEXT NOT NULL DEFAULT ''",
+    )
+    .await?;
+    ensure_column(pool, "posts", "hot_score", "REAL NOT NULL DEFAULT 0").await?;
+
+    if added_counters {
+        sqlx::query(
+            r#"UPDATE posts SET
+                   comment_count = (SELECT COUNT(*) FROM comments c WHERE c.post_id = posts.id),
+                   last_comment_at = (SELECT MAX(c.created_at) FROM comments c WHERE c.post_id = posts.id)"#,
+        )
+        .execute(pool)
+        .await?;
+        sqlx::query("UPDATE posts SET last_activity_at = COALESCE(last_comment_at, created_at)")
+            .execute(pool)
+            .await?;
+
+        let rows: Vec<(i64, String, i64)> =
+            sqlx::query_as("SELECT id, created_at, comment_count FROM posts")
+                .fetch_all(pool)
+                .await?;
+        for (id, created_at, comment_count) in rows {
+            sqlx::query("UPDATE posts SET hot_score = ?1 WHERE id = ?2")
+                .bind(hot_score(&created_at, comment_count))
+                .bind(id)
+                .execute(pool)
+                .await?;
+        }
+    }
+
+    for (name, column) in [
+        ("idx_posts_created", "created_at"),
+        ("idx_posts_activity", "last_activity_at"),
+        ("idx_posts_comments", "comment_count"),
+        ("idx_posts_hot", "hot_score"),
+    ] {
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {name} ON posts({column} DESC);"
+        ))
+        .execute(pool)
+        .await?;
+    }
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS bookmarks (
+            user_id INTEGER NOT NULL,
+            post_id INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY(user_id, post_id),
+            FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_bookmarks_post ON bookmarks(post_id);")
+        .execute(pool)
+        .await?;
+
+    let has_subscriptions: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'post_subscriptions')",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS post_subscriptions (
+            user_id INTEGER NOT NULL,
+            post_id INTEGER NOT NULL,
+            muted INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY(user_id, post_id),
+            FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+
+    if !has_subscriptions {
+        sqlx::query(
+            r#"INSERT OR IGNORE INTO post_subscriptions (user_id, post_id, muted, created_at)
+               SELECT user_id, id, 0, created_at FROM posts
+               UNION ALL
+               SELECT user_id, post_id, 0, created_at FROM comments"#,
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_post_subscriptions_post ON post_subscriptions(post_id);",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS notifications (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            post_id INTEGER NOT NULL,
+            comment_id INTEGER,
+            created_at TEXT NOT NULL,
+            read_at TEXT,
+            FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE,
+            FOREIGN KEY(comment_id) REFERENCES comments(id) ON DELETE CASCADE
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_notifications_user ON notifications(user_id, created_at);",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            banned INTEGER NOT NULL DEFAULT 0,
+            merged_into INTEGER,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY(merged_into) REFERENCES tags(id) ON DELETE SET NULL
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS post_tags (
+            post_id INTEGER NOT NULL,
+            tag_id INTEGER NOT NULL,
+            PRIMARY KEY(post_id, tag_id),
+            FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE,
+            FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_post_tags_tag ON post_tags(tag_id);")
         .execute(pool)
         .await?;
 
     sqlx::query(
-        r#"CREATE TABLE IF NOT EXISTS users (
+        r#"CREATE TABLE IF NOT EXISTS polls (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
-            uid TEXT NOT NULL UNIQUE,
-            username TEXT NOT NULL UNIQUE,
-            qq TEXT NOT NULL,
-            password_hash TEXT NOT NULL,
-            created_at TEXT NOT NULL
+            post_id INTEGER NOT NULL UNIQUE,
+            multiple INTEGER NOT NULL DEFAULT 0,
+            anonymous_votes INTEGER NOT NULL DEFAULT 1,
+            closes_at TEXT,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE
         );"#,
     )
     .execute(pool)
     .await?;
 
     sqlx::query(
-        r#"CREATE TABLE IF NOT EXISTS sessions (
-            id TEXT PRIMARY KEY,
+        r#"CREATE TABLE IF NOT EXISTS poll_options (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            poll_id INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            label TEXT NOT NULL,
+            FOREIGN KEY(poll_id) REFERENCES polls(id) ON DELETE CASCADE
+        );"#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS poll_votes (
+            poll_id INTEGER NOT NULL,
             user_id INTEGER NOT NULL,
             created_at TEXT NOT NULL,
-            expires_at TEXT NOT NULL,
+            PRIMARY KEY(poll_id, user_id),
+            FOREIGN KEY(poll_id) REFERENCES polls(id) ON DELETE CASCADE,
             FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
         );"#,
     )
@@ -828,130 +8263,663 @@ async fn init_db(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     .await?;
 
     sqlx::query(
-        r#"CREATE TABLE IF NOT EXISTS posts (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
+        r#"CREATE TABLE IF NOT EXISTS poll_vote_choices (
+            poll_id INTEGER NOT NULL,
             user_id INTEGER NOT NULL,
-            title TEXT NOT NULL,
-            content TEXT NOT NULL,
-            category TEXT NOT NULL DEFAULT '其它',
-            is_anonymous INTEGER NOT NULL,
-            created_at TEXT NOT NULL,
-            FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+            option_id INTEGER NOT NULL,
+            PRIMARY KEY(poll_id, user_id, option_id),
+            FOREIGN KEY(poll_id, user_id) REFERENCES poll_votes(poll_id, user_id) ON DELETE CASCADE,
+            FOREIGN KEY(option_id) REFERENCES poll_options(id) ON DELETE CASCADE
         );"#,
     )
     .execute(pool)
     .await?;
 
-    ensure_column(pool, "posts", "title", "TEXT NOT NULL DEFAULT ''").await?;
-    ensure_column(pool, "posts", "category", "TEXT NOT NULL DEFAULT '其它'").await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_poll_vote_choices_option ON poll_vote_choices(option_id);",
+    )
+    .execute(pool)
+    .await?;
 
     sqlx::query(
-        r#"CREATE TABLE IF NOT EXISTS comments (
+        r#"CREATE TABLE IF NOT EXISTS admin_audit_log (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
-            post_id INTEGER NOT NULL,
-            user_id INTEGER NOT NULL,
-            content TEXT NOT NULL,
-            is_anonymous INTEGER NOT NULL,
-            created_at TEXT NOT NULL,
-            FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE,
-            FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+            admin_id INTEGER NOT NULL,
+            action TEXT NOT NULL,
+            target_type TEXT NOT NULL,
+            target_id TEXT NOT NULL,
+            detail TEXT,
+            created_at TEXT NOT NULL
         );"#,
     )
     .execute(pool)
     .await?;
 
-    Ok(())
+    Ok(())
+}
+
+fn is_unique_violation(err: &sqlx::Error) -> bool {
+    matches!(err, sqlx::Error::Database(db_err) if db_err.is_unique_violation())
+}
+#[derive(Deserialize)]
+struct RegisterPayload {
+    username: String,
+    qq: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct LoginPayload {
+    username: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct CreatePostPayload {
+    title: String,
+    content: String,
+    category: Option<String>,
+    anonymous: Option<bool>,
+    poll: Option<CreatePollPayload>,
+    tags: Option<Vec<String>>,
+    publish_at: Option<String>,
+    expires_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SaveDraftPayload {
+    title: Option<String>,
+    content: Option<String>,
+    category: Option<String>,
+    anonymous: Option<bool>,
+    tags: Option<Vec<String>>,
+    publish_at: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+enum PostStatus {
+    Draft,
+    Published,
+}
+
+#[derive(Clone, Copy)]
+enum PostSort {
+    New,
+    Active,
+    Comments,
+    Hot,
+}
+
+impl PostSort {
+    fn order_by(self) -> &'static str {
+        match self {
+            PostSort::New => "p.created_at DESC, p.id DESC",
+            PostSort::Active => "p.last_activity_at DESC, p.id DESC",
+            PostSort::Comments => "p.comment_count DESC, p.created_at DESC, p.id DESC",
+            PostSort::Hot => "p.hot_score DESC, p.id DESC",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PinPostPayload {
+    scope: PinScope,
+    expires_at: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+enum PinScope {
+    Global,
+    Category,
+}
+
+#[derive(Deserialize)]
+struct TrendingTagsQuery {
+    days: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct CreateTagPayload {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct UpdateTagPayload {
+    banned: bool,
+}
+
+#[derive(Deserialize)]
+struct MergeTagPayload {
+    into: String,
+}
+
+#[derive(Deserialize)]
+struct CreatePollPayload {
+    options: Vec<String>,
+    multiple: Option<bool>,
+    closes_at: Option<String>,
+    anonymous_votes: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct VotePollPayload {
+    option_ids: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+struct CreateCategoryPayload {
+    slug: String,
+    name: Option<String>,
+    description: Option<String>,
+    sort_order: Option<i64>,
+    post_permission: Option<CategoryPermission>,
+    post_lifetime_days: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct UpdateCategoryPayload {
+    name: Option<String>,
+    description: Option<String>,
+    sort_order: Option<i64>,
+    archived: Option<bool>,
+    post_permission: Option<CategoryPermission>,
+    post_lifetime_days: Option<i64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+enum CategoryPermission {
+    Everyone,
+    Admins,
+}
+
+#[derive(Deserialize)]
+struct CreateCommentPayload {
+    content: String,
+    anonymous: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct PostListQuery {
+    category: Option<String>,
+    feed: Option<String>,
+    tag: Option<String>,
+    sort: Option<String>,
+    page: Option<i64>,
+    page_size: Option<i64>,
+    full: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct ArchiveQuery {
+    category: Option<String>,
+    page: Option<i64>,
+    page_size: Option<i64>,
+    full: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    page: Option<i64>,
+    page_size: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct ContentQuery {
+    full: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct UpdateSubscriptionPayload {
+    muted: bool,
+}
+
+#[derive(Deserialize)]
+struct UpdateProfilePayload {
+    username: Option<String>,
+    qq: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChangePasswordPayload {
+    current_password: String,
+    new_password: String,
+}
+
+#[derive(Deserialize)]
+struct AdminUserQuery {
+    q: Option<String>,
+    page: Option<i64>,
+    page_size: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct BulkModerationPayload {
+    action: ModerationAction,
+    targets: Option<Vec<ModerationTarget>>,
+    user: Option<String>,
+    keyword: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MovePostsPayload {
+    post_ids: Vec<i64>,
+    category: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ModerationAction {
+    Delete,
+    Hide,
+    Unhide,
+}
+
+impl ModerationAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            ModerationAction::Delete => "delete",
+            ModerationAction::Hide => "hide",
+            ModerationAction::Unhide => "unhide",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ModerationTarget {
+    Post,
+    Comment,
+}
+
+impl ModerationTarget {
+    fn as_str(self) -> &'static str {
+        match self {
+            ModerationTarget::Post => "post",
+            ModerationTarget::Comment => "comment",
+        }
+    }
+}
+
+struct ModerationFilter {
+    user_id: Option<i64>,
+    keyword: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+}
+
+struct NewAuditEntry<'a> {
+    admin_id: i64,
+    action: &'a str,
+    target_type: &'a str,
+    target_id: &'a str,
+    detail: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct AuditReasonQuery {
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DeleteAccountPayload {
+    password: String,
+    mode: Option<DeletionMode>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+enum DeletionMode {
+    Anonymize,
+    Delete,
+}
+
+#[derive(Deserialize)]
+struct UpdatePrivacyPayload {
+    qq_visibility: Option<QqVisibility>,
+    profile_hidden: Option<bool>,
+    posts_hidden: Option<bool>,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+enum QqVisibility {
+    Everyone,
+    Following,
+    Nobody,
+}
+
+#[derive(Serialize)]
+struct UserResponse {
+    id: i64,
+    username: String,
+    qq: String,
+    uid: String,
+    is_admin: bool,
+    deletion_scheduled_at: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UserProfileResponse {
+    username: String,
+    qq: Option<String>,
+    uid: String,
+    joined_at: String,
+    follower_count: i64,
+    following_count: i64,
+    is_following: bool,
+    posts_hidden: bool,
+    posts: Vec<PostSummary>,
+}
+
+#[derive(Serialize)]
+struct TagCountResponse {
+    name: String,
+    post_count: i64,
+}
+
+#[derive(Serialize)]
+struct CategoryResponse {
+    slug: String,
+    name: String,
+    description: String,
+    sort_order: i64,
+    archived: bool,
+    post_permission: CategoryPermission,
+    post_lifetime_days: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct SubscriptionResponse {
+    subscribed: bool,
+    muted: bool,
+}
+
+#[derive(Serialize)]
+struct NotificationResponse {
+    id: i64,
+    kind: String,
+    post_id: i64,
+    post_title: String,
+    comment_id: Option<i64>,
+    excerpt: Option<String>,
+    created_at: String,
+    read: bool,
+}
+
+#[derive(Serialize)]
+struct PrivacySettingsResponse {
+    qq_visibility: QqVisibility,
+    profile_hidden: bool,
+    posts_hidden: bool,
+}
+
+#[derive(Serialize)]
+struct PostSummary {
+    id: i64,
+    title: String,
+    content: Option<String>,
+    excerpt: Option<String>,
+    category: String,
+    created_at: String,
+    anonymous: bool,
+    author: Option<AuthorInfo>,
+    tags: Vec<String>,
+    comment_count: i64,
+    last_comment_at: Option<String>,
+    last_activity_at: String,
+    expires_at: Option<String>,
+    archived: bool,
+    hidden: bool,
+    pinned: bool,
+    bookmarked: bool,
+}
+
+#[derive(Serialize)]
+struct PostDetailResponse {
+    id: i64,
+    title: String,
+    content: String,
+    category: String,
+    created_at: String,
+    anonymous: bool,
+    author: Option<AuthorInfo>,
+    tags: Vec<String>,
+    expires_at: Option<String>,
+    archived: bool,
+    hidden: bool,
+    pinned: bool,
+    bookmarked: bool,
+    subscription: SubscriptionResponse,
+    poll: Option<PollResponse>,
+    comments: Vec<CommentResponse>,
+}
+
+#[derive(Serialize)]
+struct AdminUserResponse {
+    uid: String,
+    username: String,
+    qq: String,
+    created_at: String,
+    is_admin: bool,
+    banned: bool,
+    deleted: bool,
+    deletion_scheduled_at: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AdminUserDetailResponse {
+    user: AdminUserResponse,
+    post_count: i64,
+    anonymous_post_count: i64,
+    comment_count: i64,
+    session_count: i64,
+    last_login_at: Option<String>,
 }
 
-fn is_unique_violation(err: &sqlx::Error) -> bool {
-    if let sqlx::Error::Database(db_err) = err {
-        return db_err.message().contains("UNIQUE");
+#[derive(Serialize)]
+struct AuthorAttributionResponse {
+    author: AdminUserResponse,
+}
+
+#[derive(Serialize)]
+struct BulkModerationResponse {
+    matched: usize,
+    succeeded: usize,
+    results: Vec<ModerationItemResult>,
+}
+
+#[derive(Serialize)]
+struct ModerationItemResult {
+    target: ModerationTarget,
+    id: i64,
+    ok: bool,
+    error: Option<String>,
+}
+
+impl ModerationItemResult {
+    fn new(target: ModerationTarget, id: i64, outcome: ApiResult<()>) -> Self {
+        Self {
+            target,
+            id,
+            ok: outcome.is_ok(),
+            error: outcome.err().map(|err| err.to_string()),
+        }
     }
-    false
 }
-#[derive(Deserialize)]
-struct RegisterPayload {
-    username: String,
-    qq: String,
-    password: String,
+
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
 }
 
-#[derive(Deserialize)]
-struct LoginPayload {
-    username: String,
-    password: String,
+#[derive(Serialize)]
+struct ReadinessResponse {
+    status: &'static str,
+    checks: Vec<ReadinessCheck>,
 }
 
-#[derive(Deserialize)]
-struct CreatePostPayload {
-    title: String,
-    content: String,
-    category: Option<String>,
-    anonymous: Option<bool>,
+#[derive(Serialize)]
+struct ReadinessCheck {
+    name: &'static str,
+    ok: bool,
+    error: Option<String>,
 }
 
-#[derive(Deserialize)]
-struct CreateCommentPayload {
-    content: String,
-    anonymous: Option<bool>,
+impl ReadinessCheck {
+    fn new(name: &'static str, outcome: Result<(), String>) -> Self {
+        Self {
+            name,
+            ok: outcome.is_ok(),
+            error: outcome.err(),
+        }
+    }
 }
 
-#[derive(Deserialize)]
-struct PostListQuery {
-    category: Option<String>,
+#[derive(Serialize)]
+struct BackupResponse {
+    file: String,
+    size_bytes: u64,
+    created_at: String,
+    #[serde(skip)]
+    modified: SystemTime,
 }
 
-#[derive(Deserialize)]
-struct UpdateProfilePayload {
-    username: Option<String>,
-    qq: Option<String>,
+impl BackupResponse {
+    fn from_file(file: String, meta: &fs::Metadata) -> ApiResult<Self> {
+        let modified = meta.modified().map_err(backup_io_error)?;
+        Ok(Self {
+            file,
+            size_bytes: meta.len(),
+            created_at: format_time(OffsetDateTime::from(modified)),
+            modified,
+        })
+    }
 }
 
-#[derive(Deserialize)]
-struct ChangePasswordPayload {
-    current_password: String,
-    new_password: String,
+#[derive(Serialize)]
+struct PasswordResetResponse {
+    temporary_password: String,
 }
 
 #[derive(Serialize)]
-struct UserResponse {
+struct AuditEntryResponse {
     id: i64,
-    username: String,
-    qq: String,
-    uid: String,
-    is_admin: bool,
+    admin_uid: Option<String>,
+    admin_username: Option<String>,
+    action: String,
+    target_type: String,
+    target_id: String,
+    detail: Option<String>,
+    created_at: String,
 }
 
 #[derive(Serialize)]
-struct UserProfileResponse {
+struct AccountDeletionResponse {
+    message: String,
+    mode: DeletionMode,
+    scheduled_at: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AccountExport {
+    exported_at: String,
+    profile: ExportProfile,
+    posts: Vec<ExportPost>,
+    comments: Vec<ExportComment>,
+    sessions: Vec<ExportSession>,
+}
+
+#[derive(Serialize, FromRow)]
+struct ExportProfile {
+    uid: String,
     username: String,
     qq: String,
-    uid: String,
-    joined_at: String,
-    posts: Vec<PostSummary>,
+    qq_visibility: QqVisibility,
+    profile_hidden: bool,
+    posts_hidden: bool,
+    created_at: String,
+    deletion_scheduled_at: Option<String>,
 }
 
 #[derive(Serialize)]
-struct PostSummary {
+struct ExportPost {
     id: i64,
     title: String,
     content: String,
     category: String,
+    anonymous: bool,
+    draft: bool,
+    tags: Vec<String>,
     created_at: String,
+    expires_at: Option<String>,
+    archived_at: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+struct ExportComment {
+    id: i64,
+    post_id: i64,
+    post_title: Option<String>,
+    content: String,
     anonymous: bool,
-    author: Option<AuthorInfo>,
+    created_at: String,
+}
+
+#[derive(Serialize, FromRow)]
+struct ExportSession {
+    created_at: String,
+    expires_at: String,
+    current: bool,
 }
 
 #[derive(Serialize)]
-struct PostDetailResponse {
+struct DraftResponse {
     id: i64,
     title: String,
     content: String,
     category: String,
-    created_at: String,
     anonymous: bool,
-    author: Option<AuthorInfo>,
-    comments: Vec<CommentResponse>,
+    tags: Vec<String>,
+    publish_at: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
+
+#[derive(Serialize)]
+struct PollResponse {
+    id: i64,
+    multiple: bool,
+    anonymous_votes: bool,
+    closes_at: Option<String>,
+    closed: bool,
+    voted: bool,
+    my_choices: Vec<i64>,
+    total_voters: Option<i64>,
+    options: Vec<PollOptionResponse>,
+}
+
+#[derive(Serialize)]
+struct PollOptionResponse {
+    id: i64,
+    label: String,
+    votes: Option<i64>,
+    voters: Option<Vec<PollVoterInfo>>,
+}
+
+#[derive(Serialize)]
+struct PollVoterInfo {
+    username: String,
+    uid: String,
 }
 
 #[derive(Serialize)]
@@ -966,15 +8934,94 @@ struct CommentResponse {
 #[derive(Serialize)]
 struct AuthorInfo {
     username: String,
-    qq: String,
+    qq: Option<String>,
     uid: String,
 }
 
+struct NewPost {
+    user_id: i64,
+    title: String,
+    content: String,
+    category: String,
+    anonymous: bool,
+    status: PostStatus,
+    publish_at: Option<String>,
+    expires_at: Option<String>,
+    tags: Vec<String>,
+    poll: Option<NewPoll>,
+}
+
+struct PostFeed {
+    viewer_id: i64,
+    category: Option<String>,
+    following_only: bool,
+    tag: Option<String>,
+    sort: PostSort,
+    full: bool,
+    limit: i64,
+    offset: i64,
+}
+
+struct NewPoll {
+    options: Vec<String>,
+    multiple: bool,
+    anonymous_votes: bool,
+    closes_at: Option<String>,
+}
+
+struct NewDraft {
+    title: String,
+    content: String,
+    category: String,
+    anonymous: bool,
+    tags: Vec<String>,
+    publish_at: Option<String>,
+}
+
 struct AuthedUser {
     id: i64,
     username: String,
     qq: String,
     uid: String,
+    deletion_scheduled_at: Option<String>,
+}
+
+struct Viewer {
+    id: i64,
+}
+
+impl Viewer {
+    fn can_see_qq(&self, author_id: i64, visibility: QqVisibility, follows_viewer: bool) -> bool {
+        if author_id == self.id {
+            return true;
+        }
+        match visibility {
+            QqVisibility::Everyone => true,
+            QqVisibility::Following => follows_viewer,
+            QqVisibility::Nobody => false,
+        }
+    }
+
+    fn author_info(
+        &self,
+        author_id: i64,
+        username: Option<String>,
+        qq: Option<String>,
+        uid: Option<String>,
+        visibility: Option<QqVisibility>,
+        follows_viewer: bool,
+    ) -> Option<AuthorInfo> {
+        match (username, qq, uid, visibility) {
+            (Some(username), Some(qq), Some(uid), Some(visibility)) => Some(AuthorInfo {
+                username,
+                qq: self
+                    .can_see_qq(author_id, visibility, follows_viewer)
+                    .then_some(qq),
+                uid,
+            }),
+            _ => None,
+        }
+    }
 }
 
 #[derive(FromRow)]
@@ -984,6 +9031,8 @@ struct DbUser {
     username: String,
     qq: String,
     password_hash: String,
+    deletion_scheduled_at: Option<String>,
+    banned_at: Option<String>,
 }
 
 #[derive(FromRow)]
@@ -998,82 +9047,371 @@ struct DbPost {
     username: Option<String>,
     qq: Option<String>,
     uid: Option<String>,
+    qq_visibility: Option<QqVisibility>,
+    pin_scope: Option<PinScope>,
+    comment_count: i64,
+    last_comment_at: Option<String>,
+    last_activity_at: String,
+    expires_at: Option<String>,
+    archived: bool,
+    hidden: bool,
+    pinned: bool,
+    bookmarked: bool,
+    follows_viewer: bool,
+    tags: Option<String>,
+}
+
+#[derive(FromRow)]
+struct DbAdminUser {
+    id: i64,
+    uid: String,
+    username: String,
+    qq: String,
+    created_at: String,
+    deletion_scheduled_at: Option<String>,
+    deleted_at: Option<String>,
+    banned_at: Option<String>,
+}
+
+#[derive(FromRow)]
+struct DbAdminUserStats {
+    post_count: i64,
+    anonymous_post_count: i64,
+    comment_count: i64,
+    session_count: i64,
+    last_login_at: Option<String>,
+}
+
+#[derive(FromRow)]
+struct DbAuditEntry {
+    id: i64,
+    admin_uid: Option<String>,
+    admin_username: Option<String>,
+    action: String,
+    target_type: String,
+    target_id: String,
+    detail: Option<String>,
+    created_at: String,
+}
+
+#[derive(FromRow)]
+struct DbExportPost {
+    id: i64,
+    title: String,
+    content: String,
+    category: String,
+    is_anonymous: bool,
+    status: PostStatus,
+    created_at: String,
+    expires_at: Option<String>,
+    archived_at: Option<String>,
+    tags: Option<String>,
+}
+
+#[derive(FromRow)]
+struct DbDraft {
+    id: i64,
+    title: String,
+    content: String,
+    category: String,
+    is_anonymous: bool,
+    publish_at: Option<String>,
+    created_at: String,
+    updated_at: String,
+    tags: Option<String>,
 }
 
 #[derive(FromRow)]
 struct DbComment {
     id: i64,
+    user_id: i64,
     content: String,
     is_anonymous: bool,
     created_at: String,
     username: Option<String>,
     qq: Option<String>,
     uid: Option<String>,
+    qq_visibility: Option<QqVisibility>,
+    follows_viewer: bool,
+}
+
+#[derive(FromRow)]
+struct DbPublicUser {
+    id: i64,
+    uid: String,
+    username: String,
+    qq: String,
+    created_at: String,
+    qq_visibility: QqVisibility,
+    profile_hidden: bool,
+    posts_hidden: bool,
+}
+
+#[derive(FromRow)]
+struct DbFollowStats {
+    follower_count: i64,
+    following_count: i64,
+    is_following: bool,
+    follows_viewer: bool,
+}
+
+#[derive(FromRow)]
+struct DbTag {
+    id: i64,
+    name: String,
+    banned: bool,
+    merged_into: Option<i64>,
+}
+
+#[derive(FromRow)]
+struct DbTagCount {
+    name: String,
+    post_count: i64,
+}
+
+#[derive(FromRow)]
+struct DbCategory {
+    slug: String,
+    name: String,
+    description: String,
+    sort_order: i64,
+    archived: bool,
+    post_permission: CategoryPermission,
+    post_lifetime_days: Option<i64>,
+}
+
+#[derive(FromRow)]
+struct DbPoll {
+    id: i64,
+    multiple: bool,
+    anonymous_votes: bool,
+    closes_at: Option<String>,
+}
+
+#[derive(FromRow)]
+struct DbPollOption {
+    id: i64,
+    label: String,
+    votes: i64,
+}
+
+#[derive(FromRow)]
+struct DbPollVoter {
+    option_id: i64,
+    username: String,
+    uid: String,
+}
+
+#[derive(FromRow)]
+struct DbNotification {
+    id: i64,
+    kind: String,
+    post_id: i64,
+    comment_id: Option<i64>,
+    created_at: String,
+    read_at: Option<String>,
+    post_title: String,
+    comment_content: Option<String>,
+}
+
+#[derive(FromRow)]
+struct DbPrivacy {
+    qq_visibility: QqVisibility,
+    profile_hidden: bool,
+    posts_hidden: bool,
+}
+
+#[derive(FromRow)]
+struct DbSession {
+    user_id: i64,
+    expires_at: String,
+    username: String,
+    qq: String,
+    uid: String,
+    deletion_scheduled_at: Option<String>,
+}
+
+impl From<DbPrivacy> for PrivacySettingsResponse {
+    fn from(value: DbPrivacy) -> Self {
+        Self {
+            qq_visibility: value.qq_visibility,
+            profile_hidden: value.profile_hidden,
+            posts_hidden: value.posts_hidden,
+        }
+    }
+}
+
+impl From<DbCategory> for CategoryResponse {
+    fn from(value: DbCategory) -> Self {
+        Self {
+            slug: value.slug,
+            name: value.name,
+            description: value.description,
+            sort_order: value.sort_order,
+            archived: value.archived,
+            post_permission: value.post_permission,
+            post_lifetime_days: value.post_lifetime_days,
+        }
+    }
+}
+
+impl AdminUserResponse {
+    fn from_db(value: DbAdminUser, config: &AppConfig) -> Self {
+        Self {
+            is_admin: config.is_admin(&value.uid),
+            uid: value.uid,
+            username: value.username,
+            qq: value.qq,
+            created_at: value.created_at,
+            banned: value.banned_at.is_some(),
+            deleted: value.deleted_at.is_some(),
+            deletion_scheduled_at: value.deletion_scheduled_at,
+        }
+    }
+}
+
+impl From<DbAuditEntry> for AuditEntryResponse {
+    fn from(value: DbAuditEntry) -> Self {
+        Self {
+            id: value.id,
+            admin_uid: value.admin_uid,
+            admin_username: value.admin_username,
+            action: value.action,
+            target_type: value.target_type,
+            target_id: value.target_id,
+            detail: value.detail,
+            created_at: value.created_at,
+        }
+    }
+}
+
+impl From<DbExportPost> for ExportPost {
+    fn from(value: DbExportPost) -> Self {
+        Self {
+            id: value.id,
+            title: value.title,
+            content: value.content,
+            category: value.category,
+            anonymous: value.is_anonymous,
+            draft: value.status == PostStatus::Draft,
+            tags: split_tags(value.tags),
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            archived_at: value.archived_at,
+        }
+    }
 }
 
-#[derive(FromRow)]
-struct DbPublicUser {
-    id: i64,
-    uid: String,
-    username: String,
-    qq: String,
-    created_at: String,
+impl From<DbDraft> for DraftResponse {
+    fn from(value: DbDraft) -> Self {
+        Self {
+            id: value.id,
+            title: value.title,
+            content: value.content,
+            category: value.category,
+            anonymous: value.is_anonymous,
+            tags: split_tags(value.tags),
+            publish_at: value.publish_at,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+        }
+    }
 }
 
-#[derive(FromRow)]
-struct DbSession {
-    user_id: i64,
-    expires_at: String,
-    username: String,
-    qq: String,
-    uid: String,
+impl From<DbNotification> for NotificationResponse {
+    fn from(value: DbNotification) -> Self {
+        Self {
+            id: value.id,
+            kind: value.kind,
+            post_id: value.post_id,
+            post_title: value.post_title,
+            comment_id: value.comment_id,
+            excerpt: value
+                .comment_content
+                .map(|content| excerpt(&content, NOTIFICATION_EXCERPT_CHARS)),
+            created_at: value.created_at,
+            read: value.read_at.is_some(),
+        }
+    }
 }
 
-impl From<DbPost> for PostSummary {
-    fn from(value: DbPost) -> Self {
+impl PostSummary {
+    fn from_db(value: DbPost, viewer: &Viewer, full: bool) -> Self {
         let author = if !value.is_anonymous {
-            match (value.username.clone(), value.qq.clone(), value.uid.clone()) {
-                (Some(username), Some(qq), Some(uid)) => Some(AuthorInfo { username, qq, uid }),
-                _ => None,
-            }
+            viewer.author_info(
+                value.user_id,
+                value.username,
+                value.qq,
+                value.uid,
+                value.qq_visibility,
+                value.follows_viewer,
+            )
         } else {
             None
         };
         Self {
             id: value.id,
             title: value.title,
-            content: value.content,
+            excerpt: (!full).then(|| excerpt(&value.content, POST_EXCERPT_CHARS)),
+            content: full.then_some(value.content),
             category: value.category,
             created_at: value.created_at,
             anonymous: value.is_anonymous,
             author,
+            tags: split_tags(value.tags),
+            comment_count: value.comment_count,
+            last_comment_at: value.last_comment_at,
+            last_activity_at: value.last_activity_at,
+            expires_at: value.expires_at,
+            archived: value.archived,
+            hidden: value.hidden,
+            pinned: value.pinned,
+            bookmarked: value.bookmarked,
         }
     }
 }
 
 impl PostDetailResponse {
-    fn from_parts(post: DbPost, comments: Vec<CommentResponse>) -> Self {
-        let summary: PostSummary = post.into();
+    fn from_parts(
+        post: DbPost,
+        comments: Vec<CommentResponse>,
+        subscription: SubscriptionResponse,
+        poll: Option<PollResponse>,
+        viewer: &Viewer,
+    ) -> Self {
+        let summary = PostSummary::from_db(post, viewer, true);
         Self {
             id: summary.id,
             title: summary.title,
-            content: summary.content,
+            content: summary.content.unwrap_or_default(),
             category: summary.category,
             created_at: summary.created_at,
             anonymous: summary.anonymous,
             author: summary.author,
+            tags: summary.tags,
+            expires_at: summary.expires_at,
+            archived: summary.archived,
+            hidden: summary.hidden,
+            pinned: summary.pinned,
+            bookmarked: summary.bookmarked,
+            subscription,
+            poll,
             comments,
         }
     }
 }
 
-impl From<DbComment> for CommentResponse {
-    fn from(value: DbComment) -> Self {
+impl CommentResponse {
+    fn from_db(value: DbComment, viewer: &Viewer) -> Self {
         let author = if !value.is_anonymous {
-            match (value.username.clone(), value.qq.clone(), value.uid.clone()) {
-                (Some(username), Some(qq), Some(uid)) => Some(AuthorInfo { username, qq, uid }),
-                _ => None,
-            }
+            viewer.author_info(
+                value.user_id,
+                value.username,
+                value.qq,
+                value.uid,
+                value.qq_visibility,
+                value.follows_viewer,
+            )
         } else {
             None
         };
@@ -1086,3 +9424,1383 @@ impl From<DbComment> for CommentResponse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    const TEST_POSTGRES_URL: &str = "TALK_WALL_TEST_POSTGRES_URL";
+
+    struct TestDatabase {
+        repo: Arc<dyn Repository>,
+        cleanup: Cleanup,
+    }
+
+    enum Cleanup {
+        File(PathBuf),
+        Schema(PgPool, String),
+    }
+
+    impl TestDatabase {
+        async fn sqlite() -> Self {
+            let path = std::env::temp_dir().join(format!("talk-wall-test-{}.db", Uuid::new_v4()));
+            let database = DatabaseSection {
+                path: path.clone(),
+                ..DatabaseSection::default()
+            };
+            let repo = SqliteRepository::connect(&database)
+                .await
+                .expect("open sqlite repository");
+            Self {
+                repo: Arc::new(repo),
+                cleanup: Cleanup::File(path),
+            }
+        }
+
+        async fn postgres() -> Self {
+            let (url, admin, schema) = postgres_schema().await;
+            let options = PgConnectOptions::from_str(&url).expect("parse postgres url");
+            let repo = PostgresRepository::open(options.options([("search_path", &schema)]), 2)
+                .await
+                .expect("open postgres repository");
+            repo.init_schema().await.expect("create postgres schema");
+            Self {
+                repo: Arc::new(repo),
+                cleanup: Cleanup::Schema(admin, schema),
+            }
+        }
+
+        async fn finish(self) {
+            self.repo.close().await;
+            match self.cleanup {
+                Cleanup::File(path) => {
+                    for suffix in ["", "-wal", "-shm"] {
+                        let mut file = path.clone().into_os_string();
+                        file.push(suffix);
+                        let _ = fs::remove_file(file);
+                    }
+                }
+                Cleanup::Schema(admin, schema) => {
+                    sqlx::query(&format!("DROP SCHEMA {schema} CASCADE"))
+                        .execute(&admin)
+                        .await
+                        .expect("drop test schema");
+                    admin.close().await;
+                }
+            }
+        }
+    }
+
+    fn default_config() -> AppConfig {
+        AppConfig::deserialize(toml::Value::Table(toml::Table::new())).expect("default config")
+    }
+
+    fn test_state(repo: Arc<dyn Repository>, config: AppConfig) -> SharedState {
+        Arc::new(AppState {
+            repo,
+            config: RwLock::new(Arc::new(config)),
+            shutdown: watch::Sender::new(false),
+            rate_limiter: RateLimiter::default(),
+        })
+    }
+
+    /// 在 `TALK_WALL_TEST_POSTGRES_URL` 指向的库里建一个临时 schema。
+    async fn postgres_schema() -> (String, PgPool, String) {
+        let url = std::env::var(TEST_POSTGRES_URL)
+            .unwrap_or_else(|_| panic!("postgres 用例需要设置 {TEST_POSTGRES_URL}"));
+        let admin = PgPool::connect(&url).await.expect("connect to postgres");
+        let schema = format!("talk_wall_test_{}", Uuid::new_v4().simple());
+        sqlx::query(&format!("CREATE SCHEMA {schema}"))
+            .execute(&admin)
+            .await
+            .expect("create test schema");
+        (url, admin, schema)
+    }
+
+    macro_rules! repository_tests {
+        ($($name:ident),* $(,)?) => {
+            mod sqlite {
+                $(
+                    #[tokio::test]
+                    async fn $name() {
+                        let database = super::TestDatabase::sqlite().await;
+                        super::$name(database.repo.as_ref()).await;
+                        database.finish().await;
+                    }
+                )*
+            }
+
+            mod postgres {
+                $(
+                    #[tokio::test]
+                    #[ignore = "需要 TALK_WALL_TEST_POSTGRES_URL，使用 --ignored 运行"]
+                    async fn $name() {
+                        let database = super::TestDatabase::postgres().await;
+                        super::$name(database.repo.as_ref()).await;
+                        database.finish().await;
+                    }
+                )*
+            }
+        };
+    }
+
+    repository_tests!(
+        schema_is_complete,
+        users_and_sessions,
+        feed_lists_tags_pins_and_hidden_posts,
+        comments_refresh_counters_and_notify,
+        scheduled_posts_publish,
+        polls_count_votes,
+        tags_merge,
+        categories_round_trip,
+        account_erasure,
+        moderation_and_audit,
+        notifications_skip_hidden_content,
+    );
+
+    async fn create_user(repo: &dyn Repository, username: &str) -> i64 {
+        repo.create_user(
+            &Uuid::new_v4().to_string(),
+            username,
+            "12345",
+            "hash",
+            &now_iso(),
+        )
+        .await
+        .expect("create user");
+        repo.find_login_user(username)
+            .await
+            .expect("find user")
+            .expect("user exists")
+            .id
+    }
+
+    fn new_post(user_id: i64, title: &str, tags: &[&str]) -> NewPost {
+        NewPost {
+            user_id,
+            title: title.to_owned(),
+            content: format!("{title} content"),
+            category: "其它".to_owned(),
+            anonymous: false,
+            status: PostStatus::Published,
+            publish_at: None,
+            expires_at: None,
+            tags: tags.iter().map(|tag| (*tag).to_owned()).collect(),
+            poll: None,
+        }
+    }
+
+    fn feed(viewer_id: i64) -> PostFeed {
+        PostFeed {
+            viewer_id,
+            category: None,
+            following_only: false,
+            tag: None,
+            sort: PostSort::New,
+            full: true,
+            limit: 20,
+            offset: 0,
+        }
+    }
+
+    fn post_ids(posts: &[DbPost]) -> Vec<i64> {
+        posts.iter().map(|post| post.id).collect()
+    }
+
+    fn sorted_tags(tags: Option<String>) -> Vec<String> {
+        let mut tags: Vec<String> = tags
+            .unwrap_or_default()
+            .split(',')
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect();
+        tags.sort();
+        tags
+    }
+
+    async fn schema_is_complete(repo: &dyn Repository) {
+        let columns = repo.schema_columns().await.unwrap();
+        assert!(missing_schema(&columns).is_empty());
+    }
+
+    async fn users_and_sessions(repo: &dyn Repository) {
+        let alice = create_user(repo, "alice").await;
+        let admin = create_user(repo, "admin").await;
+        let user = repo.find_login_user("alice").await.unwrap().unwrap();
+        assert_eq!(user.qq, "12345");
+        assert!(repo.find_user_by_uid(&user.uid).await.unwrap().is_some());
+        assert!(repo.find_login_user("nobody").await.unwrap().is_none());
+        let duplicate = repo
+            .create_user(
+                &Uuid::new_v4().to_string(),
+                "alice",
+                "1",
+                "hash",
+                &now_iso(),
+            )
+            .await
+            .unwrap_err();
+        assert!(is_unique_violation(&duplicate));
+
+        let expires_at = "9999-01-01T00:00:00Z";
+        repo.create_session("s1", alice, &now_iso(), expires_at)
+            .await
+            .unwrap();
+        repo.create_session("s2", alice, &now_iso(), expires_at)
+            .await
+            .unwrap();
+        assert_eq!(
+            repo.find_session("s1").await.unwrap().unwrap().username,
+            "alice"
+        );
+        assert_eq!(repo.count_active_sessions(&now_iso()).await.unwrap(), 2);
+        repo.delete_session("s1").await.unwrap();
+        assert!(repo.find_session("s1").await.unwrap().is_none());
+        assert_eq!(
+            repo.revoke_sessions(alice, admin, &user.uid).await.unwrap(),
+            1
+        );
+        assert_eq!(repo.count_active_sessions(&now_iso()).await.unwrap(), 0);
+
+        repo.follow(admin, alice, &now_iso()).await.unwrap();
+        let stats = repo.follow_stats(alice, admin).await.unwrap();
+        assert_eq!(stats.follower_count, 1);
+        assert!(stats.is_following);
+        repo.unfollow(admin, alice).await.unwrap();
+        assert_eq!(
+            repo.follow_stats(alice, admin)
+                .await
+                .unwrap()
+                .follower_count,
+            0
+        );
+    }
+
+    async fn feed_lists_tags_pins_and_hidden_posts(repo: &dyn Repository) {
+        let alice = create_user(repo, "alice").await;
+        let first = repo
+            .create_post(&new_post(alice, "first", &["rust", "db"]), true, &now_iso())
+            .await
+            .unwrap();
+        let second = repo
+            .create_post(&new_post(alice, "second", &["rust"]), true, &now_iso())
+            .await
+            .unwrap();
+        let third = repo
+            .create_post(&new_post(alice, "third", &[]), true, &now_iso())
+            .await
+            .unwrap();
+
+        let posts = repo.list_posts(&feed(alice)).await.unwrap();
+        assert_eq!(post_ids(&posts), vec![third, second, first]);
+        let tagged = posts.into_iter().find(|post| post.id == first).unwrap();
+        assert_eq!(sorted_tags(tagged.tags), vec!["db", "rust"]);
+
+        let rust = PostFeed {
+            tag: Some("rust".to_owned()),
+            ..feed(alice)
+        };
+        assert_eq!(
+            post_ids(&repo.list_posts(&rust).await.unwrap()),
+            vec![second, first]
+        );
+
+        assert!(repo
+            .pin_post(first, PinScope::Global, &now_iso(), None)
+            .await
+            .unwrap());
+        let posts = repo.list_posts(&feed(alice)).await.unwrap();
+        assert_eq!(post_ids(&posts), vec![first, third, second]);
+        assert!(posts[0].pinned);
+        assert!(repo.unpin_post(first).await.unwrap());
+
+        assert!(repo
+            .moderate(
+                ModerationTarget::Post,
+                ModerationAction::Hide,
+                second,
+                &now_iso()
+            )
+            .await
+            .unwrap());
+        assert_eq!(
+            post_ids(&repo.list_posts(&feed(alice)).await.unwrap()),
+            vec![third, first]
+        );
+        assert!(repo.delete_post(third).await.unwrap());
+        assert!(!repo.post_exists(third).await.unwrap());
+        assert!(!repo.delete_post(third).await.unwrap());
+    }
+
+    async fn comments_refresh_counters_and_notify(repo: &dyn Repository) {
+        let alice = create_user(repo, "alice").await;
+        let bob = create_user(repo, "bob").await;
+        let post_id = repo
+            .create_post(&new_post(alice, "post", &[]), true, &now_iso())
+            .await
+            .unwrap();
+        let comment_id = repo
+            .create_comment(post_id, bob, "hello", false, &now_iso())
+            .await
+            .unwrap();
+
+        let post = repo.find_post(post_id, alice).await.unwrap().unwrap();
+        assert_eq!(post.comment_count, 1);
+        assert!(post.last_comment_at.is_some());
+        let notifications = repo.list_notifications(alice, 20, 0).await.unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(repo.list_comments(post_id, alice).await.unwrap().len(), 1);
+        assert_eq!(
+            repo.find_subscription(bob, post_id).await.unwrap(),
+            Some(false)
+        );
+
+        assert!(repo.delete_comment(comment_id, None).await.unwrap());
+        assert!(!repo.delete_comment(comment_id, None).await.unwrap());
+        let post = repo.find_post(post_id, alice).await.unwrap().unwrap();
+        assert_eq!(post.comment_count, 0);
+        assert!(post.last_comment_at.is_none());
+    }
+
+    async fn notifications_skip_hidden_content(repo: &dyn Repository) {
+        let alice = create_user(repo, "alice").await;
+        let bob = create_user(repo, "bob").await;
+        let post_id = repo
+            .create_post(&new_post(alice, "post", &[]), true, &now_iso())
+            .await
+            .unwrap();
+        let comment_id = repo
+            .create_comment(post_id, bob, "abuse", false, &now_iso())
+            .await
+            .unwrap();
+        repo.create_comment(post_id, bob, "fine", false, &now_iso())
+            .await
+            .unwrap();
+        assert_eq!(
+            repo.list_notifications(alice, 20, 0).await.unwrap().len(),
+            2
+        );
+
+        let moderate = |target, action, id| async move {
+            assert!(repo.moderate(target, action, id, &now_iso()).await.unwrap());
+        };
+        moderate(
+            ModerationTarget::Comment,
+            ModerationAction::Hide,
+            comment_id,
+        )
+        .await;
+        let notifications = repo.list_notifications(alice, 20, 0).await.unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].comment_content.as_deref(), Some("fine"));
+
+        moderate(ModerationTarget::Post, ModerationAction::Hide, post_id).await;
+        assert!(repo
+            .list_notifications(alice, 20, 0)
+            .await
+            .unwrap()
+            .is_empty());
+        moderate(ModerationTarget::Post, ModerationAction::Unhide, post_id).await;
+        assert_eq!(
+            repo.list_notifications(alice, 20, 0).await.unwrap().len(),
+            1
+        );
+    }
+
+    async fn scheduled_posts_publish(repo: &dyn Repository) {
+        let alice = create_user(repo, "alice").await;
+        let scheduled = NewPost {
+            status: PostStatus::Draft,
+            publish_at: Some("2000-01-01T00:00:00Z".to_owned()),
+            ..new_post(alice, "scheduled", &["later"])
+        };
+        let post_id = repo
+            .create_post(&scheduled, true, &now_iso())
+            .await
+            .unwrap();
+        assert!(repo.list_posts(&feed(alice)).await.unwrap().is_empty());
+        assert_eq!(repo.list_drafts(alice).await.unwrap().len(), 1);
+
+        let now = now_iso();
+        assert_eq!(repo.due_scheduled_posts(&now).await.unwrap(), vec![post_id]);
+        assert!(repo.publish_post(post_id, &now).await.unwrap());
+        assert!(!repo.publish_post(post_id, &now).await.unwrap());
+        assert_eq!(
+            post_ids(&repo.list_posts(&feed(alice)).await.unwrap()),
+            vec![post_id]
+        );
+        assert!(repo.list_drafts(alice).await.unwrap().is_empty());
+    }
+
+    async fn polls_count_votes(repo: &dyn Repository) {
+        let alice = create_user(repo, "alice").await;
+        let bob = create_user(repo, "bob").await;
+        let post = NewPost {
+            poll: Some(NewPoll {
+                options: vec!["a".to_owned(), "b".to_owned()],
+                multiple: true,
+                anonymous_votes: true,
+                closes_at: None,
+            }),
+            ..new_post(alice, "poll", &[])
+        };
+        let post_id = repo.create_post(&post, true, &now_iso()).await.unwrap();
+        let poll = repo.find_poll(post_id).await.unwrap().unwrap();
+        assert!(poll.multiple);
+        let options = repo.list_poll_options(poll.id).await.unwrap();
+        let option_ids: Vec<i64> = options.iter().map(|option| option.id).collect();
+
+        repo.cast_vote(poll.id, alice, &option_ids, &now_iso())
+            .await
+            .unwrap();
+        repo.cast_vote(poll.id, bob, &option_ids[..1], &now_iso())
+            .await
+            .unwrap();
+        let again = repo
+            .cast_vote(poll.id, bob, &option_ids[..1], &now_iso())
+            .await
+            .unwrap_err();
+        assert!(is_unique_violation(&again));
+
+        let votes: Vec<i64> = repo
+            .list_poll_options(poll.id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|option| option.votes)
+            .collect();
+        assert_eq!(votes, vec![2, 1]);
+        assert_eq!(repo.count_poll_voters(poll.id).await.unwrap(), 2);
+        assert_eq!(
+            repo.list_poll_choices(poll.id, bob).await.unwrap(),
+            vec![option_ids[0]]
+        );
+    }
+
+    async fn tags_merge(repo: &dyn Repository) {
+        let alice = create_user(repo, "alice").await;
+        let post_id = repo
+            .create_post(&new_post(alice, "post", &["db", "rust"]), true, &now_iso())
+            .await
+            .unwrap();
+        let rejected = repo
+            .create_post(&new_post(alice, "post", &["unknown"]), false, &now_iso())
+            .await;
+        assert!(rejected.is_err());
+
+        let source = repo.find_tag("db").await.unwrap().unwrap();
+        let target = repo.find_tag("rust").await.unwrap().unwrap();
+        repo.merge_tag(source.id, target.id).await.unwrap();
+        assert_eq!(repo.resolve_tag("db").await.unwrap().unwrap().id, target.id);
+        let post = repo.find_post(post_id, alice).await.unwrap().unwrap();
+        assert_eq!(sorted_tags(post.tags), vec!["rust"]);
+
+        assert!(repo.set_tag_banned("rust", true).await.unwrap());
+        let post = repo.find_post(post_id, alice).await.unwrap().unwrap();
+        assert!(sorted_tags(post.tags).is_empty());
+        assert!(!repo.set_tag_banned("missing", true).await.unwrap());
+    }
+
+    async fn categories_round_trip(repo: &dyn Repository) {
+        let seeded = repo.list_categories(true).await.unwrap();
+        assert_eq!(seeded.len(), SEED_CATEGORIES.len());
+
+        let mut category = DbCategory {
+            slug: "news".to_owned(),
+            name: "新闻".to_owned(),
+            description: String::new(),
+            sort_order: 9,
+            archived: false,
+            post_permission: CategoryPermission::Everyone,
+            post_lifetime_days: Some(3),
+        };
+        repo.create_category(&category, &now_iso()).await.unwrap();
+        category.archived = true;
+        repo.update_category(&category).await.unwrap();
+        let stored = repo.find_category("news").await.unwrap().unwrap();
+        assert!(stored.archived);
+        assert_eq!(stored.post_lifetime_days, Some(3));
+        assert_eq!(
+            repo.list_categories(false).await.unwrap().len(),
+            SEED_CATEGORIES.len()
+        );
+
+        let alice = create_user(repo, "alice").await;
+        let post = NewPost {
+            category: "news".to_owned(),
+            ..new_post(alice, "news", &[])
+        };
+        let post_id = repo.create_post(&post, true, &now_iso()).await.unwrap();
+        assert!(repo.category_in_use("news").await.unwrap());
+        assert!(repo.move_post(post_id, "其它").await.unwrap());
+        assert!(!repo.category_in_use("news").await.unwrap());
+        assert!(repo.delete_category("news").await.unwrap());
+        assert!(repo.find_category("news").await.unwrap().is_none());
+    }
+
+    async fn account_erasure(repo: &dyn Repository) {
+        let alice = create_user(repo, "alice").await;
+        let bob = create_user(repo, "bob").await;
+        let alice_post = repo
+            .create_post(&new_post(alice, "alice", &[]), true, &now_iso())
+            .await
+            .unwrap();
+        let bob_post = repo
+            .create_post(&new_post(bob, "bob", &[]), true, &now_iso())
+            .await
+            .unwrap();
+        repo.create_comment(alice_post, bob, "hi", false, &now_iso())
+            .await
+            .unwrap();
+        repo.create_comment(bob_post, alice, "hi", false, &now_iso())
+            .await
+            .unwrap();
+
+        repo.request_account_deletion(
+            bob,
+            DeletionMode::Delete,
+            Some("2000-01-01T00:00:00Z"),
+            &now_iso(),
+        )
+        .await
+        .unwrap();
+        let due = repo.due_account_deletions(&now_iso()).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0, bob);
+        repo.erase_account(bob, DeletionMode::Delete, &now_iso())
+            .await
+            .unwrap();
+        assert!(repo.find_login_user("bob").await.unwrap().is_none());
+        assert!(!repo.post_exists(bob_post).await.unwrap());
+        let post = repo.find_post(alice_post, alice).await.unwrap().unwrap();
+        assert_eq!(post.comment_count, 0);
+
+        repo.erase_account(alice, DeletionMode::Anonymize, &now_iso())
+            .await
+            .unwrap();
+        assert!(repo.find_login_user("alice").await.unwrap().is_none());
+        let post = repo.find_post(alice_post, alice).await.unwrap().unwrap();
+        assert!(post.is_anonymous);
+    }
+
+    async fn moderation_and_audit(repo: &dyn Repository) {
+        let admin = create_user(repo, "admin").await;
+        let alice = create_user(repo, "alice").await;
+        let post_id = repo
+            .create_post(&new_post(alice, "spam offer", &[]), true, &now_iso())
+            .await
+            .unwrap();
+        let comment_id = repo
+            .create_comment(post_id, alice, "SPAM here", false, &now_iso())
+            .await
+            .unwrap();
+
+        let filter = ModerationFilter {
+            user_id: Some(alice),
+            keyword: Some("spam".to_owned()),
+            since: None,
+            until: None,
+        };
+        assert_eq!(
+            repo.find_moderation_targets(ModerationTarget::Comment, &filter)
+                .await
+                .unwrap(),
+            vec![comment_id]
+        );
+        assert_eq!(
+            repo.find_moderation_targets(ModerationTarget::Post, &filter)
+                .await
+                .unwrap(),
+            vec![post_id]
+        );
+
+        let audit = NewAuditEntry {
+            admin_id: admin,
+            action: AUDIT_REVEAL_COMMENT_AUTHOR,
+            target_type: ModerationTarget::Comment.as_str(),
+            target_id: &comment_id.to_string(),
+            detail: Some("check"),
+        };
+        let author = repo
+            .reveal_author(ModerationTarget::Comment, comment_id, &audit)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(author.username, "alice");
+        assert!(repo
+            .reveal_author(ModerationTarget::Comment, comment_id + 100, &audit)
+            .await
+            .unwrap()
+            .is_none());
+
+        assert!(repo
+            .moderate(
+                ModerationTarget::Comment,
+                ModerationAction::Hide,
+                comment_id,
+                &now_iso()
+            )
+            .await
+            .unwrap());
+        assert_eq!(
+            repo.find_post(post_id, admin)
+                .await
+                .unwrap()
+                .unwrap()
+                .comment_count,
+            0
+        );
+        assert!(!repo
+            .moderate(
+                ModerationTarget::Post,
+                ModerationAction::Delete,
+                post_id + 100,
+                &now_iso()
+            )
+            .await
+            .unwrap());
+
+        let entries = repo.list_audit(20, 0).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, AUDIT_REVEAL_COMMENT_AUTHOR);
+        assert_eq!(entries[0].admin_username.as_deref(), Some("admin"));
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 TALK_WALL_TEST_POSTGRES_URL，使用 --ignored 运行"]
+    async fn migrate_to_postgres_copies_every_table() {
+        let source = TestDatabase::sqlite().await;
+        let alice = create_user(source.repo.as_ref(), "alice").await;
+        let bob = create_user(source.repo.as_ref(), "bob").await;
+        let post_id = source
+            .repo
+            .create_post(&new_post(alice, "post", &["rust"]), true, &now_iso())
+            .await
+            .unwrap();
+        source
+            .repo
+            .create_comment(post_id, bob, "hello", false, &now_iso())
+            .await
+            .unwrap();
+        source.repo.close().await;
+
+        let Cleanup::File(path) = &source.cleanup else {
+            unreachable!("sqlite 测试库总是文件");
+        };
+        let mut config = default_config();
+        config.database.path = path.clone();
+        let (url, admin, schema) = postgres_schema().await;
+        let target_url = format!("{url}?options=-c%20search_path%3D{schema}");
+        migrate_to_postgres(&config, Some(target_url.clone()))
+            .await
+            .expect("migrate into empty schema");
+
+        let target = TestDatabase {
+            repo: Arc::new(
+                PostgresRepository::open(PgConnectOptions::from_str(&target_url).unwrap(), 1)
+                    .await
+                    .unwrap(),
+            ),
+            cleanup: Cleanup::Schema(admin, schema),
+        };
+        let repo = target.repo.as_ref();
+        let post = repo.find_post(post_id, alice).await.unwrap().unwrap();
+        assert_eq!(post.title, "post");
+        assert_eq!(post.comment_count, 1);
+        assert_eq!(sorted_tags(post.tags), vec!["rust"]);
+        assert_eq!(repo.list_comments(post_id, alice).await.unwrap().len(), 1);
+        assert_eq!(
+            repo.list_notifications(alice, 20, 0).await.unwrap().len(),
+            1
+        );
+        assert!(create_user(repo, "carol").await > bob);
+
+        let again = migrate_to_postgres(&config, Some(target_url)).await;
+        assert!(again.unwrap_err().to_string().contains("已有数据"));
+
+        let mut lock = path.clone().into_os_string();
+        lock.push(".lock");
+        let _ = fs::remove_file(lock);
+        source.finish().await;
+        target.finish().await;
+    }
+
+    fn readiness_check<'a>(response: &'a ReadinessResponse, name: &str) -> &'a ReadinessCheck {
+        response
+            .checks
+            .iter()
+            .find(|check| check.name == name)
+            .expect("check present")
+    }
+
+    #[test]
+    fn missing_schema_lists_absent_columns() {
+        let mut columns: Vec<(String, String)> = REQUIRED_SCHEMA
+            .iter()
+            .flat_map(|(table, required)| {
+                required
+                    .iter()
+                    .map(move |column| ((*table).to_owned(), (*column).to_owned()))
+            })
+            .collect();
+        assert!(missing_schema(&columns).is_empty());
+
+        columns.retain(|(table, column)| {
+            !(table == "users" && column == "banned_at") && table != "admin_audit_log"
+        });
+        let missing = missing_schema(&columns);
+        assert!(missing.contains(&"users.banned_at".to_owned()));
+        assert!(missing.contains(&"admin_audit_log.id".to_owned()));
+        assert!(!missing.iter().any(|entry| entry.starts_with("posts.")));
+    }
+
+    #[tokio::test]
+    async fn readyz_checks_schema_and_shutdown() {
+        let database = TestDatabase::sqlite().await;
+        let state = test_state(database.repo.clone(), default_config());
+
+        let (_, Json(response)) = readyz(State(state.clone())).await;
+        assert!(readiness_check(&response, "database").ok);
+        assert!(readiness_check(&response, "migrations").ok);
+        assert!(readiness_check(&response, "shutdown").ok);
+
+        state.shutdown.send_replace(true);
+        let (status, Json(response)) = readyz(State(state.clone())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.status, "unavailable");
+        assert!(!readiness_check(&response, "shutdown").ok);
+
+        let Cleanup::File(path) = &database.cleanup else {
+            unreachable!("sqlite 测试库总是文件");
+        };
+        let pool = connect_database(&DatabaseSection {
+            path: path.clone(),
+            ..DatabaseSection::default()
+        })
+        .await
+        .unwrap();
+        sqlx::query("ALTER TABLE users DROP COLUMN banned_at")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        let (status, Json(response)) = readyz(State(state)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let migrations = readiness_check(&response, "migrations");
+        assert!(!migrations.ok);
+        assert!(migrations
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("users.banned_at"));
+        database.finish().await;
+    }
+
+    fn metrics_state(state: SharedState) -> MetricsState {
+        MetricsState {
+            app: state,
+            handle: PrometheusBuilder::new().build_recorder().handle(),
+        }
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {token}").parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn metrics_config_requires_token_or_addr() {
+        let mut config = default_config();
+        config.metrics.enabled = true;
+        assert!(config.validate().is_err());
+        config.metrics.token = Some("  ".into());
+        assert!(config.validate().is_err());
+        config.metrics.token = Some("secret".into());
+        assert!(config.validate().is_ok());
+        config.metrics.token = None;
+        config.metrics.addr = Some("127.0.0.1:9090".into());
+        assert!(config.validate().is_ok());
+        config.metrics.addr = Some("not-an-addr".into());
+        assert!(config.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn metrics_endpoint_checks_token() {
+        let database = TestDatabase::sqlite().await;
+        let mut config = default_config();
+        config.metrics.enabled = true;
+        config.metrics.token = Some("secret".into());
+        let state = metrics_state(test_state(database.repo.clone(), config));
+
+        for headers in [HeaderMap::new(), bearer("wrong"), bearer("secre")] {
+            let result = render_metrics(State(state.clone()), headers).await;
+            assert!(matches!(result, Err(ApiError::Unauthorized)));
+        }
+        let response = render_metrics(State(state), bearer("secret"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        database.finish().await;
+    }
+
+    #[tokio::test]
+    async fn metrics_endpoint_on_separate_addr_skips_token() {
+        let database = TestDatabase::sqlite().await;
+        let mut config = default_config();
+        config.metrics.enabled = true;
+        config.metrics.addr = Some("127.0.0.1:9090".into());
+        let state = metrics_state(test_state(database.repo.clone(), config));
+
+        let response = render_metrics(State(state), HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        database.finish().await;
+    }
+
+    #[test]
+    fn rate_limiter_counts_per_key_and_can_be_disabled() {
+        let limiter = RateLimiter::default();
+        let config = RateLimitSection {
+            comments_per_minute: 2,
+            posts_per_hour: 0,
+            ..RateLimitSection::default()
+        };
+        for _ in 0..2 {
+            assert!(limiter
+                .check(&config, RateLimitAction::Comment, "alice")
+                .is_ok());
+        }
+        assert!(matches!(
+            limiter.check(&config, RateLimitAction::Comment, "alice"),
+            Err(ApiError::TooManyRequests)
+        ));
+        assert!(limiter
+            .check(&config, RateLimitAction::Comment, "bob")
+            .is_ok());
+        for _ in 0..100 {
+            assert!(limiter
+                .check(&config, RateLimitAction::Post, "alice")
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn client_ip_trusts_forwarded_header_only_from_loopback() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.1.1.1, 203.0.113.7".parse().unwrap());
+        let proxy: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+        let direct: SocketAddr = "198.51.100.2:40000".parse().unwrap();
+        assert_eq!(
+            client_ip(proxy, &headers),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(client_ip(direct, &headers), direct.ip());
+        assert_eq!(client_ip(proxy, &HeaderMap::new()), proxy.ip());
+    }
+
+    #[tokio::test]
+    async fn login_limit_does_not_lock_out_other_clients() {
+        let database = TestDatabase::sqlite().await;
+        let mut config = default_config();
+        config.rate_limit.login_attempts_per_account_per_minute = 2;
+        let state = test_state(database.repo.clone(), config);
+        let attempt = |ip: &'static str| {
+            let state = state.clone();
+            async move {
+                login(
+                    State(state),
+                    ConnectInfo(format!("{ip}:40000").parse().unwrap()),
+                    HeaderMap::new(),
+                    Cookies::default(),
+                    ApiJson(LoginPayload {
+                        username: "alice".into(),
+                        password: "wrong".into(),
+                    }),
+                )
+                .await
+            }
+        };
+
+        for _ in 0..2 {
+            assert!(matches!(
+                attempt("198.51.100.2").await,
+                Err(ApiError::Unauthorized)
+            ));
+        }
+        assert!(matches!(
+            attempt("198.51.100.2").await,
+            Err(ApiError::TooManyRequests)
+        ));
+        assert!(matches!(
+            attempt("198.51.100.3").await,
+            Err(ApiError::Unauthorized)
+        ));
+        database.finish().await;
+    }
+
+    async fn error_body(error: ApiError, locale: Locale) -> (StatusCode, serde_json::Value) {
+        let context = RequestContext {
+            request_id: "req-1".into(),
+            locale,
+        };
+        let response = REQUEST_CONTEXT
+            .scope(context, async { error.into_response() })
+            .await;
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn accept_language_picks_best_supported_locale() {
+        assert_eq!(Locale::from_accept_language("en-US,en;q=0.9"), Locale::En);
+        assert_eq!(Locale::from_accept_language("zh-CN,zh;q=0.9"), Locale::Zh);
+        assert_eq!(
+            Locale::from_accept_language("zh;q=0.5, en;q=0.8"),
+            Locale::En
+        );
+        assert_eq!(Locale::from_accept_language("fr-FR, de"), Locale::Zh);
+        assert_eq!(Locale::from_accept_language("en;q=0"), Locale::Zh);
+        assert_eq!(Locale::from_accept_language(""), Locale::Zh);
+    }
+
+    #[tokio::test]
+    async fn validation_errors_list_every_field() {
+        let database = TestDatabase::sqlite().await;
+        let error = super::create_user(database.repo.as_ref(), " ", "12345", "123")
+            .await
+            .unwrap_err();
+        let (status, body) = error_body(error, Locale::En).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "request.invalid");
+        assert_eq!(body["request_id"], "req-1");
+        assert_eq!(body["details"][0]["field"], "username");
+        assert_eq!(body["details"][0]["code"], "user.username_empty");
+        assert_eq!(body["details"][0]["message"], "Username must not be empty");
+        assert_eq!(body["details"][1]["field"], "password");
+        assert_eq!(body["details"][1]["code"], "user.password_too_short");
+
+        let error = validate_post_text("", "content").unwrap_err();
+        let (_, body) = error_body(error, Locale::Zh).await;
+        assert_eq!(body["code"], "post.title_empty");
+        assert_eq!(body["message"], "标题不能为空");
+        assert_eq!(body["details"][0]["field"], "title");
+        database.finish().await;
+    }
+
+    #[tokio::test]
+    async fn conflicts_and_fixed_errors_are_coded_and_localized() {
+        let database = TestDatabase::sqlite().await;
+        super::create_user(database.repo.as_ref(), "alice", "12345", "secret1")
+            .await
+            .unwrap();
+        let error = super::create_user(database.repo.as_ref(), "alice", "12345", "secret1")
+            .await
+            .unwrap_err();
+        let (status, body) = error_body(error, Locale::En).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "user.username_taken");
+        assert_eq!(body["message"], "Username is already taken");
+        assert!(body.get("details").is_none());
+
+        let (status, body) = error_body(ApiError::NotFound, Locale::En).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "resource.not_found");
+        assert_eq!(body["message"], "Resource not found");
+
+        let (status, body) = error_body(ApiError::TooManyRequests, Locale::Zh).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["code"], "rate.limited");
+        database.finish().await;
+    }
+
+    #[tokio::test]
+    async fn internal_errors_hide_their_cause() {
+        let error = ApiError::Database(sqlx::Error::RowNotFound);
+        let (status, body) = error_body(error, Locale::Zh).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "server.internal");
+        assert_eq!(body["message"], "服务器内部错误，请稍后重试");
+
+        let error = ApiError::Internal("disk full at /var/lib".into());
+        let (_, body) = error_body(error, Locale::En).await;
+        assert!(!body["message"].as_str().unwrap().contains("disk"));
+    }
+
+    async fn rejected(request: Request) -> (StatusCode, serde_json::Value) {
+        let app = Router::new()
+            .route(
+                "/posts",
+                post(|ApiJson(_): ApiJson<CreatePostPayload>| async { StatusCode::OK })
+                    .get(|ApiQuery(_): ApiQuery<PageQuery>| async { StatusCode::OK }),
+            )
+            .route(
+                "/posts/:post_id",
+                get(|ApiPath(_): ApiPath<i64>| async { StatusCode::OK }),
+            )
+            .layer(middleware::from_fn(scope_request_context));
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn json_request(body: &str) -> Request {
+        Request::post("/posts")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_owned()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn json_body_rejections_are_coded() {
+        let request = Request::post("/posts")
+            .body(Body::from(r#"{"title":"t","content":"c"}"#))
+            .unwrap();
+        let (status, body) = rejected(request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "request.content_type");
+
+        let (_, body) = rejected(json_request("{")).await;
+        assert_eq!(body["code"], "request.body_invalid");
+
+        let mut request = json_request(r#"{"content":"c"}"#);
+        request
+            .headers_mut()
+            .insert(header::ACCEPT_LANGUAGE, "en".parse().unwrap());
+        let (_, body) = rejected(request).await;
+        assert_eq!(body["code"], "request.field_missing");
+        assert_eq!(body["message"], "Missing field title");
+        assert_eq!(body["details"][0]["field"], "title");
+
+        let (_, body) = rejected(json_request(
+            r#"{"title":"t","content":"c","poll":{"options":[1]}}"#,
+        ))
+        .await;
+        assert_eq!(body["code"], "request.field_invalid");
+        assert_eq!(body["details"][0]["field"], "poll.options[0]");
+    }
+
+    #[tokio::test]
+    async fn query_and_path_rejections_are_coded() {
+        let request = Request::get("/posts?page=abc").body(Body::empty()).unwrap();
+        let (status, body) = rejected(request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "request.field_invalid");
+        assert_eq!(body["details"][0]["field"], "page");
+
+        let request = Request::get("/posts/abc").body(Body::empty()).unwrap();
+        let (status, body) = rejected(request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "request.path_invalid");
+    }
+
+    #[test]
+    fn viewer_sees_qq_according_to_privacy() {
+        let viewer = Viewer { id: 1 };
+        assert!(viewer.can_see_qq(1, QqVisibility::Nobody, false));
+        assert!(viewer.can_see_qq(2, QqVisibility::Everyone, false));
+        assert!(viewer.can_see_qq(2, QqVisibility::Following, true));
+        assert!(!viewer.can_see_qq(2, QqVisibility::Following, false));
+        assert!(!viewer.can_see_qq(2, QqVisibility::Nobody, true));
+
+        let author = |visibility| {
+            viewer.author_info(
+                2,
+                Some("alice".into()),
+                Some("12345".into()),
+                Some("uid-2".into()),
+                visibility,
+                false,
+            )
+        };
+        let visible = author(Some(QqVisibility::Everyone)).unwrap();
+        assert_eq!(visible.qq.as_deref(), Some("12345"));
+        assert!(author(Some(QqVisibility::Nobody)).unwrap().qq.is_none());
+        assert!(author(None).is_none());
+    }
+
+    async fn login_as(repo: &dyn Repository, user_id: i64) -> Cookies {
+        let token = Uuid::new_v4().to_string();
+        repo.create_session(&token, user_id, &now_iso(), "9999-01-01T00:00:00Z")
+            .await
+            .unwrap();
+        let cookies = Cookies::default();
+        cookies.add(Cookie::new(SESSION_COOKIE, token));
+        cookies
+    }
+
+    async fn profile(
+        state: &SharedState,
+        cookies: Cookies,
+        uid: &str,
+    ) -> ApiResult<Json<UserProfileResponse>> {
+        get_user_profile(
+            State(state.clone()),
+            cookies,
+            ApiPath(uid.to_owned()),
+            ApiQuery(ContentQuery { full: None }),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn profile_respects_privacy_settings() {
+        let database = TestDatabase::sqlite().await;
+        let repo = database.repo.as_ref();
+        let state = test_state(database.repo.clone(), default_config());
+        let alice = create_user(repo, "alice").await;
+        let bob = create_user(repo, "bob").await;
+        let alice_uid = repo.find_login_user("alice").await.unwrap().unwrap().uid;
+        repo.create_post(&new_post(alice, "hello", &[]), true, &now_iso())
+            .await
+            .unwrap();
+        let privacy = |qq_visibility, profile_hidden, posts_hidden| DbPrivacy {
+            qq_visibility,
+            profile_hidden,
+            posts_hidden,
+        };
+
+        let Json(seen) = profile(&state, login_as(repo, bob).await, &alice_uid)
+            .await
+            .unwrap();
+        assert_eq!(seen.qq.as_deref(), Some("12345"));
+        assert_eq!(seen.posts.len(), 1);
+        let author = seen.posts[0].author.as_ref().unwrap();
+        assert_eq!(author.qq.as_deref(), Some("12345"));
+
+        repo.update_privacy(alice, &privacy(QqVisibility::Following, false, true))
+            .await
+            .unwrap();
+        let Json(seen) = profile(&state, login_as(repo, bob).await, &alice_uid)
+            .await
+            .unwrap();
+        assert!(seen.qq.is_none());
+        assert!(seen.posts_hidden);
+        assert!(seen.posts.is_empty());
+
+        repo.follow(alice, bob, &now_iso()).await.unwrap();
+        let Json(seen) = profile(&state, login_as(repo, bob).await, &alice_uid)
+            .await
+            .unwrap();
+        assert_eq!(seen.qq.as_deref(), Some("12345"));
+
+        repo.update_privacy(alice, &privacy(QqVisibility::Nobody, true, true))
+            .await
+            .unwrap();
+        assert!(matches!(
+            profile(&state, login_as(repo, bob).await, &alice_uid).await,
+            Err(ApiError::Forbidden)
+        ));
+        let Json(own) = profile(&state, login_as(repo, alice).await, &alice_uid)
+            .await
+            .unwrap();
+        assert_eq!(own.qq.as_deref(), Some("12345"));
+        assert!(!own.posts_hidden);
+        assert_eq!(own.posts.len(), 1);
+        database.finish().await;
+    }
+
+    #[test]
+    fn poll_must_close_after_scheduled_publish() {
+        let later = |hours| format_time(OffsetDateTime::now_utc() + Duration::hours(hours));
+        let poll = |closes_at: String| CreatePollPayload {
+            options: vec!["一食堂".into(), "二食堂".into()],
+            multiple: None,
+            closes_at: Some(closes_at),
+            anonymous_votes: None,
+        };
+        let publish_at = later(2);
+        let code = |result: Result<NewPoll, ApiError>| match result {
+            Err(ApiError::Validation(details)) => details[0].code,
+            _ => panic!("expected a validation error"),
+        };
+
+        assert_eq!(
+            code(validate_poll(poll(later(1)), Some(&publish_at))),
+            "poll.closes_at_before_publish"
+        );
+        assert_eq!(
+            code(validate_poll(poll(publish_at.clone()), Some(&publish_at))),
+            "poll.closes_at_before_publish"
+        );
+        assert!(validate_poll(poll(later(1)), None).is_ok());
+        let scheduled = validate_poll(poll(later(3)), Some(&publish_at)).unwrap();
+        assert!(scheduled.closes_at.is_some());
+    }
+
+    #[test]
+    fn hot_score_treats_unparsable_time_as_now() {
+        let now = hot_score(&now_iso(), 10);
+        let broken = hot_score("not a time", 10);
+        assert!((broken - now).abs() < 0.01);
+        assert!(hot_score("1970-01-01T00:00:00Z", 10) < now);
+    }
+
+    #[test]
+    fn env_values_follow_the_type_of_the_default() {
+        let string = toml::Value::String("0.0.0.0:8080".into());
+        let integer = toml::Value::Integer(30);
+        let boolean = toml::Value::Boolean(false);
+        let array = toml::Value::Array(Vec::new());
+        let parse = |raw: &str, expected| parse_env_value(raw.to_owned(), expected);
+
+        assert_eq!(
+            parse("8080", Some(&string)),
+            toml::Value::String("8080".into())
+        );
+        assert_eq!(
+            parse("'a b'", Some(&string)),
+            toml::Value::String("a b".into())
+        );
+        assert_eq!(parse("30", Some(&integer)), toml::Value::Integer(30));
+        assert_eq!(parse("true", Some(&boolean)), toml::Value::Boolean(true));
+        assert_eq!(
+            parse(r#"["u1", "u2"]"#, Some(&array)),
+            toml::Value::Array(vec!["u1".into(), "u2".into()])
+        );
+        assert_eq!(
+            parse("abc", Some(&integer)),
+            toml::Value::String("abc".into())
+        );
+        assert_eq!(parse("12", None), toml::Value::String("12".into()));
+        assert_eq!(parse("[1]", None), toml::Value::Array(vec![1.into()]));
+    }
+
+    /// 环境变量是进程级的，读写 `TALK_WALL__*` 或加载配置的用例要先拿这把锁。
+    static CONFIG_ENV: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn env_overrides_layer_over_the_file_and_name_bad_keys() {
+        let _env = CONFIG_ENV.lock().unwrap();
+        let path = std::env::temp_dir().join(format!("talk-wall-{}.toml", Uuid::new_v4()));
+        fs::write(&path, "[server]\nshutdown_timeout_secs = 5\n").unwrap();
+        let vars = [
+            ("TALK_WALL__SERVER__SHUTDOWN_TIMEOUT_SECS", "9"),
+            ("TALK_WALL__ADMINS__UIDS", r#"["u1"]"#),
+            ("TALK_WALL__TAGS__ALLOW_NEW", "false"),
+        ];
+        for (name, value) in vars {
+            env::set_var(name, value);
+        }
+
+        let config = load_config(&path, true).unwrap();
+        assert_eq!(config.server.shutdown_timeout_secs, 9);
+        assert_eq!(config.admins.uids, vec!["u1".to_owned()]);
+        assert!(!config.tags.allow_new);
+
+        env::set_var("TALK_WALL__SERVER__SHUTDOWN_TIMEOUT_SECS", "soon");
+        let Err(error) = load_config(&path, true) else {
+            panic!("expected a config error");
+        };
+        let error = error.downcast_ref::<ConfigError>().expect("config error");
+        assert_eq!(error.key, "server.shutdown_timeout_secs");
+
+        for (name, _) in vars {
+            env::remove_var(name);
+        }
+        fs::remove_file(&path).unwrap();
+        assert!(load_config(&path, true).is_err());
+        assert!(load_config(&path, false).is_ok());
+    }
+
+    #[test]
+    fn config_changes_report_removed_options() {
+        let mut before = default_config();
+        before.metrics.token = Some("secret".into());
+        before.metrics.addr = Some("127.0.0.1:9000".into());
+        let mut after = default_config();
+        after.metrics.addr = Some("127.0.0.1:9001".into());
+
+        assert_eq!(
+            config_changes(&before, &after),
+            vec![
+                (
+                    "metrics.addr".to_owned(),
+                    "\"127.0.0.1:9000\"".to_owned(),
+                    "\"127.0.0.1:9001\"".to_owned(),
+                ),
+                (
+                    "metrics.token".to_owned(),
+                    "(已隐藏)".to_owned(),
+                    "(未设置)".to_owned(),
+                ),
+            ]
+        );
+        assert!(config_changes(&after, &after).is_empty());
+    }
+
+    #[tokio::test]
+    async fn reload_applies_runtime_sections_and_keeps_restart_sections() {
+        let database = TestDatabase::sqlite().await;
+        let path = std::env::temp_dir().join(format!("talk-wall-{}.toml", Uuid::new_v4()));
+        {
+            let _env = CONFIG_ENV.lock().unwrap_or_else(PoisonError::into_inner);
+            fs::write(&path, "[server]\naddr = \"127.0.0.1:3000\"\n").unwrap();
+            let state = test_state(database.repo.clone(), load_config(&path, true).unwrap());
+
+            fs::write(&path, "[server]\naddr = \"127.0.0.1:4000\"\n").unwrap();
+            reload_config(&state, &path, true);
+            assert_eq!(state.config().server.addr, "127.0.0.1:3000");
+
+            fs::write(
+                &path,
+                "[server]\naddr = \"127.0.0.1:4000\"\n[admins]\nuids = [\"u1\"]\n",
+            )
+            .unwrap();
+            reload_config(&state, &path, true);
+            let config = state.config();
+            assert_eq!(config.admins.uids, vec!["u1".to_owned()]);
+            assert_eq!(config.server.addr, "127.0.0.1:3000");
+
+            fs::write(&path, "[admins]\nuids = 1\n").unwrap();
+            reload_config(&state, &path, true);
+            assert_eq!(state.config().admins.uids, vec!["u1".to_owned()]);
+        }
+
+        fs::remove_file(&path).unwrap();
+        database.finish().await;
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_while_the_database_is_locked() {
+        let dir = std::env::temp_dir().join(format!("talk-wall-restore-{}", Uuid::new_v4()));
+        let mut config = default_config();
+        config.database.path = dir.join("talk_wall.db");
+        config.backup.dir = dir.join("backups");
+        fs::create_dir_all(&dir).unwrap();
+
+        let repo = SqliteRepository::connect(&config.database).await.unwrap();
+        create_user(&repo, "alice").await;
+        let snapshot = create_backup(&repo, &config.backup).await.unwrap();
+        create_user(&repo, "bob").await;
+        repo.close().await;
+
+        let lock = lock_database(&config.database).unwrap();
+        let error = restore_backup(&config, FsPath::new(&snapshot.file))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("请先停止服务"));
+        drop(lock);
+
+        restore_backup(&config, FsPath::new(&snapshot.file))
+            .await
+            .unwrap();
+        let repo = SqliteRepository::connect(&config.database).await.unwrap();
+        assert!(repo.find_login_user("alice").await.unwrap().is_some());
+        assert!(repo.find_login_user("bob").await.unwrap().is_none());
+        repo.close().await;
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}